@@ -27,17 +27,19 @@ impl Clean {
         engine: cross::docker::Engine,
         msg_info: &mut MessageInfo,
     ) -> cross::Result<()> {
-        let tempdir = cross::temp::dir()?;
-        match self.execute {
-            true => {
-                if tempdir.exists() {
-                    fs::remove_dir_all(tempdir)?;
+        // the extracted image tarballs of the OCI runtimes are only a cache.
+        for dir in [cross::temp::dir()?, cross::docker::oci_archives_dir()?] {
+            match self.execute {
+                true => {
+                    if dir.exists() {
+                        fs::remove_dir_all(dir)?;
+                    }
                 }
+                false => msg_info.print(format_args!(
+                    "fs::remove_dir_all({})",
+                    cross::pretty_path(&dir, |_| false)
+                ))?,
             }
-            false => msg_info.print(format_args!(
-                "fs::remove_dir_all({})",
-                cross::pretty_path(&tempdir, |_| false)
-            ))?,
         }
 
         // containers -> images -> volumes -> prune to ensure no conflicts.
@@ -128,6 +128,7 @@ impl Volumes {
         channel: Option<&Toolchain>,
         msg_info: &mut MessageInfo,
     ) -> cross::Result<()> {
+        docker::DockerVolume::check_engine(&engine)?;
         match self {
             Volumes::List(args) => args.run(engine, msg_info),
             Volumes::RemoveAll(args) => args.run(engine, msg_info),
@@ -191,12 +192,33 @@ impl RemoveAllContainers {
     }
 }
 
+#[derive(Args, Debug)]
+pub struct StopIdleContainers {
+    /// Stop sessions unused for at least this many seconds.
+    #[clap(long, default_value_t = 3600)]
+    pub timeout: u64,
+    /// Stop and remove containers. Default is a dry run.
+    #[clap(short, long)]
+    pub execute: bool,
+    /// Container engine (such as docker or podman).
+    #[clap(long)]
+    pub engine: Option<String>,
+}
+
+impl StopIdleContainers {
+    pub fn run(&self, engine: docker::Engine, msg_info: &mut MessageInfo) -> cross::Result<()> {
+        stop_idle_containers(self, &engine, msg_info)
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Containers {
     /// List cross containers in local storage.
     List(ListContainers),
     /// Stop and remove cross containers in local storage.
     RemoveAll(RemoveAllContainers),
+    /// Stop and remove idle session containers started with `--keep-alive`.
+    StopIdle(StopIdleContainers),
 }
 
 impl Containers {
@@ -204,6 +226,7 @@ impl Containers {
         match self {
             Containers::List(args) => args.run(engine, msg_info),
             Containers::RemoveAll(args) => args.run(engine, msg_info),
+            Containers::StopIdle(args) => args.run(engine, msg_info),
         }
     }
 
@@ -211,6 +234,7 @@ impl Containers {
         match self {
             Containers::List(l) => l.engine.as_deref(),
             Containers::RemoveAll(l) => l.engine.as_deref(),
+            Containers::StopIdle(l) => l.engine.as_deref(),
         }
     }
 }
@@ -455,6 +479,54 @@ pub fn remove_all_containers(
     Ok(())
 }
 
+pub fn stop_idle_containers(
+    StopIdleContainers {
+        timeout, execute, ..
+    }: &StopIdleContainers,
+    engine: &docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> cross::Result<()> {
+    let stdout = engine
+        .subcommand("ps")
+        .arg("-a")
+        .args(["--format", "{{.Names}}"])
+        .args(["--filter", &format!("label={}", docker::session_label())])
+        .run_and_get_stdout(msg_info)?;
+
+    let now = std::time::SystemTime::now();
+    let timeout = std::time::Duration::from_secs(*timeout);
+    let mut idle = vec![];
+    for name in stdout.lines().map(str::trim).filter(|s| !s.is_empty()) {
+        // sessions without a record were never used by this host, so they're idle.
+        let is_idle = match docker::session_last_used(name)? {
+            Some(last_used) => now.duration_since(last_used).unwrap_or_default() >= timeout,
+            None => true,
+        };
+        if is_idle {
+            idle.push(name);
+        }
+    }
+
+    if idle.is_empty() {
+        return Ok(());
+    }
+
+    let mut rm = engine.subcommand("rm");
+    rm.arg("--force");
+    rm.args(&idle);
+    if *execute {
+        rm.run(msg_info, false)?;
+        for name in idle {
+            docker::remove_session_record(name)?;
+        }
+    } else {
+        msg_info.note("this is a dry run. to stop the containers, pass the `--execute` flag.")?;
+        rm.print(msg_info)?;
+    }
+
+    Ok(())
+}
+
 fn toolchain_or_target(
     s: &str,
     msg_info: &mut MessageInfo,
@@ -40,12 +40,21 @@ impl Run {
             cargo_args: vec![],
             rest_args: vec![],
             subcommand: None,
+            subcommand_index: None,
             channel: None,
             target: Some(target.clone()),
             features: vec![],
             target_dir: None,
             manifest_path: None,
             version: false,
+            keep_alive: false,
+            offline_container: false,
+            test_split: None,
+            reports: vec![],
+            debug: None,
+            coverage: false,
+            install_root: None,
+            install_path: None,
             verbose: if cli.verbose { 1 } else { 0 },
             quiet: cli.quiet,
             color: cli.color.clone(),
@@ -78,7 +87,8 @@ impl Run {
 
             let image = image.to_definite_with(&engine, msg_info);
 
-            let paths = docker::DockerPaths::create(&engine, metadata, cwd, toolchain, msg_info)?;
+            let mount_finder = docker::MountFinder::create(&engine, msg_info)?;
+            let paths = docker::DockerPaths::create(mount_finder, metadata, cwd, toolchain)?;
             let options = docker::DockerOptions::new(
                 engine,
                 target,
@@ -87,6 +97,10 @@ impl Run {
                 CommandVariant::Shell,
                 None,
                 self.interactive,
+                false,
+                false,
+                docker::TestOptions::default(),
+                None,
             );
 
             let mut args = vec![String::from("-c")];
@@ -66,15 +66,18 @@ fn get_container_engine(
     docker_in_docker: bool,
     msg_info: &mut MessageInfo,
 ) -> cross::Result<docker::Engine> {
+    let in_docker = match docker_in_docker {
+        true => Some(true),
+        false => None,
+    };
+    if let Some(ssh) = docker::SshHost::from_env()? {
+        return docker::Engine::from_ssh(ssh, engine, in_docker, msg_info);
+    }
     let engine = if let Some(ce) = engine {
         which::which(ce)?
     } else {
         docker::get_container_engine()?
     };
-    let in_docker = match docker_in_docker {
-        true => Some(true),
-        false => None,
-    };
     docker::Engine::from_path(engine, in_docker, None, msg_info)
 }
 
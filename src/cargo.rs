@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus};
 
@@ -13,6 +13,8 @@ pub enum Subcommand {
     Check,
     Doc,
     Run,
+    // `cargo run` under a debug server
+    Debug,
     Rustdoc,
     Rustc,
     Test,
@@ -21,15 +23,38 @@ pub enum Subcommand {
     Metadata,
     List,
     Clean,
+    Install,
+    Miri,
+    /// A cargo subcommand allowed by `build.subcommands`.
+    External(String, ExternalSubcommand),
     Other(String),
 }
 
+/// How a cargo subcommand from `build.subcommands` is run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExternalSubcommand {
+    #[serde(default = "default_true")]
+    pub needs_docker: bool,
+    #[serde(default = "default_true")]
+    pub needs_target_in_command: bool,
+    #[serde(default)]
+    pub needs_interpreter: bool,
+    /// The crate installing the subcommand, `cargo-<name>` by default.
+    pub package: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
 impl Subcommand {
     #[must_use]
     pub fn needs_docker(self, is_remote: bool) -> bool {
         match self {
             Subcommand::Other(_) | Subcommand::List => false,
             Subcommand::Clean if !is_remote => false,
+            Subcommand::External(_, external) => external.needs_docker,
             _ => true,
         }
     }
@@ -41,12 +66,38 @@ impl Subcommand {
 
     #[must_use]
     pub fn needs_interpreter(self) -> bool {
-        matches!(self, Subcommand::Run | Subcommand::Test | Subcommand::Bench)
+        match self {
+            Subcommand::External(_, external) => external.needs_interpreter,
+            sc => matches!(
+                sc,
+                Subcommand::Run | Subcommand::Debug | Subcommand::Test | Subcommand::Bench
+            ),
+        }
     }
 
     #[must_use]
     pub fn needs_target_in_command(self) -> bool {
-        !matches!(self, Subcommand::Metadata)
+        match self {
+            Subcommand::External(_, external) => external.needs_target_in_command,
+            sc => !matches!(sc, Subcommand::Metadata),
+        }
+    }
+
+    #[must_use]
+    pub fn supports_message_format(self) -> bool {
+        matches!(
+            self,
+            Subcommand::Build
+                | Subcommand::Check
+                | Subcommand::Doc
+                | Subcommand::Run
+                | Subcommand::Debug
+                | Subcommand::Rustdoc
+                | Subcommand::Rustc
+                | Subcommand::Test
+                | Subcommand::Bench
+                | Subcommand::Clippy
+        )
     }
 }
 
@@ -56,8 +107,11 @@ impl<'a> From<&'a str> for Subcommand {
             "b" | "build" => Subcommand::Build,
             "c" | "check" => Subcommand::Check,
             "clean" => Subcommand::Clean,
+            "install" => Subcommand::Install,
+            "miri" => Subcommand::Miri,
             "doc" => Subcommand::Doc,
             "r" | "run" => Subcommand::Run,
+            "debug" => Subcommand::Debug,
             "rustc" => Subcommand::Rustc,
             "rustdoc" => Subcommand::Rustdoc,
             "t" | "test" => Subcommand::Test,
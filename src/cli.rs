@@ -2,7 +2,8 @@ use std::env;
 use std::path::{Path, PathBuf};
 
 use crate::cargo::Subcommand;
-use crate::errors::Result;
+use crate::docker::{DebugOptions, TestReport, TestSplit};
+use crate::errors::{Context, Result};
 use crate::file::{absolute_path, PathExt};
 use crate::rustc::TargetList;
 use crate::shell::{self, MessageInfo};
@@ -13,12 +14,22 @@ pub struct Args {
     pub cargo_args: Vec<String>,
     pub rest_args: Vec<String>,
     pub subcommand: Option<Subcommand>,
+    /// The index of the subcommand in `cargo_args`.
+    pub subcommand_index: Option<usize>,
     pub channel: Option<String>,
     pub target: Option<Target>,
     pub features: Vec<String>,
     pub target_dir: Option<PathBuf>,
     pub manifest_path: Option<PathBuf>,
     pub version: bool,
+    pub keep_alive: bool,
+    pub offline_container: bool,
+    pub test_split: Option<TestSplit>,
+    pub reports: Vec<TestReport>,
+    pub debug: Option<DebugOptions>,
+    pub coverage: bool,
+    pub install_root: Option<PathBuf>,
+    pub install_path: Option<PathBuf>,
     pub verbose: u8,
     pub quiet: bool,
     pub color: Option<String>,
@@ -132,6 +143,30 @@ fn parse_equal_arg<T>(
     Ok(result)
 }
 
+// parse the value of an argument only used by cross, not passed to cargo.
+fn parse_cross_arg(
+    arg: String,
+    kind: ArgKind,
+    iter: &mut impl Iterator<Item = String>,
+) -> Result<String> {
+    match kind {
+        ArgKind::Next => iter
+            .next()
+            .ok_or_else(|| eyre::eyre!("argument `{arg}` requires a value")),
+        ArgKind::Equal => {
+            let (_, value) = arg.split_once('=').expect("argument should contain `=`");
+            Ok(value.to_owned())
+        }
+    }
+}
+
+fn set_test_split(test_split: &mut Option<TestSplit>, split: TestSplit) -> Result<()> {
+    if test_split.replace(split).is_some() {
+        eyre::bail!("`--shard` and `--partition` can only be provided once");
+    }
+    Ok(())
+}
+
 fn parse_manifest_path(path: &str) -> Result<Option<PathBuf>> {
     let p = PathBuf::from(path);
     Ok(absolute_path(p).ok())
@@ -164,9 +199,18 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
     let mut manifest_path: Option<PathBuf> = None;
     let mut target_dir = None;
     let mut sc = None;
+    let mut sc_index = None;
     let mut cargo_args: Vec<String> = Vec::new();
     let mut rest_args: Vec<String> = Vec::new();
     let mut version = false;
+    let mut keep_alive = false;
+    let mut offline_container = false;
+    let mut test_split = None;
+    let mut reports = Vec::new();
+    let mut debug = DebugOptions::default();
+    let mut coverage = false;
+    let mut install_root = None;
+    let mut install_path = None;
     let mut quiet = false;
     let mut verbose = 0;
     let mut color = None;
@@ -185,6 +229,44 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
                 cargo_args.push(arg);
             } else if matches!(arg.as_str(), "--version" | "-V") {
                 version = true;
+            } else if arg == "--keep-alive" {
+                keep_alive = true;
+            } else if arg == "--offline-container" {
+                offline_container = true;
+            } else if arg == "--coverage" {
+                coverage = true;
+            } else if let Some(kind) = is_value_arg(&arg, "--shard") {
+                let value = parse_cross_arg(arg, kind, &mut args)?;
+                set_test_split(&mut test_split, TestSplit::parse_shard(&value)?)?;
+            } else if let Some(kind) = is_value_arg(&arg, "--partition") {
+                let value = parse_cross_arg(arg, kind, &mut args)?;
+                set_test_split(&mut test_split, TestSplit::parse_partition(&value)?)?;
+            } else if let Some(kind) = is_value_arg(&arg, "--report") {
+                reports.push(parse_cross_arg(arg, kind, &mut args)?.parse()?);
+            } else if let (Some(Subcommand::Debug), Some(kind)) =
+                (&sc, is_value_arg(&arg, "--port"))
+            {
+                let value = parse_cross_arg(arg, kind, &mut args)?;
+                debug.port = value
+                    .parse()
+                    .wrap_err_with(|| format!("invalid port `{value}`"))?;
+            } else if let (Some(Subcommand::Debug), Some(kind)) =
+                (&sc, is_value_arg(&arg, "--launch-json"))
+            {
+                let value = parse_cross_arg(arg, kind, &mut args)?;
+                debug.launch_json = Some(absolute_path(PathBuf::from(value))?);
+            } else if let (Some(Subcommand::Install), Some(kind)) =
+                (&sc, is_value_arg(&arg, "--root"))
+            {
+                let value = parse_cross_arg(arg, kind, &mut args)?;
+                install_root = Some(absolute_path(PathBuf::from(value))?);
+            } else if let (Some(Subcommand::Install), Some(kind)) =
+                (&sc, is_value_arg(&arg, "--path"))
+            {
+                let path = absolute_path(PathBuf::from(parse_cross_arg(arg, kind, &mut args)?))?;
+                cargo_args.push("--path".to_owned());
+                cargo_args.push(path.as_posix_absolute()?);
+                install_path = Some(path);
             } else if matches!(arg.as_str(), "--quiet" | "-q") {
                 quiet = true;
                 cargo_args.push(arg);
@@ -287,6 +369,7 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
             } else {
                 if (!arg.starts_with('-') || arg == "--list") && sc.is_none() {
                     sc = Some(Subcommand::from(arg.as_ref()));
+                    sc_index = Some(cargo_args.len());
                 }
 
                 cargo_args.push(arg.clone());
@@ -294,16 +377,26 @@ pub fn parse(target_list: &TargetList) -> Result<Args> {
         }
     }
 
+    let debug = (sc == Some(Subcommand::Debug)).then_some(debug);
     Ok(Args {
         cargo_args,
         rest_args,
         subcommand: sc,
+        subcommand_index: sc_index,
         channel,
         target,
         features,
         target_dir,
         manifest_path,
         version,
+        keep_alive,
+        offline_container,
+        test_split,
+        reports,
+        debug,
+        coverage,
+        install_root,
+        install_path,
         verbose,
         quiet,
         color,
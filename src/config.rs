@@ -1,9 +1,14 @@
-use crate::docker::custom::PreBuild;
+use crate::cargo::ExternalSubcommand;
+use crate::cross_toml::{CrossEngineConfig, CrossResourcesConfig, RemoteArtifacts};
+use crate::docker::custom::{BuildSecret, PreBuild};
+use crate::docker::runner::{Runner, RunnerKind};
+use crate::docker::sandbox::Sandbox;
 use crate::docker::{ImagePlatform, PossibleImage};
+use crate::errors::Context;
 use crate::shell::MessageInfo;
 use crate::{CrossToml, Result, Target, TargetList};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::str::FromStr;
 
@@ -123,8 +128,14 @@ impl Environment {
         })
     }
 
-    fn runner(&self, target: &Target) -> Option<String> {
+    fn runner(&self, target: &Target) -> Result<Option<RunnerKind>> {
         self.get_target_var(target, "RUNNER")
+            .filter(|kind| !kind.is_empty())
+            .map(|kind| {
+                kind.parse()
+                    .wrap_err_with(|| format!("invalid runner for target `{}`", target.triple()))
+            })
+            .transpose()
     }
 
     fn passthrough(&self, target: &Target) -> (Option<Vec<String>>, Option<Vec<String>>) {
@@ -157,6 +168,23 @@ impl Environment {
     fn build_opts(&self) -> Option<String> {
         self.get_var("CROSS_BUILD_OPTS")
     }
+
+    fn sandbox(&self) -> Option<String> {
+        self.get_build_var("SANDBOX")
+    }
+
+    fn forward_credentials(&self) -> Option<bool> {
+        self.get_build_var("FORWARD_CREDENTIALS")
+            .map(|s| bool_from_envvar(&s))
+    }
+
+    fn remote_artifacts(&self) -> Option<RemoteArtifacts> {
+        self.get_build_var("REMOTE_ARTIFACTS")
+            .map(|s| match s.trim() {
+                "cargo" => RemoteArtifacts::Cargo,
+                globs => RemoteArtifacts::Globs(split_to_cloned_by_ws(globs)),
+            })
+    }
 }
 
 fn get_possible_image(
@@ -376,12 +404,19 @@ impl Config {
         )
     }
 
-    pub fn runner(&self, target: &Target) -> Result<Option<String>> {
-        self.get_from_ref(
-            target,
-            |env, target| (None, env.runner(target)),
-            |toml, target| (None, toml.runner(target)),
-        )
+    /// The runner for the target. The runner kind from the environment
+    /// overrides the one in `Cross.toml`, keeping any other runner options.
+    pub fn runner(&self, target: &Target) -> Result<Option<Runner>> {
+        let kind = self.env.runner(target)?;
+        let runner = self.toml.as_ref().and_then(|t| t.runner(target)).cloned();
+        Ok(match (kind, runner) {
+            (Some(kind), Some(runner)) => Some(Runner {
+                kind: Some(kind),
+                ..runner
+            }),
+            (Some(kind), None) => Some(kind.into()),
+            (None, runner) => runner,
+        })
     }
 
     pub fn doctests(&self) -> Option<bool> {
@@ -400,6 +435,59 @@ impl Config {
         self.env.build_opts()
     }
 
+    pub fn remote_artifacts(&self) -> Option<RemoteArtifacts> {
+        self.env.remote_artifacts().or_else(|| {
+            self.toml
+                .as_ref()
+                .and_then(|t| t.remote_artifacts())
+                .cloned()
+        })
+    }
+
+    /// The sandbox of the container, from `CROSS_BUILD_SANDBOX` or `build.sandbox`.
+    pub fn sandbox(&self) -> Result<Sandbox> {
+        match self.env.sandbox() {
+            Some(sandbox) => sandbox.parse(),
+            None => Ok(self
+                .toml
+                .as_ref()
+                .and_then(CrossToml::sandbox)
+                .unwrap_or_default()),
+        }
+    }
+
+    /// If the credentials of the host are forwarded to the container, from
+    /// `CROSS_BUILD_FORWARD_CREDENTIALS` or `build.forward-credentials`.
+    pub fn forward_credentials(&self) -> bool {
+        self.env
+            .forward_credentials()
+            .or_else(|| self.toml.as_ref().and_then(CrossToml::forward_credentials))
+            .unwrap_or(false)
+    }
+
+    /// The resource limits of the container, from `build.resources` and
+    /// `target.{}.resources`.
+    pub fn resources(&self, target: &Target) -> CrossResourcesConfig {
+        // This value does not support env variables
+        self.toml
+            .as_ref()
+            .map(|t| t.resources(target))
+            .unwrap_or_default()
+    }
+
+    /// The cargo subcommand `name`, if allowed by `build.subcommands`.
+    pub fn subcommand(&self, name: &str) -> Option<ExternalSubcommand> {
+        self.toml.as_ref().and_then(|t| t.subcommand(name)).cloned()
+    }
+
+    /// The `build.engine` configuration, used to select the container engine.
+    pub fn engine(&self) -> CrossEngineConfig {
+        self.toml
+            .as_ref()
+            .map(|t| t.engine().clone())
+            .unwrap_or_default()
+    }
+
     pub fn env_passthrough(&self, target: &Target) -> Result<Option<Vec<String>>> {
         self.vec_from_config(
             target,
@@ -444,6 +532,22 @@ impl Config {
             .map_or(Ok(None), |t| Ok(t.dockerfile_build_args(target)))
     }
 
+    pub fn dockerfile_secrets(&self, target: &Target) -> BTreeMap<String, BuildSecret> {
+        // This value does not support env variables
+        self.toml
+            .as_ref()
+            .and_then(|t| t.dockerfile_secrets(target))
+            .unwrap_or_default()
+    }
+
+    pub fn pre_build_secrets(&self, target: &Target) -> BTreeMap<String, BuildSecret> {
+        // This value does not support env variables
+        self.toml
+            .as_ref()
+            .and_then(|t| t.pre_build_secrets(target))
+            .unwrap_or_default()
+    }
+
     pub fn pre_build(&self, target: &Target) -> Result<Option<PreBuild>> {
         self.get_from_ref(target, Environment::pre_build, CrossToml::pre_build)
     }
@@ -723,6 +827,32 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        pub fn env_and_toml_remote_artifacts_then_use_env() -> Result<()> {
+            let toml_str = r#"
+            [build.remote]
+            artifacts = "cargo"
+            "#;
+
+            let env = Environment::new(Some(HashMap::new()));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(config.remote_artifacts(), Some(RemoteArtifacts::Cargo));
+
+            let mut map = HashMap::new();
+            map.insert("CROSS_BUILD_REMOTE_ARTIFACTS", "*/release/app release/*.so");
+            let env = Environment::new(Some(map));
+            let config = Config::new_with(Some(toml(toml_str)?), env);
+            assert_eq!(
+                config.remote_artifacts(),
+                Some(RemoteArtifacts::Globs(vec![
+                    s!("*/release/app"),
+                    s!("release/*.so")
+                ]))
+            );
+
+            Ok(())
+        }
+
         #[test]
         pub fn toml_build_passthrough() -> Result<()> {
             let map = HashMap::new();
@@ -5,14 +5,18 @@
 //!
 //! [1]: https://github.com/cross-rs/cross/blob/main/docs/config_file.md
 
-use crate::docker::custom::PreBuild;
+use crate::cargo::ExternalSubcommand;
+use crate::docker::custom::{BuildSecret, PreBuild};
+use crate::docker::runner::{Runner, RunnerKind};
+use crate::docker::sandbox::Sandbox;
 use crate::docker::PossibleImage;
 use crate::shell::MessageInfo;
 use crate::{config, errors::*};
 use crate::{Target, TargetList};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer, Serialize};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 /// Environment configuration
@@ -35,8 +39,109 @@ pub struct CrossBuildConfig {
     default_target: Option<String>,
     #[serde(default, deserialize_with = "opt_string_or_string_vec")]
     pre_build: Option<PreBuild>,
+    pre_build_secrets: Option<BTreeMap<String, BuildSecret>>,
     #[serde(default, deserialize_with = "opt_string_or_struct")]
     dockerfile: Option<CrossTargetDockerfileConfig>,
+    #[serde(default)]
+    remote: CrossRemoteConfig,
+    #[serde(default)]
+    subcommands: BTreeMap<String, ExternalSubcommand>,
+    #[serde(default)]
+    engine: CrossEngineConfig,
+    sandbox: Option<Sandbox>,
+    forward_credentials: Option<bool>,
+    #[serde(default)]
+    resources: CrossResourcesConfig,
+}
+
+/// Remote configuration
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrossRemoteConfig {
+    #[serde(default, deserialize_with = "opt_string_or_string_vec")]
+    artifacts: Option<RemoteArtifacts>,
+}
+
+/// The build artifacts to copy back to the host after a remote build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteArtifacts {
+    /// The artifacts of the workspace members reported by cargo in
+    /// `compiler-artifact` messages.
+    Cargo,
+    /// Glob patterns matching files, relative to the target directory.
+    Globs(Vec<String>),
+}
+
+impl Serialize for RemoteArtifacts {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            RemoteArtifacts::Cargo => serializer.serialize_str("cargo"),
+            RemoteArtifacts::Globs(globs) => globs.serialize(serializer),
+        }
+    }
+}
+
+impl FromStr for RemoteArtifacts {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "cargo" => RemoteArtifacts::Cargo,
+            glob => RemoteArtifacts::Globs(vec![glob.to_owned()]),
+        })
+    }
+}
+
+impl From<Vec<String>> for RemoteArtifacts {
+    fn from(vec: Vec<String>) -> Self {
+        RemoteArtifacts::Globs(vec)
+    }
+}
+
+/// Container engine configuration
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrossEngineConfig {
+    /// The engines to try in order, if `path` isn't provided.
+    #[serde(default)]
+    pub prefer: Vec<String>,
+    /// The engine to use, tried before `prefer`.
+    pub path: Option<PathBuf>,
+    /// Whether the engine is remote, if `CROSS_REMOTE` isn't set.
+    pub remote: Option<bool>,
+}
+
+/// Resource limits of the container
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrossResourcesConfig {
+    /// The number of CPUs, which can be fractional.
+    #[serde(default, deserialize_with = "opt_number_or_string")]
+    pub cpus: Option<String>,
+    /// The memory limit, in the format of the container engine, like `8g`.
+    pub memory: Option<String>,
+    /// The maximum number of processes.
+    pub pids: Option<u64>,
+    /// The size of `/dev/shm`, like `memory`.
+    pub shm_size: Option<String>,
+}
+
+impl CrossResourcesConfig {
+    /// Use the limits of `self`, and the limits of `other` for the others.
+    #[must_use]
+    pub fn or(self, other: CrossResourcesConfig) -> CrossResourcesConfig {
+        CrossResourcesConfig {
+            cpus: self.cpus.or(other.cpus),
+            memory: self.memory.or(other.memory),
+            pids: self.pids.or(other.pids),
+            shm_size: self.shm_size.or(other.shm_size),
+        }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == CrossResourcesConfig::default()
+    }
 }
 
 /// Target configuration
@@ -53,9 +158,13 @@ pub struct CrossTargetConfig {
     dockerfile: Option<CrossTargetDockerfileConfig>,
     #[serde(default, deserialize_with = "opt_string_or_string_vec")]
     pre_build: Option<PreBuild>,
-    runner: Option<String>,
+    pre_build_secrets: Option<BTreeMap<String, BuildSecret>>,
+    #[serde(default, deserialize_with = "opt_runner")]
+    runner: Option<Runner>,
     #[serde(default)]
     env: CrossEnvConfig,
+    #[serde(default)]
+    resources: CrossResourcesConfig,
 }
 
 /// Dockerfile configuration
@@ -65,6 +174,7 @@ pub struct CrossTargetDockerfileConfig {
     file: String,
     context: Option<String>,
     build_args: Option<HashMap<String, String>>,
+    secrets: Option<BTreeMap<String, BuildSecret>>,
 }
 
 impl FromStr for CrossTargetDockerfileConfig {
@@ -75,6 +185,7 @@ impl FromStr for CrossTargetDockerfileConfig {
             file: s.to_owned(),
             context: None,
             build_args: None,
+            secrets: None,
         })
     }
 }
@@ -290,13 +401,38 @@ impl CrossToml {
         config::opt_merge(target.cloned(), build.cloned())
     }
 
+    /// Returns the `target.{}.dockerfile.secrets` part of `Cross.toml`
+    pub fn dockerfile_secrets(&self, target: &Target) -> Option<BTreeMap<String, BuildSecret>> {
+        let target = self
+            .get_target(target)
+            .and_then(|t| t.dockerfile.as_ref())
+            .and_then(|d| d.secrets.as_ref());
+
+        let build = self
+            .build
+            .dockerfile
+            .as_ref()
+            .and_then(|d| d.secrets.as_ref());
+
+        config::opt_merge(target.cloned(), build.cloned())
+    }
+
+    /// Returns the `build.pre-build-secrets` and `target.{}.pre-build-secrets` part of `Cross.toml`
+    pub fn pre_build_secrets(&self, target: &Target) -> Option<BTreeMap<String, BuildSecret>> {
+        let target = self
+            .get_target(target)
+            .and_then(|t| t.pre_build_secrets.as_ref());
+
+        config::opt_merge(target.cloned(), self.build.pre_build_secrets.clone())
+    }
+
     /// Returns the `build.dockerfile.pre-build` and `target.{}.dockerfile.pre-build` part of `Cross.toml`
     pub fn pre_build(&self, target: &Target) -> (Option<&PreBuild>, Option<&PreBuild>) {
         self.get_ref(target, |b| b.pre_build.as_ref(), |t| t.pre_build.as_ref())
     }
 
     /// Returns the `target.{}.runner` part of `Cross.toml`
-    pub fn runner(&self, target: &Target) -> Option<&String> {
+    pub fn runner(&self, target: &Target) -> Option<&Runner> {
         self.get_target(target).and_then(|t| t.runner.as_ref())
     }
 
@@ -355,6 +491,41 @@ impl CrossToml {
         )
     }
 
+    /// Returns the `build.remote.artifacts` part of `Cross.toml`
+    pub fn remote_artifacts(&self) -> Option<&RemoteArtifacts> {
+        self.build.remote.artifacts.as_ref()
+    }
+
+    /// Returns the `build.subcommands.<name>` part of `Cross.toml`
+    pub fn subcommand(&self, name: &str) -> Option<&ExternalSubcommand> {
+        self.build.subcommands.get(name)
+    }
+
+    /// Returns the `build.engine` part of `Cross.toml`
+    pub fn engine(&self) -> &CrossEngineConfig {
+        &self.build.engine
+    }
+
+    /// Returns the `build.sandbox` part of `Cross.toml`
+    pub fn sandbox(&self) -> Option<Sandbox> {
+        self.build.sandbox
+    }
+
+    /// Returns the `build.forward-credentials` part of `Cross.toml`
+    pub fn forward_credentials(&self) -> Option<bool> {
+        self.build.forward_credentials
+    }
+
+    /// Returns the `build.resources` part of `Cross.toml`, with the limits
+    /// in `target.{}.resources` taking precedence.
+    pub fn resources(&self, target: &Target) -> CrossResourcesConfig {
+        let build = self.build.resources.clone();
+        match self.get_target(target) {
+            Some(t) => t.resources.clone().or(build),
+            None => build,
+        }
+    }
+
     /// Returns the default target to build,
     pub fn default_target(&self, target_list: &TargetList) -> Option<Target> {
         self.build
@@ -446,9 +617,122 @@ where
     deserializer.deserialize_any(StringOrStruct(PhantomData))
 }
 
+fn opt_runner<'de, D>(deserializer: D) -> Result<Option<Runner>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use std::fmt;
+
+    use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
+
+    struct KindOrRunner;
+
+    impl<'de> Visitor<'de> for KindOrRunner {
+        type Value = Option<Runner>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("string or map")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let kind: RunnerKind = Deserialize::deserialize(value.into_deserializer())?;
+            Ok(Some(kind.into()))
+        }
+
+        fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            Deserialize::deserialize(de::value::MapAccessDeserializer::new(map)).map(Some)
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+    }
+
+    deserializer.deserialize_any(KindOrRunner)
+}
+
+fn opt_number_or_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use std::fmt;
+
+    use serde::de::{self, Visitor};
+
+    struct NumberOrString;
+
+    impl<'de> Visitor<'de> for NumberOrString {
+        type Value = Option<String>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("number or string")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(Some(value.to_owned()))
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(Some(value.to_string()))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(Some(value.to_string()))
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(Some(value.to_string()))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+    }
+
+    deserializer.deserialize_any(NumberOrString)
+}
+
 fn opt_string_or_string_vec<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
 where
-    T: Deserialize<'de> + std::str::FromStr<Err = std::convert::Infallible> + From<Vec<String>>,
+    T: std::str::FromStr<Err = std::convert::Infallible> + From<Vec<String>>,
     D: serde::Deserializer<'de>,
 {
     use std::{fmt, marker::PhantomData};
@@ -459,7 +743,7 @@ where
 
     impl<'de, T> Visitor<'de> for StringOrStringVec<T>
     where
-        T: Deserialize<'de> + FromStr<Err = std::convert::Infallible> + From<Vec<String>>,
+        T: FromStr<Err = std::convert::Infallible> + From<Vec<String>>,
     {
         type Value = Option<T>;
 
@@ -613,7 +897,14 @@ mod tests {
                 zig: None,
                 default_target: None,
                 pre_build: Some(PreBuild::Lines(vec![p!("echo 'Hello World!'")])),
+                resources: CrossResourcesConfig::default(),
+                pre_build_secrets: None,
                 dockerfile: None,
+                remote: CrossRemoteConfig::default(),
+                subcommands: BTreeMap::new(),
+                engine: CrossEngineConfig::default(),
+                sandbox: None,
+                forward_credentials: None,
             },
         };
 
@@ -653,6 +944,8 @@ mod tests {
                 runner: None,
                 dockerfile: None,
                 pre_build: Some(PreBuild::Lines(vec![])),
+                resources: CrossResourcesConfig::default(),
+                pre_build_secrets: None,
             },
         );
         target_map.insert(
@@ -675,6 +968,8 @@ mod tests {
                 runner: None,
                 dockerfile: None,
                 pre_build: None,
+                resources: CrossResourcesConfig::default(),
+                pre_build_secrets: None,
             },
         );
 
@@ -727,8 +1022,20 @@ mod tests {
                     file: p!("Dockerfile.test"),
                     context: None,
                     build_args: None,
+                    secrets: None,
                 }),
                 pre_build: Some(PreBuild::Lines(vec![p!("echo 'Hello'")])),
+                resources: CrossResourcesConfig {
+                    memory: Some(p!("16g")),
+                    ..CrossResourcesConfig::default()
+                },
+                pre_build_secrets: Some(BTreeMap::from([(
+                    p!("apt_auth"),
+                    BuildSecret {
+                        env: Some(p!("APT_AUTH")),
+                        file: None,
+                    },
+                )])),
                 runner: None,
                 env: CrossEnvConfig {
                     passthrough: None,
@@ -758,7 +1065,33 @@ mod tests {
                 }),
                 default_target: None,
                 pre_build: Some(PreBuild::Lines(vec![])),
+                resources: CrossResourcesConfig {
+                    cpus: Some(p!("4")),
+                    memory: Some(p!("8g")),
+                    pids: Some(4096),
+                    shm_size: Some(p!("1g")),
+                },
+                pre_build_secrets: None,
                 dockerfile: None,
+                remote: CrossRemoteConfig {
+                    artifacts: Some(RemoteArtifacts::Globs(vec![p!("*/release/app")])),
+                },
+                subcommands: BTreeMap::from([(
+                    p!("nextest"),
+                    ExternalSubcommand {
+                        needs_docker: true,
+                        needs_target_in_command: true,
+                        needs_interpreter: true,
+                        package: None,
+                    },
+                )]),
+                engine: CrossEngineConfig {
+                    prefer: vec![p!("podman"), p!("docker")],
+                    path: None,
+                    remote: Some(true),
+                },
+                sandbox: Some(Sandbox::Strict),
+                forward_credentials: Some(true),
             },
         };
 
@@ -766,6 +1099,19 @@ mod tests {
             [build]
             xargo = true
             pre-build = []
+            sandbox = "strict"
+            forward-credentials = true
+            resources = { cpus = 4, memory = "8g", pids = 4096, shm-size = "1g" }
+
+            [build.remote]
+            artifacts = ["*/release/app"]
+
+            [build.subcommands.nextest]
+            needs-interpreter = true
+
+            [build.engine]
+            prefer = ["podman", "docker"]
+            remote = true
 
             [build.zig.image]
             name = "zig:local"
@@ -778,8 +1124,10 @@ mod tests {
             xargo = false
             dockerfile = "Dockerfile.test"
             pre-build = ["echo 'Hello'"]
+            pre-build-secrets = { apt_auth = { env = "APT_AUTH" } }
             image.name = "test-image"
             image.toolchain = ["aarch64-unknown-linux-musl"]
+            resources.memory = "16g"
 
             [target.aarch64-unknown-linux-gnu.env]
             volumes = ["VOL"]
@@ -789,6 +1137,13 @@ mod tests {
         assert_eq!(parsed_cfg, cfg);
         assert!(unused.is_empty());
 
+        let aarch64 = Target::new_built_in("aarch64-unknown-linux-gnu");
+        let resources = parsed_cfg.resources(&aarch64);
+        assert_eq!(resources.cpus.as_deref(), Some("4"));
+        assert_eq!(resources.memory.as_deref(), Some("16g"));
+        let musl = Target::new_built_in("aarch64-unknown-linux-musl");
+        assert_eq!(parsed_cfg.resources(&musl).memory.as_deref(), Some("8g"));
+
         Ok(())
     }
 
@@ -823,7 +1178,14 @@ mod tests {
                 zig: None,
                 default_target: None,
                 pre_build: None,
+                resources: CrossResourcesConfig::default(),
+                pre_build_secrets: None,
                 dockerfile: None,
+                remote: CrossRemoteConfig::default(),
+                subcommands: BTreeMap::new(),
+                engine: CrossEngineConfig::default(),
+                sandbox: None,
+                forward_credentials: None,
             },
         };
 
@@ -862,10 +1224,12 @@ mod tests {
             dockerfile.context = ".."
             pre-build = ["sh"]
             zig = true
+            runner = { kind = "qemu-system", memory = "2G", smp = 4 }
 
             [target.b]
             pre-build = "sh"
             zig = "2.17"
+            runner = "qemu-user"
         "#;
 
         let (cfg, _) = CrossToml::parse_from_cross_str(cfg, None, &mut m!())?;
@@ -873,6 +1237,54 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    pub fn parse_runner_toml() -> Result<()> {
+        let test_str = r#"
+            [target.aarch64-unknown-linux-gnu.runner]
+            kind = "qemu-system"
+            cpu = "cortex-a72"
+            memory = "2G"
+            smp = 4
+            kernel-args = ["nokaslr"]
+
+            [target.armv7-unknown-linux-gnueabihf]
+            runner = "qemu-user"
+
+            [target.armv7-unknown-linux-musleabihf]
+            runner.command = ["./scripts/run-on-board.sh", "--serial"]
+        "#;
+        let (cfg, unused) = CrossToml::parse_from_cross_str(test_str, None, &mut m!())?;
+        assert!(unused.is_empty());
+
+        let aarch64 = Target::new_built_in("aarch64-unknown-linux-gnu");
+        assert_eq!(
+            cfg.runner(&aarch64),
+            Some(&Runner {
+                kind: Some(RunnerKind::QemuSystem),
+                cpu: Some("cortex-a72".to_owned()),
+                memory: Some("2G".to_owned()),
+                smp: Some(4),
+                kernel_args: Some(vec!["nokaslr".to_owned()]),
+                command: None,
+            })
+        );
+        let armv7 = Target::new_built_in("armv7-unknown-linux-gnueabihf");
+        assert_eq!(cfg.runner(&armv7), Some(&RunnerKind::QemuUser.into()));
+        let armv7_musl = Target::new_built_in("armv7-unknown-linux-musleabihf");
+        assert_eq!(
+            cfg.runner(&armv7_musl).and_then(Runner::command),
+            Some(("./scripts/run-on-board.sh", &["--serial".to_owned()][..]))
+        );
+
+        let test_str = r#"
+            [target.aarch64-unknown-linux-gnu]
+            runner = "qemu"
+        "#;
+        assert!(CrossToml::parse_from_cross_str(test_str, None, &mut m!()).is_err());
+
+        Ok(())
+    }
+
     #[test]
     pub fn merge() -> Result<()> {
         let cfg1_str = r#"
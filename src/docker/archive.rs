@@ -0,0 +1,349 @@
+//! Stream tar archives to and from a container.
+//!
+//! Remote cross needs to copy the toolchain, project and build artifacts
+//! to and from a data volume. Rather than staging files in a temporary
+//! directory and calling `docker cp` for each of them, which has large
+//! overhead with SSH-backed engines, we stream a single compressed archive
+//! through `docker exec -i $container tar`.
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::{env, fs};
+
+use super::EngineCommand;
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::shell::MessageInfo;
+
+/// The compression used for archives streamed to or from a container.
+///
+/// `zstd` requires the `zstd` binary to be installed in the image, and
+/// `cross` to be built with the `zstd` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_env() -> Result<Self> {
+        match env::var("CROSS_REMOTE_COPY_COMPRESSION").as_deref() {
+            Err(_) | Ok("gzip") => Ok(Compression::Gzip),
+            Ok("zstd") if cfg!(feature = "zstd") => Ok(Compression::Zstd),
+            Ok("zstd") => Err(zstd_unsupported()),
+            Ok("none") => Ok(Compression::None),
+            Ok(value) => eyre::bail!(
+                "invalid value for `CROSS_REMOTE_COPY_COMPRESSION`: got `{value}`, expected one of `gzip`, `zstd`, or `none`"
+            ),
+        }
+    }
+
+    fn decompress_program(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gzip -d"),
+            Compression::Zstd => Some("zstd -d"),
+        }
+    }
+
+    fn compress_program(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gzip"),
+            Compression::Zstd => Some("zstd -q"),
+        }
+    }
+
+    /// The shell script to extract an archive from stdin into `dst`.
+    pub fn extract_script(&self, dst: &str) -> String {
+        let dst = shell_escape::unix::escape(dst.into());
+        let extract = format!("mkdir -p {dst} && tar -x -f - -C {dst}");
+        match self.decompress_program() {
+            Some(program) => format!("{program} | {{ {extract}; }}"),
+            None => extract,
+        }
+    }
+
+    /// The shell script to write an archive of `src` to stdout. If `files`
+    /// is provided, it is a script run inside `src` printing the relative
+    /// paths of the files to archive, one per line.
+    pub fn create_script(&self, src: &str, files: Option<&str>) -> String {
+        let src = shell_escape::unix::escape(src.into());
+        let create = match files {
+            Some(files) => format!("cd {src} && {files} | tar -c -f - -T -"),
+            None => format!("tar -c -f - -C {src} ."),
+        };
+        match self.compress_program() {
+            Some(program) => format!("{create} | {program}"),
+            None => create,
+        }
+    }
+
+    fn encoder<W: Write>(&self, writer: W) -> Result<Encoder<W>> {
+        Ok(match self {
+            Compression::None => Encoder::None(writer),
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, 0)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(zstd_unsupported()),
+        })
+    }
+
+    fn decoder<'a, R: Read + 'a>(&self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(zstd_unsupported()),
+        })
+    }
+}
+
+pub(crate) fn zstd_unsupported() -> eyre::Report {
+    eyre::eyre!("zstd compression requires `cross` to be built with the `zstd` feature")
+        .suggestion("install `cross` with `cargo install cross --features zstd`")
+}
+
+enum Encoder<W: Write> {
+    None(W),
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::None(writer) => Ok(writer),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(writer) => writer.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(writer) => writer.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+pub type Builder<'a> = tar::Builder<&'a mut dyn Write>;
+
+/// Stream an archive to the stdin of `command`, which should extract it
+/// using [`Compression::extract_script`]. The archive contents are added
+/// by `build`.
+#[track_caller]
+pub fn send<T>(
+    mut command: EngineCommand,
+    compression: Compression,
+    msg_info: &mut MessageInfo,
+    build: impl FnOnce(&mut Builder<'_>) -> Result<T>,
+) -> Result<T> {
+    command.debug(msg_info)?;
+    let mut child = command
+        .stdin(Stdio::piped())
+        .spawn()
+        .wrap_err("when spawning archive extraction")?;
+    let stdin = child.stdin.take().expect("stdin should be piped");
+    let result = (|| {
+        let mut encoder = compression.encoder(stdin)?;
+        let writer: &mut dyn Write = &mut encoder;
+        let mut builder = tar::Builder::new(writer);
+        builder.follow_symlinks(false);
+        let result = build(&mut builder)?;
+        builder.into_inner()?;
+        // closing stdin signals the end of the archive
+        drop(encoder.finish()?);
+        Ok(result)
+    })();
+    let status = child.wait()?;
+    // a failure to extract often causes a broken pipe: report the status first.
+    command.status_result(msg_info, status, None)?;
+
+    result
+}
+
+/// Read an archive from the stdout of `command`, which should create it
+/// using [`Compression::create_script`], and unpack it into `dst`.
+#[track_caller]
+pub fn receive(
+    mut command: EngineCommand,
+    compression: Compression,
+    dst: &Path,
+    msg_info: &mut MessageInfo,
+) -> Result<ExitStatus> {
+    command.debug(msg_info)?;
+    let mut child = command
+        .stdout(Stdio::piped())
+        .spawn()
+        .wrap_err("when spawning archive creation")?;
+    let stdout = child.stdout.take().expect("stdout should be piped");
+    let result = (|| {
+        fs::create_dir_all(dst)?;
+        let mut archive = tar::Archive::new(compression.decoder(stdout)?);
+        archive.set_preserve_mtime(true);
+        archive.set_overwrite(true);
+        archive
+            .unpack(dst)
+            .wrap_err_with(|| format!("when unpacking archive to {dst:?}"))
+    })();
+    let status = child.wait()?;
+    command.status_result(msg_info, status, None)?;
+    result?;
+
+    Ok(status)
+}
+
+/// Append a directory to the archive at `name`, recursively, skipping
+/// entries for which `skip` returns true. Returns whether the directory
+/// contained symlinks and the number of bytes of files added.
+pub fn append_dir<Skip>(
+    builder: &mut Builder<'_>,
+    src: &Path,
+    name: &Path,
+    depth: u32,
+    skip: Skip,
+) -> Result<(bool, u64)>
+where
+    Skip: Copy + Fn(&fs::DirEntry, u32) -> bool,
+{
+    let mut had_symlinks = false;
+    let mut bytes = 0;
+    if !name.as_os_str().is_empty() {
+        builder.append_dir(name, src)?;
+    }
+    for entry in fs::read_dir(src).wrap_err_with(|| format!("when reading directory {src:?}"))? {
+        let file = entry?;
+        if skip(&file, depth) {
+            continue;
+        }
+
+        let src_path = file.path();
+        let dst_path = name.join(file.file_name());
+        let file_type = file.file_type()?;
+        if file_type.is_dir() {
+            let (symlinks, size) = append_dir(builder, &src_path, &dst_path, depth + 1, skip)?;
+            had_symlinks |= symlinks;
+            bytes += size;
+        } else {
+            had_symlinks |= file_type.is_symlink();
+            bytes += file.metadata()?.len();
+            builder
+                .append_path_with_name(&src_path, &dst_path)
+                .wrap_err_with(|| format!("when archiving file {src_path:?}"))?;
+        }
+    }
+
+    Ok((had_symlinks, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_scripts() {
+        assert_eq!(
+            Compression::Gzip.extract_script("/cross/project"),
+            "gzip -d | { mkdir -p /cross/project && tar -x -f - -C /cross/project; }"
+        );
+        assert_eq!(
+            Compression::None.create_script("/project/target", None),
+            "tar -c -f - -C /project/target ."
+        );
+        assert_eq!(
+            Compression::Zstd.create_script("/project/target", Some("find . -type f")),
+            "cd /project/target && find . -type f | tar -c -f - -T - | zstd -q"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_scripts_quote_paths() -> Result<()> {
+        assert_eq!(
+            Compression::None.extract_script("/cross/it's"),
+            r"mkdir -p '/cross/it'\''s' && tar -x -f - -C '/cross/it'\''s'"
+        );
+
+        // the quote must not end the path, running the rest as a command.
+        let dir = tempfile::tempdir()?;
+        let src = dir.path().join("it's; touch pwned; '");
+        fs::create_dir(&src)?;
+        fs::write(src.join("lib.rs"), "fn a() {}")?;
+        let output = Command::new("sh")
+            .args([
+                "-c",
+                &Compression::None.create_script(src.to_str().unwrap(), None),
+            ])
+            .current_dir(dir.path())
+            .output()?;
+        assert!(output.status.success());
+        assert!(!output.stdout.is_empty());
+        assert!(!dir.path().join("pwned").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let src = tempfile::tempdir()?;
+        let dst = tempfile::tempdir()?;
+        fs::create_dir_all(src.path().join("src/nested"))?;
+        fs::write(src.path().join("src/nested/lib.rs"), "fn a() {}")?;
+        fs::write(src.path().join("Cargo.toml"), "[package]")?;
+
+        let mut compressions = vec![Compression::None, Compression::Gzip];
+        if cfg!(feature = "zstd") {
+            compressions.push(Compression::Zstd);
+        }
+        for compression in compressions {
+            let mut buffer = vec![];
+            {
+                let mut encoder = compression.encoder(&mut buffer)?;
+                let writer: &mut dyn Write = &mut encoder;
+                let mut builder = tar::Builder::new(writer);
+                let (symlinks, bytes) =
+                    append_dir(&mut builder, src.path(), Path::new(""), 0, |_, _| false)?;
+                assert!(!symlinks);
+                assert_eq!(bytes, 18);
+                builder.into_inner()?;
+                encoder.finish()?;
+            }
+
+            let out = dst.path().join(format!("{compression:?}"));
+            let mut archive = tar::Archive::new(compression.decoder(&buffer[..])?);
+            archive.unpack(&out)?;
+            assert_eq!(
+                fs::read_to_string(out.join("src/nested/lib.rs"))?,
+                "fn a() {}"
+            );
+            assert_eq!(fs::read_to_string(out.join("Cargo.toml"))?, "[package]");
+        }
+
+        Ok(())
+    }
+}
@@ -1,8 +1,7 @@
 use std::env;
-use std::process::Command;
 use std::str::FromStr;
 
-use super::engine::Engine;
+use super::engine::{Engine, EngineCommand};
 use crate::errors::*;
 use crate::shell::Verbosity;
 
@@ -44,7 +43,7 @@ pub trait BuildCommandExt {
     fn cross_labels(&mut self, target: &str, platform: &str) -> &mut Self;
 }
 
-impl BuildCommandExt for Command {
+impl BuildCommandExt for EngineCommand {
     fn invoke_build_command(&mut self) -> &mut Self {
         match Engine::has_buildkit() {
             true => self.args(["buildx", "build"]),
@@ -0,0 +1,87 @@
+//! Collect the coverage of the tests run by `cross test --coverage`.
+//!
+//! The tests are built with `-C instrument-coverage`, and the wrapper
+//! runner writes their profiles to `target/<triple>/cross-coverage/`. The
+//! profiles are merged with the `llvm-tools` of the toolchain inside the
+//! container, and exported as an lcov report, whose source paths are then
+//! remapped to the host.
+
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+use crate::errors::*;
+use crate::file::ToUtf8;
+use crate::shell::MessageInfo;
+use crate::Target;
+
+pub const COVERAGE_DIR: &str = "cross-coverage";
+pub const LCOV_FILE: &str = "lcov.info";
+
+/// The directory the coverage is collected into, inside `target_dir`.
+pub fn coverage_dir(target_dir: &str, target: &Target) -> String {
+    format!("{target_dir}/{}/{COVERAGE_DIR}", target.triple())
+}
+
+/// The directory the coverage is collected into on the host.
+pub fn host_coverage_dir(target_dir: &Path, target: &Target) -> PathBuf {
+    target_dir.join(target.triple()).join(COVERAGE_DIR)
+}
+
+/// Remap the source paths in the lcov report from the container's
+/// `mount_root` to `host_root`, and print where it was written.
+pub fn finish(
+    dir: &Path,
+    mount_root: &str,
+    host_root: &Path,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    let path = dir.join(LCOV_FILE);
+    let lcov = match fs::read_to_string(&path) {
+        Ok(lcov) => lcov,
+        Err(_) => {
+            msg_info.warn("no coverage report was written.")?;
+            return Ok(());
+        }
+    };
+    fs::write(&path, remap(&lcov, mount_root, host_root.to_utf8()?))
+        .wrap_err_with(|| format!("when writing {path:?}"))?;
+    msg_info.note(format_args!("wrote the coverage report to {path:?}"))?;
+
+    Ok(())
+}
+
+fn remap(lcov: &str, mount_root: &str, host_root: &str) -> String {
+    let mut remapped = String::with_capacity(lcov.len());
+    for line in lcov.lines() {
+        match line
+            .strip_prefix("SF:")
+            .and_then(|source| source.strip_prefix(mount_root))
+        {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                remapped.push_str("SF:");
+                remapped.push_str(host_root);
+                remapped.push_str(&rest.replace('/', &MAIN_SEPARATOR.to_string()));
+            }
+            _ => remapped.push_str(line),
+        }
+        remapped.push('\n');
+    }
+    remapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remap() {
+        let lcov = "SF:/project/src/lib.rs\nDA:1,1\nend_of_record\nSF:/projects/src/lib.rs\n";
+        let sep = MAIN_SEPARATOR;
+        assert_eq!(
+            remap(lcov, "/project", "/home/user/project"),
+            format!(
+                "SF:/home/user/project{sep}src{sep}lib.rs\nDA:1,1\nend_of_record\nSF:/projects/src/lib.rs\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Collect core dumps and `qemu` logs from crashed binaries.
+//!
+//! With `CROSS_COLLECT_CRASHES`, binaries are run through the wrapper
+//! runner, which raises the core file size limit and moves any core dumps
+//! and `QEMU_STRACE` or `QEMU_LOG` logs from binaries killed by a signal
+//! to `target/<triple>/cross-crashes/`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::bool_from_envvar;
+use crate::errors::*;
+use crate::shell::MessageInfo;
+use crate::Target;
+
+pub const CRASH_DIR: &str = "cross-crashes";
+
+/// Returns `true` if crashes should be collected.
+#[must_use]
+pub fn enabled() -> bool {
+    env::var("CROSS_COLLECT_CRASHES")
+        .map(|s| bool_from_envvar(&s))
+        .unwrap_or_default()
+}
+
+/// The directory crashes are collected into, inside `target_dir`.
+pub fn crash_dir(target_dir: &str, target: &Target) -> String {
+    format!("{target_dir}/{}/{CRASH_DIR}", target.triple())
+}
+
+/// The directory crashes are collected into on the host.
+pub fn host_crash_dir(target_dir: &Path, target: &Target) -> PathBuf {
+    target_dir.join(target.triple()).join(CRASH_DIR)
+}
+
+/// Print the crashes collected since `since`.
+pub fn report(dir: &Path, since: SystemTime, msg_info: &mut MessageInfo) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+    let mut crashes = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.metadata()?.modified()? >= since {
+            crashes.push(entry.path());
+        }
+    }
+    crashes.sort();
+
+    if crashes.is_empty() {
+        msg_info.note(format_args!(
+            "no crash artifacts were collected: core dumps may be disabled by the engine or the kernel's `core_pattern`."
+        ))?;
+    }
+    for crash in crashes {
+        msg_info.note(format_args!("collected crash artifacts in {crash:?}"))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,475 @@
+//! Forwarding of the credentials for private registries and git
+//! repositories, enabled with `build.forward-credentials`.
+//!
+//! The SSH agent of the host is mounted into the container, so git
+//! dependencies can be fetched without exposing the private keys, and the
+//! registry tokens of the credential providers are forwarded as environment
+//! variables. Credential providers usually aren't available in the image, so
+//! they're run on the host, and the token is passed in for the `cargo:token`
+//! provider. The registry tokens exported on the host are always forwarded.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use super::EngineCommand;
+use crate::cargo::CargoMetadata;
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::file;
+use crate::shell::MessageInfo;
+
+/// The path of the SSH agent socket inside the container.
+pub const SSH_AUTH_SOCK_MOUNT: &str = "/run/cross-ssh-agent.sock";
+
+/// The socket of the SSH agent of the host, if any.
+pub(crate) fn ssh_agent_socket() -> Option<PathBuf> {
+    env::var_os("SSH_AUTH_SOCK")
+        .map(PathBuf::from)
+        .filter(|socket| socket.exists())
+}
+
+// the name of the environment variable for `key` of the registry `name`.
+fn registry_envvar(name: &str, key: &str) -> String {
+    format!(
+        "CARGO_REGISTRIES_{}_{key}",
+        name.to_uppercase().replace('-', "_")
+    )
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StringOrVec {
+    String(String),
+    Vec(Vec<String>),
+}
+
+impl StringOrVec {
+    fn into_args(self) -> Vec<String> {
+        match self {
+            StringOrVec::String(s) => s.split_whitespace().map(ToOwned::to_owned).collect(),
+            StringOrVec::Vec(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RegistryConfig {
+    index: Option<String>,
+    credential_provider: Option<StringOrVec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct GlobalRegistryConfig {
+    #[serde(default)]
+    global_credential_providers: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CargoConfigFile {
+    #[serde(default)]
+    registries: BTreeMap<String, RegistryConfig>,
+    #[serde(default)]
+    registry: GlobalRegistryConfig,
+    #[serde(default)]
+    credential_alias: BTreeMap<String, StringOrVec>,
+}
+
+/// A registry with its credential providers, in the order they're tried.
+#[derive(Debug, PartialEq, Eq)]
+struct Registry {
+    name: String,
+    index: String,
+    providers: Vec<Vec<String>>,
+}
+
+/// The credential configuration of cargo, merged from the config files.
+#[derive(Debug, Default)]
+struct CredentialConfig {
+    indexes: BTreeMap<String, String>,
+    providers: BTreeMap<String, Vec<String>>,
+    global_providers: Vec<Vec<String>>,
+    aliases: BTreeMap<String, Vec<String>>,
+}
+
+impl CredentialConfig {
+    /// Read the config files used by cargo in `cwd`, from the lowest to the
+    /// highest precedence.
+    fn read(cwd: &Path, cargo_home: &Path) -> Result<Self> {
+        let mut dirs = vec![cargo_home.to_owned()];
+        let mut ancestors: Vec<_> = cwd.ancestors().map(|dir| dir.join(".cargo")).collect();
+        ancestors.reverse();
+        dirs.extend(ancestors.into_iter().filter(|dir| dir != cargo_home));
+
+        let mut config = CredentialConfig::default();
+        for dir in dirs {
+            let path = match ["config.toml", "config"]
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.is_file())
+            {
+                Some(path) => path,
+                None => continue,
+            };
+            let contents = file::read(&path)?;
+            config.merge(
+                toml::from_str(&contents)
+                    .wrap_err_with(|| format!("could not parse cargo config {path:?}"))?,
+            );
+        }
+
+        Ok(config)
+    }
+
+    fn merge(&mut self, file: CargoConfigFile) {
+        for (name, registry) in file.registries {
+            if let Some(index) = registry.index {
+                self.indexes.insert(name.clone(), index);
+            }
+            if let Some(provider) = registry.credential_provider {
+                self.providers.insert(name, provider.into_args());
+            }
+        }
+        self.global_providers.extend(
+            file.registry
+                .global_credential_providers
+                .into_iter()
+                .map(|provider| StringOrVec::String(provider).into_args()),
+        );
+        for (name, alias) in file.credential_alias {
+            self.aliases.insert(name, alias.into_args());
+        }
+    }
+
+    // replace the credential alias used by the provider, if any.
+    fn resolve_alias(&self, mut provider: Vec<String>) -> Vec<String> {
+        if let Some(alias) = provider.first().and_then(|name| self.aliases.get(name)) {
+            provider.splice(..1, alias.iter().cloned());
+        }
+        provider
+    }
+
+    /// The registries with a credential provider, overridden by the
+    /// environment of cargo.
+    fn registries(&self, get_env: impl Fn(&str) -> Option<String>) -> Vec<Registry> {
+        let global: Vec<_> = match get_env("CARGO_REGISTRY_GLOBAL_CREDENTIAL_PROVIDERS") {
+            Some(providers) => vec![StringOrVec::String(providers).into_args()],
+            None => self.global_providers.clone(),
+        };
+        self.indexes
+            .iter()
+            .filter_map(|(name, index)| {
+                let provider = get_env(&registry_envvar(name, "CREDENTIAL_PROVIDER"))
+                    .map(|p| StringOrVec::String(p).into_args())
+                    .or_else(|| self.providers.get(name).cloned());
+                let providers: Vec<_> = match provider {
+                    Some(provider) => vec![provider],
+                    // later providers have a higher precedence.
+                    None => global.iter().rev().cloned().collect(),
+                };
+                (!providers.is_empty()).then(|| Registry {
+                    name: name.clone(),
+                    index: index.clone(),
+                    providers: providers
+                        .into_iter()
+                        .map(|p| self.resolve_alias(p))
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+// the request for a token, from the credential provider protocol.
+fn provider_request(registry: &Registry) -> String {
+    serde_json::json!({
+        "v": 1,
+        "registry": {"index-url": registry.index, "name": registry.name},
+        "kind": "get",
+        "operation": "read",
+        "args": [],
+    })
+    .to_string()
+}
+
+#[derive(Debug, Deserialize)]
+enum ProviderResponse {
+    Ok {
+        token: String,
+    },
+    Err {
+        kind: String,
+        message: Option<String>,
+    },
+}
+
+// parse the output of a credential provider: the hello message, and then
+// the response to the request.
+fn parse_provider_response(stdout: &str) -> Result<Option<String>> {
+    let response = stdout
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .nth(1)
+        .ok_or_else(|| eyre::eyre!("no response from the credential provider"))?;
+    match serde_json::from_str(response)? {
+        ProviderResponse::Ok { token } => Ok(Some(token)),
+        ProviderResponse::Err { kind, .. } if kind == "not-found" => Ok(None),
+        ProviderResponse::Err { kind, message } => {
+            eyre::bail!("credential provider failed: {}", message.unwrap_or(kind))
+        }
+    }
+}
+
+fn run_provider(
+    registry: &Registry,
+    provider: &[String],
+    msg_info: &mut MessageInfo,
+) -> Result<Option<String>> {
+    let mut command = Command::new(&provider[0]);
+    command.args(&provider[1..]).arg("--cargo-plugin");
+    command.debug(msg_info)?;
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("could not run credential provider `{}`", provider[0]))?;
+    let mut stdin = child.stdin.take().expect("stdin should be piped");
+    let result = writeln!(stdin, "{}", provider_request(registry));
+    // closing stdin ends the session after the request
+    drop(stdin);
+    let output = child.wait_with_output()?;
+    result?;
+    command.status_result(msg_info, output.status, Some(&output))?;
+
+    parse_provider_response(&String::from_utf8(output.stdout)?)
+}
+
+impl Registry {
+    // if a package of the workspace comes from this registry.
+    fn is_used(&self, metadata: &CargoMetadata) -> bool {
+        let url = |source: &str| {
+            source
+                .strip_prefix("registry+")
+                .unwrap_or(source)
+                .to_owned()
+        };
+        let index = url(&self.index);
+        metadata
+            .packages
+            .iter()
+            .filter_map(|package| package.source.as_deref())
+            .any(|source| url(source) == index)
+    }
+}
+
+/// Get the token for `registry` on the host, from the first provider that
+/// has one. Returns `None` if the token can be read in the container.
+fn get_token(registry: &Registry, msg_info: &mut MessageInfo) -> Result<Option<String>> {
+    for provider in &registry.providers {
+        match provider.first().map(String::as_str) {
+            // the credentials are in the cargo home, which is mounted.
+            Some("cargo:token") | None => return Ok(None),
+            Some("cargo:token-from-stdout") if provider.len() > 1 => {
+                let token = Command::new(&provider[1])
+                    .args(&provider[2..])
+                    .env("CARGO_REGISTRY_INDEX_URL", &registry.index)
+                    .env("CARGO_REGISTRY_NAME_OPT", &registry.name)
+                    .run_and_get_stdout(msg_info)?;
+                return Ok(token.lines().next().map(|t| t.trim().to_owned()));
+            }
+            // the builtin providers of the keyrings are part of cargo.
+            Some(builtin) if builtin.starts_with("cargo:") => {
+                return Err(eyre::eyre!(
+                    "credential provider `{builtin}` is not supported by cross"
+                ))
+                .with_suggestion(|| {
+                    format!(
+                        "export `{}`, or use a credential provider binary",
+                        registry_envvar(&registry.name, "TOKEN")
+                    )
+                });
+            }
+            Some(_) => {
+                if let Some(token) = run_provider(registry, provider, msg_info)? {
+                    return Ok(Some(token));
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Add the tokens of the registries used by the packages, with a credential
+/// provider run on the host. The tokens are passed through the environment
+/// of the engine, so they aren't visible in the arguments.
+pub(crate) fn add_registry_tokens(
+    docker: &mut EngineCommand,
+    cargo_home: &Path,
+    metadata: &CargoMetadata,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    let config = CredentialConfig::read(&env::current_dir()?, cargo_home)?;
+    let registries = config.registries(|key| env::var(key).ok());
+    for registry in registries.iter().filter(|r| r.is_used(metadata)) {
+        let token_var = registry_envvar(&registry.name, "TOKEN");
+        if env::var_os(&token_var).is_some() {
+            // already forwarded from the host.
+            continue;
+        }
+        let token = get_token(registry, msg_info)
+            .wrap_err_with(|| format!("when getting the token of registry `{}`", registry.name))?;
+        if let Some(token) = token {
+            docker.pass_env_value(&token_var, &token);
+            docker.args([
+                "-e",
+                &format!(
+                    "{}=cargo:token",
+                    registry_envvar(&registry.name, "CREDENTIAL_PROVIDER")
+                ),
+            ]);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&a| a.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_registries() -> Result<()> {
+        let mut config = CredentialConfig::default();
+        config.merge(toml::from_str(
+            r#"
+            [registry]
+            global-credential-providers = ["cargo:token", "my-alias --store"]
+
+            [registries.public]
+            index = "sparse+https://public.example.com/index/"
+
+            [credential-alias]
+            my-alias = ["/usr/bin/provider", "--flag"]
+            "#,
+        )?);
+        config.merge(toml::from_str(
+            r#"
+            [registries.my-registry]
+            index = "sparse+https://example.com/index/"
+            credential-provider = "cargo:token-from-stdout get-token --registry"
+            "#,
+        )?);
+
+        let get_env = |key: &str| {
+            (key == "CARGO_REGISTRIES_PUBLIC_CREDENTIAL_PROVIDER").then(|| "my-alias".to_owned())
+        };
+        assert_eq!(
+            config.registries(get_env),
+            vec![
+                Registry {
+                    name: "my-registry".to_owned(),
+                    index: "sparse+https://example.com/index/".to_owned(),
+                    providers: vec![args(&[
+                        "cargo:token-from-stdout",
+                        "get-token",
+                        "--registry"
+                    ])],
+                },
+                Registry {
+                    name: "public".to_owned(),
+                    index: "sparse+https://public.example.com/index/".to_owned(),
+                    providers: vec![args(&["/usr/bin/provider", "--flag"])],
+                },
+            ]
+        );
+
+        let registries = config.registries(|_| None);
+        assert_eq!(
+            registries[1].providers,
+            vec![
+                args(&["/usr/bin/provider", "--flag", "--store"]),
+                args(&["cargo:token"]),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_provider_response() -> Result<()> {
+        let hello = r#"{"v":[1]}"#;
+        assert_eq!(
+            parse_provider_response(&format!(
+                "{hello}\n{}\n",
+                r#"{"Ok":{"kind":"get","token":"secret","cache":"session","operation_independent":true}}"#
+            ))?,
+            Some("secret".to_owned())
+        );
+        assert_eq!(
+            parse_provider_response(&format!("{hello}\n{}\n", r#"{"Err":{"kind":"not-found"}}"#))?,
+            None
+        );
+        assert!(parse_provider_response(&format!(
+            "{hello}\n{}\n",
+            r#"{"Err":{"kind":"other","message":"locked"}}"#
+        ))
+        .is_err());
+        assert!(parse_provider_response(hello).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_builtin_provider() -> Result<()> {
+        let metadata: CargoMetadata = serde_json::from_value(serde_json::json!({
+            "workspace_root": "/project",
+            "target_directory": "/project/target",
+            "workspace_members": [],
+            "packages": [{
+                "id": "dep 0.1.0 (sparse+https://example.com/index/)",
+                "name": "dep",
+                "manifest_path": "/cargo/registry/src/dep-0.1.0/Cargo.toml",
+                "source": "sparse+https://example.com/index/",
+                "version": "0.1.0",
+            }],
+        }))?;
+        let registry = |index: &str| Registry {
+            name: "my-registry".to_owned(),
+            index: index.to_owned(),
+            providers: vec![args(&["cargo:libsecret"])],
+        };
+        assert!(registry("sparse+https://example.com/index/").is_used(&metadata));
+        assert!(!registry("https://example.com/git/index").is_used(&metadata));
+
+        let mut msg_info = MessageInfo::default();
+        let err = get_token(
+            &registry("sparse+https://example.com/index/"),
+            &mut msg_info,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("`cargo:libsecret` is not supported"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_registry_envvar() {
+        assert_eq!(
+            registry_envvar("my-registry", "TOKEN"),
+            "CARGO_REGISTRIES_MY_REGISTRY_TOKEN"
+        );
+    }
+}
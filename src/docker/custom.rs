@@ -1,5 +1,6 @@
+use std::collections::BTreeMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use crate::docker::{self, DockerOptions, DockerPaths};
@@ -9,7 +10,7 @@ use crate::{CargoMetadata, TargetTriple};
 
 use super::{
     create_target_dir, get_image_name, path_hash, BuildCommandExt, BuildResultExt, Engine,
-    ImagePlatform,
+    EngineType, ImagePlatform,
 };
 
 pub const CROSS_CUSTOM_DOCKERFILE_IMAGE_PREFIX: &str = "localhost/cross-rs/cross-custom-";
@@ -80,14 +81,61 @@ impl PreBuild {
     }
 }
 
+/// A secret for the build of a custom image, passed with BuildKit so it
+/// isn't stored in the image, unlike build arguments.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BuildSecret {
+    /// The environment variable holding the secret.
+    pub env: Option<String>,
+    /// The file holding the secret, relative to the workspace root.
+    pub file: Option<String>,
+}
+
+impl BuildSecret {
+    /// The value of `--secret` for the secret `id`, with a relative `file`
+    /// resolved against `workspace_root`.
+    pub fn secret_arg(&self, id: &str, workspace_root: &Path) -> Result<String> {
+        match (&self.env, &self.file) {
+            (Some(env), None) => Ok(format!("id={id},env={env}")),
+            (None, Some(file)) => Ok(format!(
+                "id={id},src={}",
+                workspace_root.join(file).to_utf8()?
+            )),
+            _ => eyre::bail!("secret `{id}` must have exactly one of `env` or `file`"),
+        }
+    }
+}
+
+/// The options of `RUN` to mount the secrets, in `/run/secrets/<id>`.
+pub fn secret_mounts(secrets: &BTreeMap<String, BuildSecret>) -> String {
+    secrets
+        .keys()
+        .map(|id| format!(" --mount=type=secret,id={id}"))
+        .collect()
+}
+
 impl<'a> Dockerfile<'a> {
     pub fn build(
         &self,
         options: &DockerOptions,
         paths: &DockerPaths,
         build_args: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<str>)>,
+        secrets: &BTreeMap<String, BuildSecret>,
         msg_info: &mut MessageInfo,
     ) -> Result<String> {
+        if !secrets.is_empty() && !Engine::has_buildkit() {
+            return Err(eyre::eyre!("build secrets require BuildKit"))
+                .with_note(|| {
+                    "build arguments are stored in the history of the image, so secrets are only passed with `--secret`, which needs BuildKit"
+                })
+                .with_suggestion(|| {
+                    format!(
+                        "unset `{}` to build with BuildKit",
+                        Engine::CROSS_CONTAINER_ENGINE_NO_BUILDKIT_ENV
+                    )
+                });
+        }
+
         let uses_zig = options.command_variant.uses_zig();
         let mut docker_build = options.engine.command();
         docker_build.invoke_build_command();
@@ -115,6 +163,10 @@ impl<'a> Dockerfile<'a> {
             docker_build.args(["--build-arg", &format!("{}={}", key.as_ref(), arg.as_ref())]);
         }
 
+        for (id, secret) in secrets {
+            docker_build.args(["--secret", &secret.secret_arg(id, paths.workspace_root())?]);
+        }
+
         if let Some(arch) = options.target.target().deb_arch() {
             docker_build.args(["--build-arg", &format!("CROSS_DEB_ARCH={arch}")]);
         }
@@ -170,6 +222,11 @@ impl<'a> Dockerfile<'a> {
             docker_build.arg(paths.host_root());
         }
 
+        if options.engine.kind == EngineType::Nerdctl {
+            docker::nerdctl::build(&options.engine, &docker_build, msg_info)?;
+            return Ok(image_name);
+        }
+
         // FIXME: Inspect the error message, while still inheriting stdout on verbose mode to
         // conditionally apply this suggestion and note. This could then inspect if a help string is emitted,
         // if the daemon is not running, etc.
@@ -309,4 +366,47 @@ mod tests {
         assert_eq!(docker_tag_name("foo-123"), s!("foo-123"));
         assert_eq!(docker_tag_name("foo-123-"), s!("foo-123"));
     }
+
+    #[test]
+    fn test_build_secrets() -> Result<()> {
+        let env = BuildSecret {
+            env: Some(s!("APT_AUTH")),
+            file: None,
+        };
+        let file = BuildSecret {
+            env: None,
+            file: Some(s!("secrets/token")),
+        };
+        let root = Path::new("/project");
+        assert_eq!(
+            env.secret_arg("apt_auth", root)?,
+            "id=apt_auth,env=APT_AUTH"
+        );
+        assert_eq!(
+            file.secret_arg("token", root)?,
+            "id=token,src=/project/secrets/token"
+        );
+        let absolute = BuildSecret {
+            env: None,
+            file: Some(s!("/etc/token")),
+        };
+        assert_eq!(
+            absolute.secret_arg("token", root)?,
+            "id=token,src=/etc/token"
+        );
+        assert!(BuildSecret {
+            env: None,
+            file: None
+        }
+        .secret_arg("none", root)
+        .is_err());
+
+        let secrets = BTreeMap::from([(s!("apt_auth"), env), (s!("token"), file)]);
+        assert_eq!(
+            secret_mounts(&secrets),
+            " --mount=type=secret,id=apt_auth --mount=type=secret,id=token"
+        );
+
+        Ok(())
+    }
 }
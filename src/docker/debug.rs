@@ -0,0 +1,248 @@
+//! Debug binaries running inside the container with `cross debug`.
+//!
+//! `cross debug` runs `cargo run` with a wrapper runner, which starts the
+//! binary under the gdb stub of `qemu-user`, or `gdbserver` for native
+//! runners, and publishes the port to the host. The libraries of the
+//! target's sysroot in the image are copied to the target directory, so
+//! debuggers on the host can load them.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::shared::runner_envvar;
+use crate::errors::*;
+use crate::extensions::SafeCommand;
+use crate::file::{self, ToUtf8};
+use crate::shell::MessageInfo;
+use crate::{CommandVariant, Target};
+
+pub const DEFAULT_PORT: u16 = 1234;
+pub const SYSROOT_DIR: &str = "cross-debug-sysroot";
+
+/// The options for `cross debug`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugOptions {
+    /// The port the debug server listens on, in the container and the host.
+    pub port: u16,
+    /// Write a VS Code `launch.json` to this path.
+    pub launch_json: Option<PathBuf>,
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        DebugOptions {
+            port: DEFAULT_PORT,
+            launch_json: None,
+        }
+    }
+}
+
+impl DebugOptions {
+    /// The command running `variant` with the wrapper runner. The
+    /// arguments to the cargo command are added to the returned command.
+    pub(crate) fn safe_command(
+        &self,
+        target: &Target,
+        variant: CommandVariant,
+        target_dir: &str,
+    ) -> SafeCommand {
+        let mut cmd = SafeCommand::new("sh");
+        cmd.args(["-c", DEBUG_SCRIPT, "cross-debug"])
+            .arg(&self.port)
+            .arg(&runner_envvar(target))
+            .arg(&sysroot_dir(target_dir, target))
+            .arg(&variant.to_str());
+        cmd
+    }
+
+    /// Print the commands to connect to the debug server from the host,
+    /// and write the `launch.json`, if requested.
+    pub fn print_instructions(
+        &self,
+        binary: &Path,
+        sysroot: &Path,
+        msg_info: &mut MessageInfo,
+    ) -> Result<()> {
+        let address = format!("localhost:{}", self.port);
+        let binary = file::shell_escape(binary.to_utf8()?).into_owned();
+        let sysroot = sysroot.to_utf8()?;
+        msg_info.note(format_args!(
+            "once built, the binary waits for a debugger on {address}, connect with:
+    gdb-multiarch -ex {} -ex {} {binary}
+    lldb {binary} -o {}",
+            file::shell_escape(&format!("set sysroot {sysroot}")),
+            file::shell_escape(&format!("target remote {address}")),
+            file::shell_escape(&format!("gdb-remote {address}")),
+        ))?;
+
+        if let Some(path) = &self.launch_json {
+            let launch = serde_json::json!({
+                "version": "0.2.0",
+                "configurations": [{
+                    "name": "cross debug",
+                    "type": "cppdbg",
+                    "request": "launch",
+                    "program": binary,
+                    "cwd": "${workspaceFolder}",
+                    "MIMode": "gdb",
+                    "miDebuggerPath": "gdb-multiarch",
+                    "miDebuggerServerAddress": address,
+                    "setupCommands": [
+                        { "text": format!("set sysroot {sysroot}") },
+                    ],
+                }],
+            });
+            if let Some(parent) = path.parent() {
+                file::create_dir_all(parent)?;
+            }
+            file::write_file(path, true)?
+                .write_all(serde_json::to_string_pretty(&launch)?.as_bytes())
+                .wrap_err_with(|| format!("when writing {path:?}"))?;
+            msg_info.note(format_args!(
+                "wrote the VS Code launch configuration to {path:?}"
+            ))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The directory the sysroot libraries are copied to, inside `target_dir`.
+pub fn sysroot_dir(target_dir: &str, target: &Target) -> String {
+    format!("{target_dir}/{}/{SYSROOT_DIR}", target.triple())
+}
+
+/// The path to the binary built by `cargo run` with `args`, inside
+/// `target_dir`. `package` is used if no binary or example is provided.
+pub fn binary_path(
+    args: &[String],
+    target_dir: &Path,
+    target: &Target,
+    package: Option<&str>,
+) -> Result<PathBuf> {
+    let mut name = None;
+    let mut example = false;
+    let mut profile = "debug".to_owned();
+    let mut iter = args.iter().take_while(|a| *a != "--");
+    while let Some(arg) = iter.next() {
+        let (key, value) = match arg.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_owned())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || value.clone().or_else(|| iter.next().cloned());
+        match key {
+            "--bin" => name = value(),
+            "--example" => {
+                name = value();
+                example = true;
+            }
+            "--release" | "-r" => profile = "release".to_owned(),
+            "--profile" => {
+                profile = match value().as_deref() {
+                    Some("dev") | Some("test") => "debug".to_owned(),
+                    Some("bench") => "release".to_owned(),
+                    Some(other) => other.to_owned(),
+                    None => profile,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let name = name
+        .or_else(|| package.map(ToOwned::to_owned))
+        .ok_or_else(|| eyre::eyre!("cannot determine the binary to debug"))
+        .with_suggestion(|| "pass the binary to debug with `--bin` or `--example`")?;
+    let mut path = target_dir.join(target.triple()).join(profile);
+    if example {
+        path.push("examples");
+    }
+    path.push(name);
+    if target.triple().contains("windows") {
+        path.set_extension("exe");
+    }
+
+    Ok(path)
+}
+
+// runs the cargo command given as arguments, with the binary run through
+// the wrapper runner. arguments are the port, the name of the cargo runner
+// environment variable, and the directory to copy the sysroot to, followed
+// by the cargo command.
+const DEBUG_SCRIPT: &str = r#"set -e
+port="$1"
+runner_var="$2"
+sysroot="$3"
+shift 3
+
+work=$(mktemp -d)
+trap 'rm -rf "$work"' EXIT
+
+cat > "$work/runner" <<'EOF'
+# debuggers on the host need the shared libraries of the target.
+if [ -n "${QEMU_LD_PREFIX:-}" ] && [ -d "$QEMU_LD_PREFIX" ]; then
+    mkdir -p "$CROSS_DEBUG_SYSROOT"
+    for dir in "$QEMU_LD_PREFIX"/lib*; do
+        [ -d "$dir" ] && cp -RL "$dir" "$CROSS_DEBUG_SYSROOT/" 2>/dev/null || true
+    done
+fi
+
+if [ -z "$CROSS_DEBUG_RUNNER" ] || [ "${CROSS_RUNNER:-}" = native ]; then
+    if ! command -v gdbserver >/dev/null 2>&1; then
+        echo "error: \`gdbserver\` is required to debug binaries run natively, but it's not installed in the image." >&2
+        exit 1
+    fi
+    exec gdbserver "0.0.0.0:$CROSS_DEBUG_PORT" "$@"
+fi
+echo "waiting for a debugger on port $CROSS_DEBUG_PORT" >&2
+QEMU_GDB="$CROSS_DEBUG_PORT" exec $CROSS_DEBUG_RUNNER "$@"
+EOF
+
+CROSS_DEBUG_RUNNER=$(printenv "$runner_var" || true)
+export CROSS_DEBUG_RUNNER CROSS_DEBUG_PORT="$port" CROSS_DEBUG_SYSROOT="$sysroot"
+export "$runner_var=sh $work/runner"
+
+status=0
+"$@" || status=$?
+exit "$status"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| (*a).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_binary_path() -> Result<()> {
+        let target = Target::new_built_in("aarch64-unknown-linux-gnu");
+        let target_dir = Path::new("/project/target");
+        assert_eq!(
+            binary_path(&args(&["run", "--bin", "foo"]), target_dir, &target, None)?,
+            Path::new("/project/target/aarch64-unknown-linux-gnu/debug/foo")
+        );
+        assert_eq!(
+            binary_path(
+                &args(&["run", "--example=bar", "--release", "--", "--bin", "baz"]),
+                target_dir,
+                &target,
+                Some("pkg")
+            )?,
+            Path::new("/project/target/aarch64-unknown-linux-gnu/release/examples/bar")
+        );
+        assert_eq!(
+            binary_path(
+                &args(&["run", "--profile", "ci"]),
+                target_dir,
+                &target,
+                Some("pkg")
+            )?,
+            Path::new("/project/target/aarch64-unknown-linux-gnu/ci/pkg")
+        );
+        assert!(binary_path(&args(&["run"]), target_dir, &target, None).is_err());
+
+        Ok(())
+    }
+}
@@ -1,13 +1,19 @@
 use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
 
 use crate::config::bool_from_envvar;
+use crate::cross_toml::CrossEngineConfig;
 use crate::extensions::CommandExt;
+use crate::file::ToUtf8;
 use crate::shell::MessageInfo;
 use crate::{errors::*, OutputExt};
 
-use super::{Architecture, ContainerOs};
+use super::{Architecture, ContainerOs, SshHost};
 
 pub const DOCKER: &str = "docker";
 pub const PODMAN: &str = "podman";
@@ -18,6 +24,13 @@ pub enum EngineType {
     Podman,
     PodmanRemote,
     Nerdctl,
+    /// `bwrap`, running the unpacked image without an engine.
+    Bwrap,
+    /// An OCI runtime such as `crun` or `runc`, running the unpacked image
+    /// without an engine.
+    OciRuntime,
+    /// `kubectl`, running the container as a pod in a Kubernetes cluster.
+    Kubernetes,
     Other,
 }
 
@@ -34,16 +47,35 @@ impl EngineType {
         matches!(self, Self::Docker)
     }
 
+    /// Returns `true` if the engine type is [`Bwrap`](Self::Bwrap) or [`OciRuntime`](Self::OciRuntime),
+    /// which run the container without an engine.
+    #[must_use]
+    pub const fn is_oci_runtime(&self) -> bool {
+        matches!(self, Self::Bwrap | Self::OciRuntime)
+    }
+
+    /// Returns `true` if the engine type is [`Kubernetes`](Self::Kubernetes).
+    #[must_use]
+    pub const fn is_kubernetes(&self) -> bool {
+        matches!(self, Self::Kubernetes)
+    }
+
     /// Returns `true` if the build command supports the `--output` flag.
     #[must_use]
     pub const fn supports_output_flag(&self) -> bool {
-        !matches!(self, Self::Other)
+        !matches!(
+            self,
+            Self::Bwrap | Self::OciRuntime | Self::Kubernetes | Self::Other
+        )
     }
 
     /// Returns `true` if the build command supports the `--pull` flag.
     #[must_use]
     pub const fn supports_pull_flag(&self) -> bool {
-        !matches!(self, Self::Nerdctl | Self::Other)
+        !matches!(
+            self,
+            Self::Nerdctl | Self::Bwrap | Self::OciRuntime | Self::Kubernetes | Self::Other
+        )
     }
 
     /// Returns `true` if the build command supports the `--cache-from type=` key.
@@ -67,20 +99,26 @@ pub struct Engine {
     pub os: Option<ContainerOs>,
     pub is_remote: bool,
     pub is_rootless: bool,
+    /// The build host the engine runs on, if the engine is run over `ssh`.
+    pub ssh: Option<SshHost>,
 }
 
 impl Engine {
     pub const CROSS_CONTAINER_ENGINE_NO_BUILDKIT_ENV: &'static str =
         "CROSS_CONTAINER_ENGINE_NO_BUILDKIT";
+    /// Select the container engine from `CROSS_CONTAINER_ENGINE`, or from
+    /// the candidates in `config`. The candidates are tried in order,
+    /// skipping engines that aren't running if another one is available.
     pub fn new(
         in_docker: Option<bool>,
         is_remote: Option<bool>,
+        config: &CrossEngineConfig,
         msg_info: &mut MessageInfo,
     ) -> Result<Engine> {
-        #[allow(clippy::map_err_ignore)]
-        let path = get_container_engine()
-            .map_err(|_| eyre::eyre!("no container engine found"))
-            .with_suggestion(|| "is docker or podman installed?")?;
+        if let Some(ssh) = SshHost::from_env()? {
+            return Self::from_ssh(ssh, None, in_docker, msg_info);
+        }
+        let path = select_container_engine(config, msg_info)?;
         Self::from_path(path, in_docker, is_remote, msg_info)
     }
 
@@ -90,12 +128,74 @@ impl Engine {
         is_remote: Option<bool>,
         msg_info: &mut MessageInfo,
     ) -> Result<Engine> {
+        Self::create(path, None, in_docker, is_remote, msg_info)
+    }
+
+    /// Use the container engine on a build host over `ssh`. The engine
+    /// defaults to `CROSS_CONTAINER_ENGINE`, or `docker` if not provided.
+    /// Since no sockets are shared, this always uses remote cross.
+    pub fn from_ssh(
+        ssh: SshHost,
+        engine: Option<&str>,
+        in_docker: Option<bool>,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Engine> {
+        let path = match engine {
+            Some(engine) => PathBuf::from(engine),
+            None => env::var_os("CROSS_CONTAINER_ENGINE")
+                .map_or_else(|| PathBuf::from(DOCKER), PathBuf::from),
+        };
+        Self::create(path, Some(ssh), in_docker, Some(true), msg_info)
+    }
+
+    fn create(
+        path: PathBuf,
+        ssh: Option<SshHost>,
+        in_docker: Option<bool>,
+        is_remote: Option<bool>,
+        msg_info: &mut MessageInfo,
+    ) -> Result<Engine> {
+        if let Some(kind) = oci_runtime_kind(&path) {
+            if ssh.is_some() {
+                eyre::bail!("OCI runtimes are not supported on a build host over `ssh`");
+            }
+            // the runtime runs the image directly on this host, so it
+            // always shares our mount namespace and architecture.
+            return Ok(Engine {
+                path,
+                kind,
+                in_docker: false,
+                arch: Architecture::new(env::consts::ARCH).ok(),
+                os: Some(ContainerOs::Linux),
+                is_remote: false,
+                is_rootless: true,
+                ssh: None,
+            });
+        }
+        if is_kubectl(&path) {
+            if ssh.is_some() {
+                eyre::bail!("Kubernetes is not supported on a build host over `ssh`");
+            }
+            // the pod runs on a node of the cluster, so data is always
+            // copied to it, and the node is selected by the image platform.
+            return Ok(Engine {
+                path,
+                kind: EngineType::Kubernetes,
+                in_docker: false,
+                arch: None,
+                os: Some(ContainerOs::Linux),
+                is_remote: true,
+                is_rootless: true,
+                ssh: None,
+            });
+        }
         let in_docker = match in_docker {
             Some(v) => v,
             None => Self::in_docker(msg_info)?,
         };
-        let (kind, arch, os) = get_engine_info(&path, msg_info)?;
-        let is_rootless = is_rootless(kind).unwrap_or_else(|| is_docker_rootless(&path, msg_info));
+        let ce = EngineQuery(&path, ssh.as_ref());
+        let (kind, arch, os) = get_engine_info(ce, msg_info)?;
+        let is_rootless = is_rootless(kind).unwrap_or_else(|| is_docker_rootless(ce, msg_info));
         let is_remote = is_remote.unwrap_or_else(Self::is_remote);
         Ok(Engine {
             path,
@@ -105,12 +205,14 @@ impl Engine {
             os,
             is_remote,
             is_rootless,
+            ssh,
         })
     }
 
     #[must_use]
     pub fn needs_remote(&self) -> bool {
-        self.is_remote && self.kind == EngineType::Podman
+        // podman on a build host over `ssh` runs locally there.
+        self.is_remote && self.ssh.is_none() && self.kind == EngineType::Podman
     }
 
     pub fn in_docker(msg_info: &mut MessageInfo) -> Result<bool> {
@@ -133,8 +235,16 @@ impl Engine {
 
     #[must_use]
     pub fn is_remote() -> bool {
+        Self::is_remote_or(None)
+    }
+
+    /// If the engine is remote, from `CROSS_REMOTE` or else `default`.
+    #[must_use]
+    pub fn is_remote_or(default: Option<bool>) -> bool {
         env::var("CROSS_REMOTE")
+            .ok()
             .map(|s| bool_from_envvar(&s))
+            .or(default)
             .unwrap_or_default()
     }
 
@@ -146,6 +256,279 @@ impl Engine {
     }
 }
 
+/// A command running the container engine.
+///
+/// On a build host over `ssh`, the arguments are joined into a single
+/// string and evaluated by the login shell of the host, so each argument
+/// is escaped as it is added. Only the methods of the wrapped [`Command`]
+/// that can't add arguments are available, so nothing bypasses the escaping.
+pub struct EngineCommand {
+    command: Command,
+    escape: bool,
+}
+
+impl EngineCommand {
+    pub fn new(program: impl AsRef<OsStr>) -> Self {
+        EngineCommand {
+            command: Command::new(program),
+            escape: false,
+        }
+    }
+
+    /// Wrap a command whose arguments are evaluated by a shell.
+    pub(crate) fn shell_escaped(command: Command) -> Self {
+        EngineCommand {
+            command,
+            escape: true,
+        }
+    }
+
+    pub fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        if self.escape {
+            let arg = arg.as_ref().to_string_lossy();
+            self.command.arg(&*shell_escape::unix::escape(arg));
+        } else {
+            self.command.arg(arg);
+        }
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+}
+
+impl EngineCommand {
+    /// Pass the variable `var` to the container, either as `KEY=value`, or as
+    /// `KEY` to forward its value from this process, if it's set. On a build
+    /// host over `ssh`, the engine would read `KEY` from the environment of
+    /// the host, so the value is read here and passed in the arguments.
+    pub fn pass_env(&mut self, var: &str) -> &mut Self {
+        if !self.escape || var.contains('=') {
+            return self.args(["-e", var]);
+        }
+        match env::var_os(var) {
+            Some(value) => {
+                let mut arg = OsString::from(format!("{var}="));
+                arg.push(value);
+                self.arg("-e").arg(arg)
+            }
+            None => self,
+        }
+    }
+
+    /// Set the variable `key` of the container to `value`, which is passed
+    /// through the environment of the engine, so it isn't visible in the
+    /// arguments. On a build host over `ssh`, the environment doesn't reach
+    /// the engine, so the value is passed in the arguments instead.
+    pub fn pass_env_value(&mut self, key: &str, value: &str) -> &mut Self {
+        if self.escape {
+            self.args(["-e", &format!("{key}={value}")])
+        } else {
+            self.env(key, value).args(["-e", key])
+        }
+    }
+
+    /// Set a variable for the engine itself, not for the container.
+    pub fn env(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.command.env(key, value);
+        self
+    }
+
+    pub fn current_dir(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.command.current_dir(dir);
+        self
+    }
+
+    pub fn stdin(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.command.stdin(cfg);
+        self
+    }
+
+    pub fn stdout(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.command.stdout(cfg);
+        self
+    }
+
+    pub fn stderr(&mut self, cfg: impl Into<Stdio>) -> &mut Self {
+        self.command.stderr(cfg);
+        self
+    }
+
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        self.command.spawn()
+    }
+
+    /// A copy of the command, with the same arguments and environment, to
+    /// run it more than once with different final arguments.
+    pub fn duplicate(&self) -> Self {
+        let mut command = Command::new(self.command.get_program());
+        command.args(self.command.get_args());
+        for (key, value) in self.command.get_envs() {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        if let Some(dir) = self.command.get_current_dir() {
+            command.current_dir(dir);
+        }
+        EngineCommand {
+            command,
+            escape: self.escape,
+        }
+    }
+}
+
+impl From<Command> for EngineCommand {
+    fn from(command: Command) -> Self {
+        EngineCommand {
+            command,
+            escape: false,
+        }
+    }
+}
+
+impl fmt::Debug for EngineCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.command.fmt(f)
+    }
+}
+
+impl CommandExt for EngineCommand {
+    fn fmt_message(&self, msg_info: &mut MessageInfo) -> String {
+        self.command.fmt_message(msg_info)
+    }
+
+    fn status_result(
+        &self,
+        msg_info: &mut MessageInfo,
+        status: ExitStatus,
+        output: Option<&Output>,
+    ) -> Result<(), CommandError> {
+        self.command.status_result(msg_info, status, output)
+    }
+
+    #[track_caller]
+    fn run(&mut self, msg_info: &mut MessageInfo, silence_stdout: bool) -> Result<()> {
+        self.command.run(msg_info, silence_stdout)
+    }
+
+    #[track_caller]
+    fn run_and_get_status(
+        &mut self,
+        msg_info: &mut MessageInfo,
+        silence_stdout: bool,
+    ) -> Result<ExitStatus> {
+        self.command.run_and_get_status(msg_info, silence_stdout)
+    }
+
+    #[track_caller]
+    fn run_and_get_status_with_stdin(
+        &mut self,
+        stdin: &[u8],
+        msg_info: &mut MessageInfo,
+        silence_stdout: bool,
+    ) -> Result<ExitStatus> {
+        self.command
+            .run_and_get_status_with_stdin(stdin, msg_info, silence_stdout)
+    }
+
+    #[track_caller]
+    fn run_and_get_stdout(&mut self, msg_info: &mut MessageInfo) -> Result<String> {
+        self.command.run_and_get_stdout(msg_info)
+    }
+
+    #[track_caller]
+    fn run_and_get_output(&mut self, msg_info: &mut MessageInfo) -> Result<Output> {
+        self.command.run_and_get_output(msg_info)
+    }
+
+    fn command_pretty(
+        &self,
+        msg_info: &mut MessageInfo,
+        strip: impl for<'a> Fn(&'a str) -> bool,
+    ) -> String {
+        self.command.command_pretty(msg_info, strip)
+    }
+}
+
+impl Deref for EngineCommand {
+    type Target = Command;
+
+    fn deref(&self) -> &Self::Target {
+        &self.command
+    }
+}
+
+/// The value of `option` when translating the arguments of an engine command.
+pub(crate) fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String> {
+    args.next()
+        .ok_or_else(|| eyre::eyre!("missing value for `{option}`"))
+}
+
+/// The variable `key` of an engine command. Variables set for the engine,
+/// such as registry tokens, take precedence over the environment.
+pub(crate) fn command_env(docker: &Command, key: &str) -> Option<String> {
+    match docker.get_envs().find(|(k, _)| *k == key) {
+        Some((_, value)) => value.and_then(|v| v.to_str()).map(ToOwned::to_owned),
+        None => env::var(key).ok(),
+    }
+}
+
+/// Add the variable of an `-e` option. Like an engine, passthrough variables
+/// are only added if they're set.
+pub(crate) fn push_env(
+    env: &mut Vec<(String, String)>,
+    var: String,
+    get_env: &impl Fn(&str) -> Option<String>,
+) {
+    match var.split_once('=') {
+        Some((key, value)) => env.push((key.to_owned(), value.to_owned())),
+        None => {
+            if let Some(value) = get_env(&var) {
+                env.push((var, value));
+            }
+        }
+    }
+}
+
+// the command used to query the engine before it is created.
+#[derive(Clone, Copy)]
+struct EngineQuery<'a>(&'a Path, Option<&'a SshHost>);
+
+impl EngineQuery<'_> {
+    fn command(self) -> EngineCommand {
+        match self.1 {
+            Some(ssh) => ssh.command(self.0),
+            None => EngineCommand::new(self.0),
+        }
+    }
+}
+
+// the OCI runtimes are detected by name, since they don't have the
+// `version` subcommand of a container engine.
+fn oci_runtime_kind(path: &Path) -> Option<EngineType> {
+    match path.file_stem()?.to_str()? {
+        "bwrap" => Some(EngineType::Bwrap),
+        "crun" | "runc" => Some(EngineType::OciRuntime),
+        _ => None,
+    }
+}
+
+// `kubectl` is detected by name, since it manages a cluster rather
+// than containers.
+fn is_kubectl(path: &Path) -> bool {
+    path.file_stem().and_then(|s| s.to_str()) == Some("kubectl")
+}
+
 fn is_rootless(kind: EngineType) -> Option<bool> {
     env::var("CROSS_ROOTLESS_CONTAINER_ENGINE")
         .ok()
@@ -153,24 +536,28 @@ fn is_rootless(kind: EngineType) -> Option<bool> {
             "auto" => None,
             b => Some(bool_from_envvar(b)),
         })
-        .or_else(|| (!kind.is_docker()).then_some(true))
+        .or_else(|| (!matches!(kind, EngineType::Docker | EngineType::Nerdctl)).then_some(true))
 }
 
+// both docker and nerdctl report rootless mode in the security options.
 #[must_use]
-fn is_docker_rootless(ce: &Path, msg_info: &mut MessageInfo) -> bool {
-    let mut cmd = Command::new(ce);
+fn is_docker_rootless(ce: EngineQuery<'_>, msg_info: &mut MessageInfo) -> bool {
+    let mut cmd = ce.command();
     cmd.args(["info", "-f", "{{.SecurityOptions}}"])
         .run_and_get_output(msg_info)
         .ok()
         .and_then(|cmd| cmd.stdout().ok())
-        .map(|out| {
-            out.to_lowercase()
-                .replace(['[', ' ', ']'], ",")
-                .contains(",name=rootless,")
-        })
+        .map(|out| has_rootless_option(&out))
         .unwrap_or_default()
 }
 
+fn has_rootless_option(security_options: &str) -> bool {
+    security_options
+        .to_lowercase()
+        .replace(['[', ' ', ']'], ",")
+        .contains(",name=rootless,")
+}
+
 #[test]
 fn various_is_rootless_configs() {
     let var = "CROSS_ROOTLESS_CONTAINER_ENGINE";
@@ -181,6 +568,7 @@ fn various_is_rootless_configs() {
     assert!(is_rootless(EngineType::Docker).unwrap_or(true));
 
     assert_eq!(is_rootless(EngineType::Docker), None);
+    assert_eq!(is_rootless(EngineType::Nerdctl), None);
     assert_eq!(is_rootless(EngineType::Podman), Some(true));
     assert_eq!(is_rootless(EngineType::PodmanRemote), Some(true));
     assert_eq!(is_rootless(EngineType::Other), Some(true));
@@ -199,6 +587,7 @@ fn various_is_rootless_configs() {
 
     env::set_var(var, "auto");
     assert_eq!(is_rootless(EngineType::Docker), None);
+    assert_eq!(is_rootless(EngineType::Nerdctl), None);
     assert_eq!(is_rootless(EngineType::Podman), Some(true));
     assert_eq!(is_rootless(EngineType::PodmanRemote), Some(true));
     assert_eq!(is_rootless(EngineType::Other), Some(true));
@@ -209,28 +598,64 @@ fn various_is_rootless_configs() {
     }
 }
 
+#[test]
+fn recorded_nerdctl_outputs() {
+    // `nerdctl --help`
+    let help = "nerdctl is a command line interface for containerd
+
+Config file ($NERDCTL_TOML): /etc/nerdctl/nerdctl.toml
+
+Usage: nerdctl [flags]
+
+Management commands:
+  apparmor    Manage AppArmor profiles
+  builder     Manage builds
+  container   Manage containers
+";
+    assert_eq!(engine_type(help), EngineType::Nerdctl);
+
+    // `nerdctl info -f {{.SecurityOptions}}`, rootless and rootful
+    assert!(has_rootless_option(
+        "[name=seccomp,profile=default name=cgroupns name=rootless]\n"
+    ));
+    assert!(!has_rootless_option(
+        "[name=apparmor name=seccomp,profile=default name=cgroupns]\n"
+    ));
+}
+
+#[test]
+fn engine_candidates_from_config() {
+    let mut config = CrossEngineConfig::default();
+    assert_eq!(
+        engine_candidates(&config),
+        [PathBuf::from(DOCKER), PathBuf::from(PODMAN)]
+    );
+
+    config.prefer = vec![PODMAN.to_owned(), DOCKER.to_owned()];
+    assert_eq!(
+        engine_candidates(&config),
+        [PathBuf::from(PODMAN), PathBuf::from(DOCKER)]
+    );
+
+    config.path = Some(PathBuf::from("/opt/bin/docker"));
+    assert_eq!(
+        engine_candidates(&config),
+        [
+            PathBuf::from("/opt/bin/docker"),
+            PathBuf::from(PODMAN),
+            PathBuf::from(DOCKER)
+        ]
+    );
+}
+
 // determine if the container engine is docker. this fixes issues with
 // any aliases (#530), and doesn't fail if an executable suffix exists.
 fn get_engine_info(
-    ce: &Path,
+    ce: EngineQuery<'_>,
     msg_info: &mut MessageInfo,
 ) -> Result<(EngineType, Option<Architecture>, Option<ContainerOs>)> {
-    let stdout_help = Command::new(ce)
-        .arg("--help")
-        .run_and_get_stdout(msg_info)?
-        .to_lowercase();
-
-    let kind = if stdout_help.contains("podman-remote") {
-        EngineType::PodmanRemote
-    } else if stdout_help.contains("podman") {
-        EngineType::Podman
-    } else if stdout_help.contains("nerdctl") {
-        EngineType::Nerdctl
-    } else if stdout_help.contains("docker") && !stdout_help.contains("emulate") {
-        EngineType::Docker
-    } else {
-        EngineType::Other
-    };
+    let stdout_help = ce.command().arg("--help").run_and_get_stdout(msg_info)?;
+    let kind = engine_type(&stdout_help);
 
     // this can fail: podman can give partial output
     //   linux,,,Error: template: version:1:15: executing "version" at <.Arch>:
@@ -263,6 +688,21 @@ fn get_engine_info(
     Ok((kind, arch, os))
 }
 
+fn engine_type(help: &str) -> EngineType {
+    let help = help.to_lowercase();
+    if help.contains("podman-remote") {
+        EngineType::PodmanRemote
+    } else if help.contains("podman") {
+        EngineType::Podman
+    } else if help.contains("nerdctl") {
+        EngineType::Nerdctl
+    } else if help.contains("docker") && !help.contains("emulate") {
+        EngineType::Docker
+    } else {
+        EngineType::Other
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum EngineInfoError {
     #[error(transparent)]
@@ -284,12 +724,12 @@ impl EngineInfoError {
 
 /// Get engine info
 fn engine_info(
-    ce: &Path,
+    ce: EngineQuery<'_>,
     args: &[&str],
     sep: &str,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<(ContainerOs, Architecture)>, EngineInfoError> {
-    let mut cmd = Command::new(ce);
+    let mut cmd = ce.command();
     cmd.args(args);
     let out = cmd
         .run_and_get_output(msg_info)
@@ -307,14 +747,14 @@ fn engine_info(
 }
 
 fn get_podman_info(
-    ce: &Path,
+    ce: EngineQuery<'_>,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<(ContainerOs, Architecture)>, EngineInfoError> {
     engine_info(ce, &["info", "-f", "{{ .Version.OsArch }}"], "/", msg_info)
 }
 
 fn get_custom_info(
-    ce: &Path,
+    ce: EngineQuery<'_>,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<(ContainerOs, Architecture)>, EngineInfoError> {
     engine_info(
@@ -325,6 +765,66 @@ fn get_custom_info(
     )
 }
 
+// the engines to try, in order, if `CROSS_CONTAINER_ENGINE` isn't set.
+fn engine_candidates(config: &CrossEngineConfig) -> Vec<PathBuf> {
+    let mut candidates: Vec<_> = config
+        .path
+        .iter()
+        .cloned()
+        .chain(config.prefer.iter().map(PathBuf::from))
+        .collect();
+    if candidates.is_empty() {
+        candidates = vec![PathBuf::from(DOCKER), PathBuf::from(PODMAN)];
+    }
+    candidates
+}
+
+// an engine is running if it can report its version: this fails if
+// the daemon or the virtual machine of the engine isn't running.
+fn is_engine_running(path: &Path, msg_info: &mut MessageInfo) -> bool {
+    if oci_runtime_kind(path).is_some() {
+        return true;
+    }
+    Command::new(path)
+        .arg("version")
+        .run_and_get_output(msg_info)
+        .map_or(false, |output| output.status.success())
+}
+
+fn select_container_engine(
+    config: &CrossEngineConfig,
+    msg_info: &mut MessageInfo,
+) -> Result<PathBuf> {
+    if env::var_os("CROSS_CONTAINER_ENGINE").is_some() {
+        #[allow(clippy::map_err_ignore)]
+        return get_container_engine()
+            .map_err(|_| eyre::eyre!("no container engine found"))
+            .with_suggestion(|| "is `CROSS_CONTAINER_ENGINE` installed?");
+    }
+
+    let found: Vec<_> = engine_candidates(config)
+        .iter()
+        .filter_map(|engine| which::which(engine).ok())
+        .collect();
+    for (index, path) in found.iter().enumerate() {
+        // the last engine is used as is, so it reports its own errors.
+        if index + 1 == found.len() || is_engine_running(path, msg_info) {
+            return Ok(path.clone());
+        }
+        msg_info.warn(format_args!(
+            "container engine `{}` is not running, trying the next one.",
+            path.to_utf8()?
+        ))?;
+    }
+
+    Err(eyre::eyre!("no container engine found")).with_suggestion(|| {
+        match config.path.is_none() && config.prefer.is_empty() {
+            true => "is docker or podman installed?",
+            false => "are the engines in `build.engine` installed?",
+        }
+    })
+}
+
 pub fn get_container_engine() -> Result<PathBuf, which::Error> {
     if let Ok(ce) = env::var("CROSS_CONTAINER_ENGINE") {
         which::which(ce)
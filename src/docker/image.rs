@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{errors::*, shell::MessageInfo, TargetTriple};
 
-use super::Engine;
+use super::{Engine, EngineCommand};
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub struct Image {
@@ -376,7 +376,7 @@ impl ImagePlatform {
         }
     }
 
-    pub fn specify_platform(&self, engine: &Engine, cmd: &mut std::process::Command) {
+    pub fn specify_platform(&self, engine: &Engine, cmd: &mut EngineCommand) {
         if self.variant.is_none()
             && Some(&self.architecture) == engine.arch.as_ref()
             && Some(&self.os) == engine.os.as_ref()
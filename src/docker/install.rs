@@ -0,0 +1,109 @@
+//! Install binaries for a target with `cross install`.
+//!
+//! `cargo install` runs in the container with its root in the target
+//! directory, and the installed binaries are then copied to the `--root`
+//! on the host. `cross install` usually runs outside of a package, so the
+//! metadata is created from the `--path` source, if any, and the target
+//! directory is kept in the data directory of `cross`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cargo::CargoMetadata;
+use crate::errors::*;
+use crate::extensions::SafeCommand;
+use crate::file;
+use crate::shell::MessageInfo;
+use crate::temp;
+use crate::{CommandVariant, Target};
+
+pub const INSTALL_DIR: &str = "cross-install";
+
+/// The root of `cargo install`, inside `target_dir`.
+pub fn install_dir(target_dir: &str, target: &Target) -> String {
+    format!("{target_dir}/{}/{INSTALL_DIR}", target.triple())
+}
+
+/// The root of `cargo install` on the host.
+pub fn host_install_dir(target_dir: &Path, target: &Target) -> PathBuf {
+    target_dir.join(target.triple()).join(INSTALL_DIR)
+}
+
+/// The command running `variant` with a clean install root in
+/// `install_dir`. The arguments to the cargo command are added to the
+/// returned command.
+pub(crate) fn safe_command(install_dir: &str, variant: CommandVariant) -> SafeCommand {
+    let mut cmd = SafeCommand::new("sh");
+    cmd.args([
+        "-c",
+        r#"rm -rf "$1" && export CARGO_INSTALL_ROOT="$1" && shift && exec "$@""#,
+        "cross-install",
+        install_dir,
+    ])
+    .arg(&variant.to_str());
+    cmd
+}
+
+/// The metadata used to install a package. Only the package at `path`,
+/// if any, is mounted in the container, rather than the current directory.
+pub fn metadata(path: Option<&Path>, target_dir: Option<&Path>) -> Result<CargoMetadata> {
+    let target_directory = match target_dir {
+        Some(target_dir) => target_dir.to_owned(),
+        None => temp::dir()?.with_file_name("install-target"),
+    };
+
+    Ok(CargoMetadata {
+        workspace_root: path.unwrap_or(&target_directory).to_owned(),
+        target_directory,
+        packages: vec![],
+        workspace_members: vec![],
+        metadata: None,
+    })
+}
+
+/// The `--root` of `cargo install` on the host, if not provided.
+pub fn default_root() -> Result<PathBuf> {
+    match env::var_os("CARGO_INSTALL_ROOT") {
+        Some(root) => Ok(PathBuf::from(root)),
+        None => home::cargo_home().wrap_err("could not find the cargo home"),
+    }
+}
+
+/// Copy the binaries installed into `dir` to `root` on the host.
+pub fn copy_binaries(dir: &Path, root: &Path, msg_info: &mut MessageInfo) -> Result<()> {
+    let bin = dir.join("bin");
+    let entries = match fs::read_dir(&bin) {
+        Ok(entries) => entries,
+        // nothing was installed.
+        Err(_) => return Ok(()),
+    };
+    let dst = root.join("bin");
+    file::create_dir_all(&dst)?;
+    for entry in entries {
+        let entry = entry?;
+        let path = dst.join(entry.file_name());
+        fs::copy(entry.path(), &path)
+            .wrap_err_with(|| format!("when copying {:?} to {path:?}", entry.path()))?;
+        msg_info.status(format_args!("Installed {path:?}"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata() -> Result<()> {
+        let target_dir = Path::new("/home/user/target");
+        let from_path = metadata(Some(Path::new("/src/app")), Some(target_dir))?;
+        assert_eq!(from_path.workspace_root, Path::new("/src/app"));
+        assert_eq!(from_path.target_directory, target_dir);
+        let from_registry = metadata(None, Some(target_dir))?;
+        assert_eq!(from_registry.workspace_root, target_dir);
+
+        Ok(())
+    }
+}
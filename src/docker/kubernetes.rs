@@ -0,0 +1,707 @@
+//! Run the container as a pod in a Kubernetes cluster.
+//!
+//! With `CROSS_CONTAINER_ENGINE` set to `kubectl`, remote cross creates a pod
+//! from the image rather than a container, in the namespace provided with
+//! `CROSS_KUBERNETES_NAMESPACE`, or the namespace of the current context.
+//! The `docker run` and `docker exec` commands built for an engine are
+//! translated into the pod manifest and `kubectl exec`, and data is copied
+//! into the pod the same as with the data volumes of an engine.
+//!
+//! Without persistent volumes, the toolchain is copied for every build. With
+//! `CROSS_KUBERNETES_PVC`, the toolchains are instead kept in subdirectories
+//! of the persistent volume claim, and are only copied on the first build.
+//!
+//! The environment variables of the container, which can hold registry
+//! tokens, are kept in a secret named after the pod rather than in the spec
+//! of the pod.
+
+use std::env;
+use std::process::{Command, ExitStatus};
+
+use serde_json::json;
+
+use super::engine::{command_env, next_value, push_env, Engine, EngineCommand};
+use super::shared::{ContainerState, VolumeId};
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::file::ToUtf8;
+use crate::shell::MessageInfo;
+
+pub const CROSS_KUBERNETES_NAMESPACE: &str = "CROSS_KUBERNETES_NAMESPACE";
+pub const CROSS_KUBERNETES_PVC: &str = "CROSS_KUBERNETES_PVC";
+
+// the name of the container in the pod.
+const CONTAINER_NAME: &str = "cross";
+// the name of the persistent volume in the pod.
+const CLAIM_VOLUME_NAME: &str = "toolchains";
+// how long to wait for the pod to be scheduled and the image pulled.
+const POD_READY_TIMEOUT: &str = "300s";
+
+/// The namespace provided with `CROSS_KUBERNETES_NAMESPACE`.
+pub fn namespace() -> Option<String> {
+    env::var(CROSS_KUBERNETES_NAMESPACE)
+        .ok()
+        .filter(|ns| !ns.is_empty())
+}
+
+/// The persistent volume claim provided with `CROSS_KUBERNETES_PVC`.
+pub fn persistent_volume_claim() -> Option<String> {
+    env::var(CROSS_KUBERNETES_PVC)
+        .ok()
+        .filter(|pvc| !pvc.is_empty())
+}
+
+/// The data volume for the toolchain: a subdirectory of the persistent
+/// volume claim if provided, or otherwise an empty directory in the pod.
+pub(crate) fn volume(toolchain_id: String) -> VolumeId {
+    match persistent_volume_claim() {
+        Some(_) => VolumeId::Keep(toolchain_id),
+        None => VolumeId::Discard,
+    }
+}
+
+/// The name of the pod for the container `id`, which must be a valid
+/// DNS subdomain.
+pub(crate) fn pod_name(id: &str) -> String {
+    let name: String = id
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9' | '-' | '.') => c,
+            _ => '-',
+        })
+        .take(253)
+        .collect();
+    name.trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_owned()
+}
+
+// the arguments of the engine command, after the subcommand.
+fn subcommand_args(engine: &Engine, docker: &Command) -> Result<Vec<String>> {
+    let global = engine.command().get_args().count();
+    docker
+        .get_args()
+        .skip(global + 1)
+        .map(|arg| arg.to_utf8().map(ToOwned::to_owned))
+        .collect()
+}
+
+// convert a size in the format of the engines, like `8g`, `1.5GiB` or
+// `512 MB`, into a Kubernetes quantity like `8Gi`. the units are binary.
+fn byte_size_quantity(value: &str) -> Result<String> {
+    let size = value.to_ascii_lowercase();
+    let index = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(index);
+    let unit = unit.trim_start();
+    let unit = unit.strip_suffix('b').unwrap_or(unit);
+    let suffix = match unit.strip_suffix('i').unwrap_or(unit) {
+        "" => "",
+        "k" => "Ki",
+        "m" => "Mi",
+        "g" => "Gi",
+        "t" => "Ti",
+        "p" => "Pi",
+        _ => eyre::bail!("invalid size `{value}`"),
+    };
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let is_number = match number.split_once('.') {
+        Some((integer, fraction)) => is_digits(integer) && is_digits(fraction),
+        None => is_digits(number),
+    };
+    if !is_number {
+        eyre::bail!("invalid size `{value}`");
+    }
+
+    Ok(format!("{number}{suffix}"))
+}
+
+/// The pod described by a `docker run` command.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PodSpec {
+    image: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    // the named volumes, as the subdirectory of the claim and the mount path.
+    claims: Vec<(String, String)>,
+    // the anonymous volumes.
+    empty_dirs: Vec<String>,
+    platform: Option<String>,
+    cpus: Option<String>,
+    memory: Option<String>,
+    shm_size: Option<String>,
+}
+
+impl PodSpec {
+    fn from_run_args(
+        args: impl IntoIterator<Item = String>,
+        get_env: impl Fn(&str) -> Option<String>,
+    ) -> Result<PodSpec> {
+        let mut spec = PodSpec::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-v" | "--volume" => {
+                    let volume = next_value(&mut args, &arg)?;
+                    let mut parts = volume.splitn(3, ':');
+                    match (parts.next(), parts.next()) {
+                        (Some(dst), None) => spec.empty_dirs.push(dst.to_owned()),
+                        (Some(src), Some(dst)) if !src.starts_with('/') => {
+                            spec.claims.push((src.to_owned(), dst.to_owned()));
+                        }
+                        _ => eyre::bail!("cannot mount `{volume}` in a pod, only volumes"),
+                    }
+                }
+                "-e" | "--env" => push_env(&mut spec.env, next_value(&mut args, &arg)?, &get_env),
+                "--platform" => spec.platform = Some(next_value(&mut args, &arg)?),
+                "--cpus" => spec.cpus = Some(next_value(&mut args, &arg)?),
+                "--memory" => {
+                    spec.memory = Some(byte_size_quantity(&next_value(&mut args, &arg)?)?);
+                }
+                "--shm-size" => {
+                    spec.shm_size = Some(byte_size_quantity(&next_value(&mut args, &arg)?)?);
+                }
+                // pods are named from the manifest, and use the default
+                // seccomp profile and user namespace of the cluster.
+                "--name" | "--userns" | "--security-opt" | "--label" => {
+                    next_value(&mut args, &arg)?;
+                }
+                "--rm" | "-d" | "-t" | "-i" => {}
+                _ if arg.starts_with('-') => {
+                    eyre::bail!("option `{arg}` is not supported with Kubernetes")
+                }
+                _ => {
+                    spec.image = arg;
+                    spec.args = args.collect();
+                    return Ok(spec);
+                }
+            }
+        }
+
+        eyre::bail!("no image in the container command")
+    }
+
+    fn manifest(&self, name: &str, claim: Option<&str>) -> Result<serde_json::Value> {
+        let mut mounts = vec![];
+        let mut volumes = vec![];
+        if !self.claims.is_empty() {
+            let claim = claim.ok_or_else(|| {
+                eyre::eyre!("persistent volumes require `{CROSS_KUBERNETES_PVC}`")
+            })?;
+            volumes.push(json!({
+                "name": CLAIM_VOLUME_NAME,
+                "persistentVolumeClaim": { "claimName": claim },
+            }));
+        }
+        for (subpath, path) in &self.claims {
+            mounts.push(json!({
+                "name": CLAIM_VOLUME_NAME,
+                "mountPath": path,
+                "subPath": subpath,
+            }));
+        }
+        for (index, path) in self.empty_dirs.iter().enumerate() {
+            let volume = format!("empty-{index}");
+            mounts.push(json!({ "name": volume, "mountPath": path }));
+            volumes.push(json!({ "name": volume, "emptyDir": {} }));
+        }
+        // `/dev/shm` is sized with a volume in memory.
+        if let Some(size) = &self.shm_size {
+            mounts.push(json!({ "name": "shm", "mountPath": "/dev/shm" }));
+            volumes.push(json!({
+                "name": "shm",
+                "emptyDir": { "medium": "Memory", "sizeLimit": size },
+            }));
+        }
+        let mut limits = serde_json::Map::new();
+        if let Some(cpus) = &self.cpus {
+            limits.insert("cpu".to_owned(), json!(cpus));
+        }
+        if let Some(memory) = &self.memory {
+            limits.insert("memory".to_owned(), json!(memory));
+        }
+        let env: Vec<_> = self
+            .env
+            .iter()
+            .map(|(key, _)| {
+                json!({
+                    "name": key,
+                    "valueFrom": { "secretKeyRef": { "name": name, "key": key } },
+                })
+            })
+            .collect();
+
+        // run on a node matching the platform of the image.
+        let mut node_selector = serde_json::Map::new();
+        if let Some(platform) = &self.platform {
+            let mut parts = platform.split('/');
+            if let Some(os) = parts.next() {
+                node_selector.insert("kubernetes.io/os".to_owned(), json!(os));
+            }
+            if let Some(arch) = parts.next() {
+                node_selector.insert("kubernetes.io/arch".to_owned(), json!(arch));
+            }
+        }
+
+        Ok(json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": name,
+                "labels": { "app.kubernetes.io/managed-by": "cross" },
+            },
+            "spec": {
+                "restartPolicy": "Never",
+                "nodeSelector": node_selector,
+                "containers": [{
+                    "name": CONTAINER_NAME,
+                    "image": self.image,
+                    "args": self.args,
+                    "env": env,
+                    "volumeMounts": mounts,
+                    "resources": { "limits": limits },
+                }],
+                "volumes": volumes,
+            },
+        }))
+    }
+
+    // the secret holding the environment variables of the pod.
+    fn secret(&self, name: &str) -> serde_json::Value {
+        let data: serde_json::Map<_, _> = self
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), json!(value)))
+            .collect();
+
+        json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": {
+                "name": name,
+                "labels": { "app.kubernetes.io/managed-by": "cross" },
+            },
+            "type": "Opaque",
+            "stringData": data,
+        })
+    }
+}
+
+/// Create the pod described by the `docker run` command, with the secret
+/// for its environment, and wait until it is running.
+pub(crate) fn create_pod(
+    engine: &Engine,
+    name: &str,
+    docker: &Command,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    let args = subcommand_args(engine, docker)?;
+    let spec = PodSpec::from_run_args(args, |key| command_env(docker, key))?;
+    let manifest = json!({
+        "apiVersion": "v1",
+        "kind": "List",
+        "items": [
+            spec.secret(name),
+            spec.manifest(name, persistent_volume_claim().as_deref())?,
+        ],
+    });
+    let status = engine
+        .subcommand("create")
+        .args(["-f", "-"])
+        .run_and_get_status_with_stdin(&serde_json::to_vec(&manifest)?, msg_info, true)?;
+    if !status.success() {
+        eyre::bail!("could not create the pod `{name}`");
+    }
+
+    engine
+        .subcommand("wait")
+        .args(["--for=condition=Ready", &format!("pod/{name}")])
+        .arg(format!("--timeout={POD_READY_TIMEOUT}"))
+        .run(msg_info, true)
+        .wrap_err_with(|| format!("when waiting for the pod `{name}` to start"))
+        .with_suggestion(|| {
+            format!("check the events of the pod with `kubectl describe pod {name}`")
+        })
+}
+
+/// Delete the pod and its secret, with `grace_period` seconds to stop.
+pub(crate) fn delete_pod(
+    engine: &Engine,
+    name: &str,
+    grace_period: u32,
+    msg_info: &mut MessageInfo,
+) -> Result<ExitStatus> {
+    // a grace period of 0 requires `--force`, so 1 is the fastest.
+    engine
+        .subcommand("delete")
+        .args(["pod,secret", name, "--ignore-not-found", "--wait=false"])
+        .arg(format!("--grace-period={}", grace_period.max(1)))
+        .run_and_get_status(msg_info, true)
+}
+
+/// The state of the pod, as the state of a container.
+pub(crate) fn pod_state(
+    engine: &Engine,
+    name: &str,
+    msg_info: &mut MessageInfo,
+) -> Result<ContainerState> {
+    let stdout = engine
+        .subcommand("get")
+        .args(["pod", name, "--ignore-not-found"])
+        .args(["-o", "jsonpath={.status.phase}"])
+        .run_and_get_stdout(msg_info)?;
+    phase_state(stdout.trim())
+}
+
+fn phase_state(phase: &str) -> Result<ContainerState> {
+    match phase {
+        "Pending" => Ok(ContainerState::Created),
+        "Running" => Ok(ContainerState::Running),
+        "Succeeded" | "Failed" => Ok(ContainerState::Exited),
+        "Unknown" => Ok(ContainerState::Dead),
+        "" => Ok(ContainerState::DoesNotExist),
+        _ => eyre::bail!("unknown pod phase: got {phase}"),
+    }
+}
+
+/// The command run by a `docker exec` command.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ExecSpec {
+    interactive: bool,
+    tty: bool,
+    env: Vec<(String, String)>,
+    cwd: Option<String>,
+    pod: String,
+    args: Vec<String>,
+}
+
+impl ExecSpec {
+    fn from_exec_args(args: impl IntoIterator<Item = String>) -> Result<ExecSpec> {
+        let mut spec = ExecSpec::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-i" | "--interactive" => spec.interactive = true,
+                "-t" | "--tty" => spec.tty = true,
+                // the variables of the host are in the secret of the pod.
+                "-e" | "--env" => match next_value(&mut args, &arg)?.split_once('=') {
+                    Some((key, value)) => spec.env.push((key.to_owned(), value.to_owned())),
+                    None => eyre::bail!(
+                        "passing variables from the host to `kubectl exec` is not supported"
+                    ),
+                },
+                "-w" | "--workdir" => spec.cwd = Some(next_value(&mut args, &arg)?),
+                // `kubectl exec` always runs as the user of the pod.
+                "-u" | "--user" => {
+                    next_value(&mut args, &arg)?;
+                }
+                _ if arg.starts_with('-') => {
+                    eyre::bail!("option `{arg}` is not supported with Kubernetes")
+                }
+                _ => {
+                    spec.pod = arg;
+                    spec.args = args.collect();
+                    return Ok(spec);
+                }
+            }
+        }
+
+        eyre::bail!("no pod in the exec command")
+    }
+
+    fn add_args(&self, command: &mut EngineCommand) {
+        if self.interactive {
+            command.arg("-i");
+        }
+        if self.tty {
+            command.arg("-t");
+        }
+        command.args([&self.pod, "--"]);
+        // `kubectl exec` has no working directory or environment options.
+        if let Some(cwd) = &self.cwd {
+            command.args(["sh", "-c", "cd \"$0\" && exec \"$@\"", cwd]);
+        }
+        if !self.env.is_empty() {
+            command.arg("env");
+            for (key, value) in &self.env {
+                command.arg(format!("{key}={value}"));
+            }
+        }
+        command.args(&self.args);
+    }
+}
+
+/// Translate a `docker exec` command into `kubectl exec`.
+pub(crate) fn exec_command(engine: &Engine, docker: &Command) -> Result<EngineCommand> {
+    let args = subcommand_args(engine, docker)?;
+    let spec = ExecSpec::from_exec_args(args)?;
+    let mut command = engine.subcommand("exec");
+    spec.add_args(&mut command);
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&a| a.to_owned()).collect()
+    }
+
+    fn get_env(key: &str) -> Option<String> {
+        (key == "TERM").then(|| "xterm".to_owned())
+    }
+
+    #[test]
+    fn test_byte_size_quantity() -> Result<()> {
+        assert_eq!(byte_size_quantity("8g")?, "8Gi");
+        assert_eq!(byte_size_quantity("512M")?, "512Mi");
+        assert_eq!(byte_size_quantity("4096")?, "4096");
+        assert_eq!(byte_size_quantity("4096b")?, "4096");
+        assert_eq!(byte_size_quantity("8gb")?, "8Gi");
+        assert_eq!(byte_size_quantity("8GiB")?, "8Gi");
+        assert_eq!(byte_size_quantity("1.5g")?, "1.5Gi");
+        assert_eq!(byte_size_quantity("1t")?, "1Ti");
+        assert_eq!(byte_size_quantity("512 MB")?, "512Mi");
+        assert!(byte_size_quantity("g").is_err());
+        assert!(byte_size_quantity("1.g").is_err());
+        assert!(byte_size_quantity("8x").is_err());
+        assert!(byte_size_quantity("").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pod_name() {
+        assert_eq!(
+            pod_name("cross-1.70.0-x86_64-unknown-linux-gnu-a1b2c3-aarch64-unknown-linux-gnu"),
+            "cross-1.70.0-x86-64-unknown-linux-gnu-a1b2c3-aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(pod_name("_Cross_"), "cross");
+        assert_eq!(pod_name(&"a".repeat(300)).len(), 253);
+    }
+
+    #[test]
+    fn test_pod_manifest() -> Result<()> {
+        let spec = PodSpec::from_run_args(
+            args(&[
+                "--userns",
+                "host",
+                "--platform",
+                "linux/arm64",
+                "--name",
+                "cross-pod",
+                "--rm",
+                "-v",
+                "cross-toolchain:/cross",
+                "-e",
+                "TERM",
+                "-e",
+                "UNSET",
+                "-e",
+                "CARGO_TARGET_DIR=/target",
+                "--security-opt",
+                "seccomp=/tmp/seccomp.json",
+                "--cpus",
+                "1.5",
+                "--memory",
+                "8g",
+                "--shm-size",
+                "512m",
+                "-v",
+                "/cross/cargo/bin",
+                "-d",
+                "ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main",
+                "sh",
+                "-c",
+                "sleep infinity",
+            ]),
+            get_env,
+        )?;
+        assert_eq!(
+            spec.image,
+            "ghcr.io/cross-rs/aarch64-unknown-linux-gnu:main"
+        );
+        assert_eq!(spec.args, args(&["sh", "-c", "sleep infinity"]));
+        assert_eq!(
+            spec.env,
+            vec![
+                ("TERM".to_owned(), "xterm".to_owned()),
+                ("CARGO_TARGET_DIR".to_owned(), "/target".to_owned()),
+            ]
+        );
+
+        let manifest = spec.manifest("cross-pod", Some("cross-toolchains"))?;
+        assert_eq!(manifest["metadata"]["name"], "cross-pod");
+        assert_eq!(
+            manifest["spec"]["nodeSelector"]["kubernetes.io/arch"],
+            "arm64"
+        );
+        let container = &manifest["spec"]["containers"][0];
+        assert_eq!(
+            container["env"][0],
+            json!({
+                "name": "TERM",
+                "valueFrom": { "secretKeyRef": { "name": "cross-pod", "key": "TERM" } },
+            })
+        );
+        assert_eq!(
+            container["volumeMounts"],
+            json!([
+                { "name": "toolchains", "mountPath": "/cross", "subPath": "cross-toolchain" },
+                { "name": "empty-0", "mountPath": "/cross/cargo/bin" },
+                { "name": "shm", "mountPath": "/dev/shm" },
+            ])
+        );
+        assert_eq!(
+            container["resources"],
+            json!({ "limits": { "cpu": "1.5", "memory": "8Gi" } })
+        );
+        assert_eq!(
+            manifest["spec"]["volumes"],
+            json!([
+                { "name": "toolchains", "persistentVolumeClaim": { "claimName": "cross-toolchains" } },
+                { "name": "empty-0", "emptyDir": {} },
+                { "name": "shm", "emptyDir": { "medium": "Memory", "sizeLimit": "512Mi" } },
+            ])
+        );
+        assert!(spec.manifest("cross-pod", None).is_err());
+        // the values are only in the secret.
+        assert!(!manifest.to_string().contains("xterm"));
+        assert_eq!(
+            spec.secret("cross-pod")["stringData"],
+            json!({ "TERM": "xterm", "CARGO_TARGET_DIR": "/target" })
+        );
+
+        assert!(PodSpec::from_run_args(args(&["-v", "/home:/home", "image"]), get_env).is_err());
+        assert!(PodSpec::from_run_args(args(&["--privileged", "image"]), get_env).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exec_args() -> Result<()> {
+        let spec = ExecSpec::from_exec_args(args(&[
+            "-i",
+            "--user",
+            "1000:1000",
+            "-e",
+            "PKG_CONFIG_PATH=/usr/lib",
+            "-w",
+            "/project",
+            "cross-pod",
+            "sh",
+            "-c",
+            "cargo build",
+        ]))?;
+        let mut command = EngineCommand::new("kubectl");
+        spec.add_args(&mut command);
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            [
+                "-i",
+                "cross-pod",
+                "--",
+                "sh",
+                "-c",
+                "cd \"$0\" && exec \"$@\"",
+                "/project",
+                "env",
+                "PKG_CONFIG_PATH=/usr/lib",
+                "sh",
+                "-c",
+                "cargo build",
+            ]
+        );
+        assert!(ExecSpec::from_exec_args(args(&["--privileged", "pod"])).is_err());
+        assert!(ExecSpec::from_exec_args(args(&["-e", "TOKEN", "pod"])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_phase_state() -> Result<()> {
+        assert_eq!(phase_state("Pending")?, ContainerState::Created);
+        assert_eq!(phase_state("Running")?, ContainerState::Running);
+        assert_eq!(phase_state("Failed")?, ContainerState::Exited);
+        assert_eq!(phase_state("")?, ContainerState::DoesNotExist);
+        assert!(phase_state("running").is_err());
+
+        Ok(())
+    }
+
+    // a fake `kubectl` recording its arguments, one command per line.
+    #[test]
+    #[cfg(unix)]
+    fn test_fake_kubectl() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let tempdir = tempfile::tempdir()?;
+        let log = tempdir.path().join("log");
+        let kubectl = tempdir.path().join("kubectl");
+        fs::write(
+            &kubectl,
+            format!(
+                "#!/bin/sh
+echo \"$*\" >> '{0}'
+case \"$1\" in
+    create) cat > '{0}.json' ;;
+    get) printf Running ;;
+esac
+",
+                log.to_utf8()?
+            ),
+        )?;
+        fs::set_permissions(&kubectl, fs::Permissions::from_mode(0o755))?;
+
+        let mut msg_info = MessageInfo::default();
+        let engine = Engine::from_path(kubectl, None, None, &mut msg_info)?;
+        assert_eq!(engine.kind, super::super::EngineType::Kubernetes);
+        assert!(engine.is_remote);
+
+        let mut docker = engine.subcommand("run");
+        docker.args([
+            "--name", "pod", "-v", "/cross", "-e", "TOKEN", "-d", "image",
+        ]);
+        docker.env("TOKEN", "secret");
+        create_pod(&engine, "pod", &docker, &mut msg_info)?;
+        assert_eq!(
+            pod_state(&engine, "pod", &mut msg_info)?,
+            ContainerState::Running
+        );
+
+        let mut docker = engine.subcommand("exec");
+        docker.args(["-w", "/project", "pod", "true"]);
+        exec_command(&engine, &docker)?.run(&mut msg_info, true)?;
+        delete_pod(&engine, "pod", 0, &mut msg_info)?;
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(log.with_extension("json"))?)?;
+        let (secret, pod) = (&manifest["items"][0], &manifest["items"][1]);
+        assert_eq!(secret["stringData"]["TOKEN"], "secret");
+        assert_eq!(pod["metadata"]["name"], "pod");
+        assert_eq!(pod["spec"]["containers"][0]["image"], "image");
+        assert!(!pod.to_string().contains("secret\""));
+
+        let log = fs::read_to_string(&log)?;
+        let skip_namespace = |line: &str| {
+            line.strip_prefix("--namespace ")
+                .and_then(|l| l.split_once(' '))
+                .map_or(line.to_owned(), |(_, l)| l.to_owned())
+        };
+        assert_eq!(
+            log.lines().map(skip_namespace).collect::<Vec<_>>(),
+            [
+                "create -f -",
+                "wait --for=condition=Ready pod/pod --timeout=300s",
+                "get pod pod --ignore-not-found -o jsonpath={.status.phase}",
+                "exec pod -- sh -c cd \"$0\" && exec \"$@\" /project true",
+                "delete pod,secret pod --ignore-not-found --wait=false --grace-period=1",
+            ]
+        );
+
+        Ok(())
+    }
+}
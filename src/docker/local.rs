@@ -1,18 +1,22 @@
 use std::io;
 use std::path::Path;
-use std::process::{Command, ExitStatus};
+use std::process::ExitStatus;
 use std::sync::atomic::Ordering;
 
+use super::sandbox;
+use super::shard::SHARD_INDEX_VAR;
 use super::shared::*;
+use super::EngineCommand;
+use crate::cargo::Subcommand;
 use crate::errors::Result;
-use crate::extensions::CommandExt;
-use crate::file::{PathExt, ToUtf8};
+use crate::extensions::{CommandExt, SafeCommand};
+use crate::file::{self, PathExt, ToUtf8};
 use crate::shell::{MessageInfo, Stream};
 use eyre::Context;
 
 // NOTE: host path must be absolute
 fn mount(
-    docker: &mut Command,
+    docker: &mut EngineCommand,
     host_path: &Path,
     absolute_path: &Path,
     prefix: &str,
@@ -30,13 +34,20 @@ pub(crate) fn run(
     options: DockerOptions,
     paths: DockerPaths,
     args: &[String],
+    subcommand: Option<Subcommand>,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<ExitStatus>> {
     let engine = &options.engine;
     let toolchain_dirs = paths.directories.toolchain_directories();
     let package_dirs = paths.directories.package_directories();
 
-    let mut cmd = options.command_variant.safe_command();
+    let sandbox = options.config.sandbox()?;
+    let no_network = options.offline || sandbox.is_strict();
+    if no_network {
+        sandbox::fetch_dependencies(&paths.metadata, &options.target, msg_info)?;
+    }
+
+    let mut cmd = options.safe_command("/target", subcommand.as_ref());
     cmd.args(args);
 
     let mut docker = engine.subcommand("run");
@@ -56,7 +67,7 @@ pub(crate) fn run(
         .image
         .platform
         .specify_platform(&options.engine, &mut docker);
-    docker.add_envvars(&options, toolchain_dirs, msg_info)?;
+    docker.add_envvars(&options, &paths, msg_info)?;
 
     docker.add_mounts(
         &options,
@@ -66,14 +77,27 @@ pub(crate) fn run(
         msg_info,
     )?;
 
-    let container_id = toolchain_dirs.unique_container_identifier(options.target.target())?;
-    docker.args(["--name", &container_id]);
-    docker.arg("--rm");
-
     docker
-        .add_seccomp(engine.kind, &options.target, &paths.metadata)
+        .add_seccomp(engine, &options.target, &paths.metadata, sandbox, msg_info)
         .wrap_err("when copying seccomp profile")?;
+    docker.add_resources(&options, msg_info)?;
     docker.add_user_id(engine.is_rootless);
+    if sandbox.is_strict() {
+        sandbox::add_strict_options(&mut docker);
+    } else if options.offline {
+        sandbox::add_offline_options(&mut docker);
+    }
+
+    // the cargo home is read-only without network access, since everything
+    // was fetched on the host.
+    let cargo_selinux = match no_network {
+        true => selinux_ro,
+        false => selinux,
+    };
+    if no_network && !options.in_docker() {
+        // the mount point of the `bin` volume can't be created afterwards.
+        file::create_dir_all(toolchain_dirs.cargo().join("bin"))?;
+    }
 
     docker
         .args([
@@ -87,7 +111,7 @@ pub(crate) fn run(
         .args([
             "-v",
             &format!(
-                "{}:{}{selinux}",
+                "{}:{}{cargo_selinux}",
                 toolchain_dirs.cargo_host_path()?,
                 toolchain_dirs.cargo_mount_path()
             ),
@@ -131,7 +155,58 @@ pub(crate) fn run(
         ]);
     }
 
-    if io::Stdin::is_atty() && io::Stdout::is_atty() && io::Stderr::is_atty() {
+    let is_tty = io::Stdin::is_atty() && io::Stdout::is_atty() && io::Stderr::is_atty();
+
+    let mut image_name = options.image.name.clone();
+    if options.needs_custom_image() {
+        image_name = options
+            .custom_image_build(&paths, msg_info)
+            .wrap_err("when building custom image")?;
+    }
+
+    if options.keep_alive {
+        return run_session(
+            &options,
+            &paths,
+            docker,
+            &image_name,
+            &cmd,
+            subcommand,
+            is_tty,
+            msg_info,
+        );
+    }
+
+    docker.add_runner_command(&options, &paths, subcommand)?;
+
+    let container_id = toolchain_dirs.unique_container_identifier(options.target.target())?;
+    docker.arg("--rm");
+
+    // every partition runs in its own container, without a terminal or
+    // input, since their output is printed once all of them completed.
+    if let Some(count) = options.partitions() {
+        if msg_info.should_fail() {
+            return Ok(None);
+        }
+        let status = options.run_partitions(
+            count,
+            |index| {
+                let mut docker = docker.duplicate();
+                docker.args(["--name", &format!("{container_id}-{index}")]);
+                docker.args(["-e", &format!("{SHARD_INDEX_VAR}={index}")]);
+                docker
+                    .arg(&image_name)
+                    .add_build_command(toolchain_dirs, &cmd);
+                docker
+            },
+            msg_info,
+        );
+        return status.map(Some);
+    }
+
+    docker.args(["--name", &container_id]);
+
+    if is_tty {
         docker.arg("-t");
     }
 
@@ -139,21 +214,28 @@ pub(crate) fn run(
         docker.arg("-i");
     }
 
-    let mut image_name = options.image.name.clone();
-    if options.needs_custom_image() {
-        image_name = options
-            .custom_image_build(&paths, msg_info)
-            .wrap_err("when building custom image")?;
+    if let Some(debug) = &options.debug {
+        docker.args(["-p", &format!("127.0.0.1:{0}:{0}", debug.port)]);
+    }
+
+    if engine.kind.is_oci_runtime() {
+        if msg_info.should_fail() {
+            return Ok(None);
+        }
+        docker
+            .arg(&image_name)
+            .add_build_command(toolchain_dirs, &cmd);
+        return super::oci::run(&options, &docker, msg_info).map(Some);
     }
 
     ChildContainer::create(engine.clone(), container_id)?;
     if msg_info.should_fail() {
         return Ok(None);
     }
-    let status = docker
+    docker
         .arg(&image_name)
-        .add_build_command(toolchain_dirs, &cmd)
-        .run_and_get_status(msg_info, false);
+        .add_build_command(toolchain_dirs, &cmd);
+    let status = options.run_and_get_status(&mut docker, msg_info);
 
     // `cargo` generally returns 0 or 101 on completion, but isn't guaranteed
     // to. `ExitStatus::code()` may be None if a signal caused the process to
@@ -167,3 +249,97 @@ pub(crate) fn run(
 
     status.map(Some)
 }
+
+// run the command inside a persistent session container, starting it if
+// it isn't running or if its mounts or image changed since it was created.
+#[allow(clippy::too_many_arguments)]
+fn run_session(
+    options: &DockerOptions,
+    paths: &DockerPaths,
+    mut docker: EngineCommand,
+    image_name: &str,
+    cmd: &SafeCommand,
+    subcommand: Option<Subcommand>,
+    is_tty: bool,
+    msg_info: &mut MessageInfo,
+) -> Result<Option<ExitStatus>> {
+    let engine = &options.engine;
+    let toolchain_dirs = paths.directories.toolchain_directories();
+    let session_id = toolchain_dirs.unique_session_identifier(
+        options.target.target(),
+        paths.directories.package_directories().host_root(),
+    )?;
+    let container = DockerContainer::new(engine, &session_id);
+
+    docker.arg(image_name);
+    let hash = session_hash(&docker)?;
+    let label = session_label();
+    let state = container.state(msg_info)?;
+    let is_current = state == ContainerState::Running
+        && container.label(&label, msg_info)?.as_deref() == Some(hash.as_str());
+    if !is_current {
+        if state.exists() {
+            msg_info.note(format_args!(
+                "session container {session_id} is outdated, recreating it."
+            ))?;
+            container.stop_default(msg_info)?;
+            container.remove(msg_info)?;
+        }
+
+        // the image name was the last argument, so it must come after our options
+        let mut create = engine.subcommand("run");
+        let args: Vec<_> = docker
+            .get_args()
+            .skip_while(|arg| *arg != "run")
+            .skip(1)
+            .collect();
+        let (image, args) = args.split_last().expect("must have an image argument");
+        create.args(args);
+        create.args(["--name", &session_id]);
+        create.args(["--label", &format!("{label}={hash}")]);
+        create.arg("-d");
+        create.arg(image);
+        // keep the container alive until it's explicitly stopped.
+        create.args(["sh", "-c", "sleep infinity"]);
+        create
+            .run(msg_info, true)
+            .wrap_err("when starting session container")?;
+    }
+
+    if msg_info.should_fail() {
+        return Ok(None);
+    }
+
+    let mut docker = engine.subcommand("exec");
+    docker.add_user_id(engine.is_rootless);
+    docker.add_envvars(options, paths, msg_info)?;
+    // the runner depends on the subcommand, so it isn't part of the session.
+    docker.add_runner_command(options, paths, subcommand)?;
+    docker.add_cwd(paths)?;
+    touch_session(&session_id)?;
+
+    if let Some(count) = options.partitions() {
+        let status = options.run_partitions(
+            count,
+            |index| {
+                let mut docker = docker.duplicate();
+                docker.args(["-e", &format!("{SHARD_INDEX_VAR}={index}")]);
+                docker.arg(&session_id);
+                docker.add_build_command(toolchain_dirs, cmd);
+                docker
+            },
+            msg_info,
+        );
+        return status.map(Some);
+    }
+
+    if is_tty {
+        docker.arg("-t");
+    }
+    if options.interactive {
+        docker.arg("-i");
+    }
+    docker.arg(&session_id);
+    docker.add_build_command(toolchain_dirs, cmd);
+    options.run_and_get_status(&mut docker, msg_info).map(Some)
+}
@@ -0,0 +1,42 @@
+//! Run `cargo miri` for the target inside the container.
+//!
+//! Miri interprets code for the target, so no runner is needed, but it
+//! requires a sysroot built for the target. The sysroot is built by
+//! `cargo miri setup` into the xargo home, so it's kept between runs.
+
+use crate::extensions::SafeCommand;
+use crate::{CommandVariant, Target};
+
+/// The command running `variant` after setting up the miri sysroot for
+/// `target`. The arguments to the cargo command are added to the returned
+/// command.
+pub(crate) fn safe_command(target: &Target, variant: CommandVariant) -> SafeCommand {
+    let mut cmd = SafeCommand::new("sh");
+    cmd.args(["-c", MIRI_SCRIPT, "cross-miri", target.triple()])
+        .arg(&variant.to_str());
+    cmd
+}
+
+// runs the cargo command given as arguments, after building the sysroot for
+// the target given as the first argument. with `MIRI_SYSROOT`, `cargo miri`
+// uses the sysroot without checking it, so it's always set up first.
+const MIRI_SCRIPT: &str = r#"set -e
+target="$1"
+shift
+
+export MIRI_SYSROOT="$XARGO_HOME/miri/$target"
+"$1" miri setup --target "$target"
+exec "$@"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_command() {
+        let target = Target::new_built_in("s390x-unknown-linux-gnu");
+        let cmd = safe_command(&target, CommandVariant::Cargo);
+        assert!(format!("{cmd:?}").ends_with(" cross-miri s390x-unknown-linux-gnu cargo"));
+    }
+}
@@ -1,16 +1,37 @@
+mod archive;
 mod build;
+pub mod coverage;
+pub mod crash;
+mod credentials;
 pub(crate) mod custom;
+pub mod debug;
 mod engine;
 mod image;
+pub mod install;
+mod kubernetes;
 mod local;
+mod miri;
+mod nerdctl;
+mod oci;
 mod provided_images;
 pub mod remote;
+pub(crate) mod report;
+pub(crate) mod runner;
+pub mod sandbox;
+pub(crate) mod shard;
 mod shared;
+pub(crate) mod ssh;
+mod subcommand;
 
 pub use self::build::{BuildCommandExt, BuildResultExt, Progress};
+pub use self::debug::DebugOptions;
 pub use self::engine::*;
+pub use self::oci::oci_archives_dir;
 pub use self::provided_images::PROVIDED_IMAGES;
+pub use self::report::{ReportFormat, TestReport};
+pub use self::shard::TestSplit;
 pub use self::shared::*;
+pub use self::ssh::SshHost;
 
 pub use image::{Architecture, Image, ImagePlatform, Os as ContainerOs, PossibleImage};
 
@@ -25,6 +46,8 @@ pub struct ProvidedImage {
     pub name: &'static str,
     pub platforms: &'static [ImagePlatform],
     pub sub: Option<&'static str>,
+    /// The runners supported by the image, empty if unknown
+    pub runners: &'static [&'static str],
 }
 
 impl ProvidedImage {
@@ -57,10 +80,28 @@ pub fn run(
             1,
         );
     }
+    if options.engine.kind.is_oci_runtime() {
+        oci::check_options(&options)?;
+    }
+    if options.config.sandbox()?.is_strict() && options.is_remote() {
+        eyre::bail!("the strict sandbox is not supported with remote container engines");
+    }
+    if options.partitions().is_some() && options.is_remote() {
+        return Err(eyre::eyre!(
+            "`--partition` is not supported with remote container engines"
+        ))
+        .with_suggestion(|| "run each `--shard` with a separate `cross test` instead");
+    }
+    if options.config.forward_credentials()
+        && options.is_remote()
+        && credentials::ssh_agent_socket().is_some()
+    {
+        msg_info.warn("the SSH agent is not forwarded to remote container engines.")?;
+    }
     if options.is_remote() {
         remote::run(options, paths, args, subcommand, msg_info)
             .wrap_err("could not complete remote run")
     } else {
-        local::run(options, paths, args, msg_info)
+        local::run(options, paths, args, subcommand, msg_info)
     }
 }
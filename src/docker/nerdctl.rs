@@ -0,0 +1,247 @@
+//! Support for nerdctl, the command line interface of containerd.
+//!
+//! nerdctl runs containers in a containerd namespace, selected with
+//! `CROSS_CONTAINERD_NAMESPACE`, and BuildKit runs as a separate daemon
+//! rather than as part of the engine. Custom images are built with
+//! `buildctl`, and then loaded into the namespace with `nerdctl load`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::engine::{next_value, Engine};
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::file::ToUtf8;
+use crate::shell::MessageInfo;
+use crate::temp;
+
+pub const CROSS_CONTAINERD_NAMESPACE: &str = "CROSS_CONTAINERD_NAMESPACE";
+const DEFAULT_NAMESPACE: &str = "default";
+
+/// The containerd namespace provided with `CROSS_CONTAINERD_NAMESPACE`.
+pub fn namespace() -> Option<String> {
+    env::var(CROSS_CONTAINERD_NAMESPACE)
+        .ok()
+        .filter(|ns| !ns.is_empty())
+}
+
+// the namespace used by nerdctl, which also reads `CONTAINERD_NAMESPACE`.
+fn effective_namespace() -> String {
+    namespace()
+        .or_else(|| env::var("CONTAINERD_NAMESPACE").ok())
+        .unwrap_or_else(|| DEFAULT_NAMESPACE.to_owned())
+}
+
+/// The root filesystem of the running container `id` in `namespace`, as
+/// seen by containerd. Defaults to the namespace used by nerdctl.
+pub(crate) fn container_rootfs(namespace: Option<&str>, id: &str) -> PathBuf {
+    PathBuf::from("/run/containerd/io.containerd.runtime.v2.task")
+        .join(namespace.map_or_else(effective_namespace, ToOwned::to_owned))
+        .join(id)
+        .join("rootfs")
+}
+
+// the sockets of BuildKit, in the order they are looked up by nerdctl.
+fn buildkit_sockets(namespace: &str, runtime_dir: Option<&Path>) -> Vec<PathBuf> {
+    let root = runtime_dir.unwrap_or_else(|| Path::new("/run"));
+    let mut dirs = vec![format!("buildkit-{namespace}")];
+    if namespace != DEFAULT_NAMESPACE {
+        dirs.push(format!("buildkit-{DEFAULT_NAMESPACE}"));
+    }
+    dirs.push("buildkit".to_owned());
+    dirs.iter()
+        .map(|dir| root.join(dir).join("buildkitd.sock"))
+        .collect()
+}
+
+// the address of BuildKit, unless provided by `BUILDKIT_HOST`.
+fn buildkit_addr(engine: &Engine) -> Option<String> {
+    if env::var_os("BUILDKIT_HOST").is_some() {
+        return None;
+    }
+    let runtime_dir = match engine.is_rootless {
+        true => env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from),
+        false => None,
+    };
+    buildkit_sockets(&effective_namespace(), runtime_dir.as_deref())
+        .into_iter()
+        .find(|socket| socket.exists())
+        .and_then(|socket| Some(format!("unix://{}", socket.to_str()?)))
+}
+
+/// Translate the arguments to `docker build` into the arguments to
+/// `buildctl build`, exporting the image to `dest`.
+fn buildctl_args(args: impl IntoIterator<Item = String>, dest: &str) -> Result<Vec<String>> {
+    let mut buildctl = vec!["build".to_owned(), "--frontend=dockerfile.v0".to_owned()];
+    let mut opt = |value: String| buildctl.extend(["--opt".to_owned(), value]);
+    let mut tags = vec![];
+    let mut file = None;
+    let mut context = None;
+    let mut other = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => opt(format!("platform={}", next_value(&mut args, &arg)?)),
+            "--build-arg" => opt(format!("build-arg:{}", next_value(&mut args, &arg)?)),
+            "--label" => opt(format!("label:{}", next_value(&mut args, &arg)?)),
+            "--target" => opt(format!("target={}", next_value(&mut args, &arg)?)),
+            "--pull" => opt("image-resolve-mode=pull".to_owned()),
+            "-t" | "--tag" => tags.push(next_value(&mut args, &arg)?),
+            "-f" | "--file" => file = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+            "--progress" | "--secret" | "--ssh" => {
+                other.extend([arg.clone(), next_value(&mut args, &arg)?]);
+            }
+            "--cache-from" => {
+                let cache = next_value(&mut args, &arg)?;
+                let cache = match cache.contains('=') {
+                    true => cache,
+                    false => format!("type=registry,ref={cache}"),
+                };
+                other.extend(["--import-cache".to_owned(), cache]);
+            }
+            "--no-cache" => other.push(arg),
+            // the image is always exported, to be loaded by nerdctl.
+            "--load" => {}
+            _ if arg.starts_with('-') => {
+                eyre::bail!("option `{arg}` is not supported when building with `buildctl`")
+            }
+            _ => context = Some(arg),
+        }
+    }
+
+    let context = context.ok_or_else(|| eyre::eyre!("no build context provided"))?;
+    let (dockerfile_dir, filename) = match &file {
+        Some(file) => (
+            file.parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new(".")),
+            file.file_name()
+                .ok_or_else(|| eyre::eyre!("invalid dockerfile {file:?}"))?
+                .to_utf8()?,
+        ),
+        None => (Path::new(&context), "Dockerfile"),
+    };
+    buildctl.extend([
+        "--opt".to_owned(),
+        format!("filename={filename}"),
+        "--local".to_owned(),
+        format!("context={context}"),
+        "--local".to_owned(),
+        format!("dockerfile={}", dockerfile_dir.to_utf8()?),
+        "--output".to_owned(),
+        format!("type=docker,\"name={}\",dest={dest}", tags.join(",")),
+    ]);
+    buildctl.extend(other);
+
+    Ok(buildctl)
+}
+
+/// Build the image of the `docker build` command with `buildctl`, and load
+/// it into the namespace of the engine.
+pub(crate) fn build(
+    engine: &Engine,
+    docker_build: &Command,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    let args = docker_build
+        .get_args()
+        .map(|arg| arg.to_utf8().map(ToOwned::to_owned))
+        .collect::<Result<Vec<_>>>()?;
+    // skip the global options of the engine, and the build subcommand.
+    let args = args.into_iter().skip_while(|arg| arg != "build").skip(1);
+
+    // SAFETY: safe, single-threaded execution.
+    let archive = unsafe { temp::TempFile::new()? };
+    let mut buildctl = Command::new("buildctl");
+    if let Some(addr) = buildkit_addr(engine) {
+        buildctl.args(["--addr", &addr]);
+    }
+    buildctl.args(buildctl_args(args, archive.path().to_utf8()?)?);
+    buildctl
+        .run(msg_info, false)
+        .suggestion("is `buildkitd` running, and `buildctl` installed?")
+        .with_note(|| "set `BUILDKIT_HOST` to the address of `buildkitd` if needed")?;
+
+    let mut load = engine.subcommand("load");
+    load.stdin(fs::File::open(archive.path())?);
+    load.run(msg_info, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&a| a.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_buildctl_args() -> Result<()> {
+        let actual = buildctl_args(
+            args(&[
+                "--platform",
+                "linux/amd64",
+                "--progress",
+                "auto",
+                "--label",
+                "org.cross-rs.for-cross-target=aarch64-unknown-linux-gnu",
+                "--tag",
+                "localhost/cross-rs/cross-custom-app:aarch64-unknown-linux-gnu-a1b2c",
+                "--build-arg",
+                "CROSS_DEB_ARCH=arm64",
+                "--file",
+                "docker/Dockerfile.aarch64",
+                "/home/user/app",
+            ]),
+            "/tmp/image.tar",
+        )?;
+        assert_eq!(
+            actual,
+            args(&[
+                "build",
+                "--frontend=dockerfile.v0",
+                "--opt",
+                "platform=linux/amd64",
+                "--opt",
+                "label:org.cross-rs.for-cross-target=aarch64-unknown-linux-gnu",
+                "--opt",
+                "build-arg:CROSS_DEB_ARCH=arm64",
+                "--opt",
+                "filename=Dockerfile.aarch64",
+                "--local",
+                "context=/home/user/app",
+                "--local",
+                "dockerfile=docker",
+                "--output",
+                "type=docker,\"name=localhost/cross-rs/cross-custom-app:aarch64-unknown-linux-gnu-a1b2c\",dest=/tmp/image.tar",
+                "--progress",
+                "auto",
+            ])
+        );
+
+        assert!(buildctl_args(args(&["--network", "host", "."]), "/tmp/image.tar").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buildkit_sockets() {
+        assert_eq!(
+            buildkit_sockets("default", None),
+            vec![
+                PathBuf::from("/run/buildkit-default/buildkitd.sock"),
+                PathBuf::from("/run/buildkit/buildkitd.sock"),
+            ]
+        );
+        assert_eq!(
+            buildkit_sockets("cross", Some(Path::new("/run/user/1000"))),
+            vec![
+                PathBuf::from("/run/user/1000/buildkit-cross/buildkitd.sock"),
+                PathBuf::from("/run/user/1000/buildkit-default/buildkitd.sock"),
+                PathBuf::from("/run/user/1000/buildkit/buildkitd.sock"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1092 @@
+//! Run the container with an OCI runtime, without a container engine.
+//!
+//! With `CROSS_CONTAINER_ENGINE` set to `bwrap`, `crun` or `runc`, the image
+//! is read from the OCI layouts or image tarballs in `CROSS_OCI_IMAGE`, and
+//! its layers are unpacked into a rootfs cached in the data directory. The
+//! `docker run` command built for an engine is then translated into the
+//! mounts, environment and process of the runtime, so the container is the
+//! same as with an engine.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use serde::Deserialize;
+
+use super::engine::{command_env, next_value, push_env, EngineType};
+use super::image::{Architecture, ImagePlatform};
+use super::shared::DockerOptions;
+use crate::errors::*;
+use crate::file::{self, ToUtf8};
+use crate::id;
+use crate::shell::MessageInfo;
+use crate::temp;
+
+pub const CROSS_OCI_IMAGE: &str = "CROSS_OCI_IMAGE";
+
+// the annotations with the name of an image in an OCI layout.
+const REF_NAME_ANNOTATIONS: &[&str] = &[
+    "org.opencontainers.image.ref.name",
+    "io.containerd.image.name",
+];
+
+/// Check that the options can be used without a container engine.
+pub(crate) fn check_options(options: &DockerOptions) -> Result<()> {
+    if options.is_remote() {
+        eyre::bail!("remote cross is not supported with OCI runtimes");
+    }
+    if options.needs_custom_image() {
+        return Err(eyre::eyre!(
+            "custom images are not supported with OCI runtimes"
+        ))
+        .with_suggestion(|| {
+            "build the image with a container engine, and add it to `CROSS_OCI_IMAGE`"
+        });
+    }
+    if options.keep_alive {
+        eyre::bail!("`--keep-alive` is not supported with OCI runtimes");
+    }
+    if options.partitions().is_some() {
+        return Err(eyre::eyre!(
+            "`--partition` is not supported with OCI runtimes"
+        ))
+        .with_suggestion(|| "run each `--shard` with a separate `cross test` instead");
+    }
+    // the seccomp profile is an option of the container engine.
+    if options.config.sandbox()?.is_strict() {
+        eyre::bail!("the strict sandbox is not supported with OCI runtimes");
+    }
+
+    Ok(())
+}
+
+/// Run the container described by the `docker run` command with the OCI
+/// runtime of the engine.
+pub(crate) fn run(
+    options: &DockerOptions,
+    docker: &Command,
+    msg_info: &mut MessageInfo,
+) -> Result<ExitStatus> {
+    let args = docker
+        .get_args()
+        .map(|arg| arg.to_utf8().map(ToOwned::to_owned))
+        .collect::<Result<Vec<_>>>()?;
+    // the first argument is the `run` subcommand.
+    let spec = Spec::from_run_args(args.into_iter().skip(1), |key| command_env(docker, key))?;
+    if !spec.ports.is_empty() {
+        msg_info.note(
+            "OCI runtimes share the network of the host, so ports don't need to be published.",
+        )?;
+    }
+
+    let image = find_image(&spec.image, &options.image.platform, msg_info)?;
+    let rootfs = unpack(&image, msg_info)?;
+    spec.create_mount_points(&rootfs)?;
+
+    let mut env = BTreeMap::new();
+    for var in image.config.env.iter().flatten() {
+        if let Some((key, value)) = var.split_once('=') {
+            env.insert(key.to_owned(), value.to_owned());
+        }
+    }
+    env.extend(spec.env.iter().cloned());
+    let mut process = image.config.entrypoint.clone().unwrap_or_default();
+    process.extend(spec.args.iter().cloned());
+    let cwd = spec
+        .cwd
+        .clone()
+        .or_else(|| image.config.working_dir.clone())
+        .filter(|cwd| !cwd.is_empty())
+        .unwrap_or_else(|| "/".to_owned());
+
+    let engine = &options.engine;
+    match engine.kind {
+        EngineType::Bwrap => {
+            let mut command = Command::new(&engine.path);
+            spec.add_bwrap_args(&mut command, &rootfs, &cwd)?;
+            command.env_clear().envs(&env).arg("--").args(&process);
+            options.run_and_get_status(&mut command.into(), msg_info)
+        }
+        _ => {
+            // SAFETY: safe, single-threaded execution.
+            let bundle = unsafe { temp::TempDir::new()? };
+            let config = spec.runtime_config(&rootfs, &cwd, &env, &process)?;
+            let config_path = bundle.path().join("config.json");
+            fs::write(&config_path, serde_json::to_vec_pretty(&config)?)
+                .wrap_err_with(|| format!("when writing {config_path:?}"))?;
+            let id = format!("cross-{}", std::process::id());
+            let mut command = Command::new(&engine.path);
+            command
+                .args(["run", "--bundle"])
+                .arg(bundle.path())
+                .arg(&id);
+            options.run_and_get_status(&mut command.into(), msg_info)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bind {
+    src: PathBuf,
+    dst: String,
+    readonly: bool,
+}
+
+/// The container described by the arguments to `docker run`.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Spec {
+    binds: Vec<Bind>,
+    tmpfs: Vec<String>,
+    env: Vec<(String, String)>,
+    cwd: Option<String>,
+    user: Option<(u32, u32)>,
+    network: Option<String>,
+    ports: Vec<String>,
+    image: String,
+    args: Vec<String>,
+}
+
+impl Spec {
+    fn from_run_args(
+        args: impl IntoIterator<Item = String>,
+        get_env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Spec> {
+        let mut spec = Spec::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-v" | "--volume" => {
+                    let volume = next_value(&mut args, &arg)?;
+                    let mut parts = volume.splitn(3, ':');
+                    match (parts.next(), parts.next(), parts.next()) {
+                        (Some(dst), None, _) => spec.tmpfs.push(dst.to_owned()),
+                        (Some(src), Some(dst), opts) => spec.binds.push(Bind {
+                            src: PathBuf::from(src),
+                            dst: dst.to_owned(),
+                            readonly: opts.map_or(false, |o| o.split(',').any(|o| o == "ro")),
+                        }),
+                        _ => eyre::bail!("invalid volume `{volume}`"),
+                    }
+                }
+                "-e" | "--env" => push_env(&mut spec.env, next_value(&mut args, &arg)?, &get_env),
+                "-w" | "--workdir" => spec.cwd = Some(next_value(&mut args, &arg)?),
+                "-u" | "--user" => {
+                    let user = next_value(&mut args, &arg)?;
+                    let (uid, gid) = user.split_once(':').unwrap_or((&user, "0"));
+                    spec.user = Some((
+                        uid.parse()
+                            .wrap_err_with(|| format!("invalid user `{user}`"))?,
+                        gid.parse()
+                            .wrap_err_with(|| format!("invalid user `{user}`"))?,
+                    ));
+                }
+                "-p" | "--publish" => spec.ports.push(next_value(&mut args, &arg)?),
+                "--network" => spec.network = Some(next_value(&mut args, &arg)?),
+                // the runtimes run without capabilities, or new privileges.
+                "--cap-drop" => {
+                    next_value(&mut args, &arg)?;
+                }
+                // the platform is selected from the image options instead.
+                "--platform" | "--name" | "--userns" | "--security-opt" | "--label" => {
+                    next_value(&mut args, &arg)?;
+                }
+                "--rm" | "-t" | "-i" | "--init" => {}
+                _ if arg.starts_with('-') => {
+                    eyre::bail!("option `{arg}` is not supported with OCI runtimes")
+                }
+                _ => {
+                    spec.image = arg;
+                    spec.args = args.collect();
+                    return Ok(spec);
+                }
+            }
+        }
+
+        eyre::bail!("no image in the container command")
+    }
+
+    /// Create the destinations of the mounts in the rootfs, since the
+    /// rootfs is mounted read-only.
+    fn create_mount_points(&self, rootfs: &Path) -> Result<()> {
+        for bind in &self.binds {
+            // like an engine, create missing directories on the host.
+            if !bind.src.exists() {
+                file::create_dir_all(&bind.src)?;
+            }
+            create_mount_point(rootfs, &bind.dst, bind.src.is_file())?;
+        }
+        for dst in &self.tmpfs {
+            create_mount_point(rootfs, dst, false)?;
+        }
+
+        Ok(())
+    }
+
+    // otherwise, the network of the host is shared.
+    fn has_no_network(&self) -> bool {
+        self.network.as_deref() == Some("none")
+    }
+
+    fn uid_gid(&self) -> (u32, u32) {
+        self.user.unwrap_or((0, 0))
+    }
+
+    fn add_bwrap_args(&self, command: &mut Command, rootfs: &Path, cwd: &str) -> Result<()> {
+        let (uid, gid) = self.uid_gid();
+        command
+            .args(["--die-with-parent", "--unshare-user", "--unshare-pid"])
+            .args(["--unshare-ipc", "--unshare-uts", "--hostname", "cross"])
+            .args(["--uid", &uid.to_string(), "--gid", &gid.to_string()])
+            .arg("--ro-bind")
+            .arg(rootfs)
+            .arg("/")
+            .args(["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"])
+            .args(["--ro-bind-try", "/etc/resolv.conf", "/etc/resolv.conf"])
+            .args(["--ro-bind-try", "/etc/hosts", "/etc/hosts"]);
+        if self.has_no_network() {
+            command.arg("--unshare-net");
+        }
+        for bind in &self.binds {
+            command
+                .arg(if bind.readonly { "--ro-bind" } else { "--bind" })
+                .arg(&bind.src)
+                .arg(&bind.dst);
+        }
+        for dst in &self.tmpfs {
+            command.args(["--tmpfs", dst]);
+        }
+        command.args(["--chdir", cwd]);
+
+        Ok(())
+    }
+
+    fn runtime_config(
+        &self,
+        rootfs: &Path,
+        cwd: &str,
+        env: &BTreeMap<String, String>,
+        process: &[String],
+    ) -> Result<serde_json::Value> {
+        let (uid, gid) = self.uid_gid();
+        let mut mounts = vec![
+            serde_json::json!({"destination": "/proc", "type": "proc", "source": "proc"}),
+            serde_json::json!({
+                "destination": "/dev",
+                "type": "tmpfs",
+                "source": "tmpfs",
+                "options": ["nosuid", "strictatime", "mode=755", "size=65536k"],
+            }),
+            serde_json::json!({
+                "destination": "/dev/pts",
+                "type": "devpts",
+                "source": "devpts",
+                "options": ["nosuid", "noexec", "newinstance", "ptmxmode=0666", "mode=0620"],
+            }),
+            serde_json::json!({
+                "destination": "/dev/shm",
+                "type": "tmpfs",
+                "source": "shm",
+                "options": ["nosuid", "noexec", "nodev", "mode=1777"],
+            }),
+        ];
+        for dst in std::iter::once("/tmp").chain(self.tmpfs.iter().map(String::as_str)) {
+            mounts.push(serde_json::json!({
+                "destination": dst,
+                "type": "tmpfs",
+                "source": "tmpfs",
+                "options": ["nosuid", "nodev", "mode=1777"],
+            }));
+        }
+        for path in ["/etc/resolv.conf", "/etc/hosts"] {
+            if Path::new(path).exists() {
+                mounts.push(serde_json::json!({
+                    "destination": path,
+                    "type": "bind",
+                    "source": path,
+                    "options": ["rbind", "ro"],
+                }));
+            }
+        }
+        for bind in &self.binds {
+            mounts.push(serde_json::json!({
+                "destination": bind.dst,
+                "type": "bind",
+                "source": bind.src.to_utf8()?,
+                "options": ["rbind", if bind.readonly { "ro" } else { "rw" }],
+            }));
+        }
+
+        let mut namespaces = vec![
+            serde_json::json!({"type": "pid"}),
+            serde_json::json!({"type": "ipc"}),
+            serde_json::json!({"type": "uts"}),
+            serde_json::json!({"type": "mount"}),
+            serde_json::json!({"type": "user"}),
+        ];
+        if self.has_no_network() {
+            namespaces.push(serde_json::json!({"type": "network"}));
+        }
+
+        Ok(serde_json::json!({
+            "ociVersion": "1.0.2",
+            "process": {
+                "terminal": false,
+                "user": {"uid": uid, "gid": gid},
+                "args": process,
+                "env": env.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>(),
+                "cwd": cwd,
+                "noNewPrivileges": true,
+            },
+            "root": {"path": rootfs.to_utf8()?, "readonly": true},
+            "hostname": "cross",
+            "mounts": mounts,
+            "linux": {
+                "namespaces": namespaces,
+                "uidMappings": [{"containerID": uid, "hostID": id::user(), "size": 1}],
+                "gidMappings": [{"containerID": gid, "hostID": id::group(), "size": 1}],
+            },
+        }))
+    }
+}
+
+// create the mount point `dst` inside the rootfs, without following any
+// symlinks in the rootfs, which could point outside of it.
+fn create_mount_point(rootfs: &Path, dst: &str, is_file: bool) -> Result<()> {
+    let mut path = rootfs.to_owned();
+    for component in Path::new(dst).components() {
+        if let Component::Normal(name) = component {
+            path.push(name);
+            if path.is_symlink() {
+                return Ok(());
+            }
+        }
+    }
+    if path.exists() {
+        return Ok(());
+    }
+    if is_file {
+        if let Some(parent) = path.parent() {
+            file::create_dir_all(parent)?;
+        }
+        fs::write(&path, b"").wrap_err_with(|| format!("when creating {path:?}"))?;
+    } else {
+        file::create_dir_all(&path)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct Index {
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    #[serde(default)]
+    media_type: String,
+    digest: String,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+    platform: Option<Platform>,
+}
+
+impl Descriptor {
+    fn refs(&self) -> Vec<&str> {
+        REF_NAME_ANNOTATIONS
+            .iter()
+            .filter_map(|a| self.annotations.get(*a).map(String::as_str))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+    variant: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    config: Descriptor,
+    layers: Vec<Descriptor>,
+}
+
+// an image saved with `docker save`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ArchiveManifest {
+    config: String,
+    #[serde(default)]
+    repo_tags: Option<Vec<String>>,
+    layers: Vec<String>,
+}
+
+impl ArchiveManifest {
+    fn refs(&self) -> Vec<&str> {
+        self.repo_tags
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ImageConfig {
+    #[serde(default)]
+    config: ContainerConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerConfig {
+    env: Option<Vec<String>>,
+    entrypoint: Option<Vec<String>>,
+    working_dir: Option<String>,
+}
+
+#[derive(Debug)]
+struct OciImage {
+    /// The unique id of the image, from its config and layers.
+    id: String,
+    layers: Vec<PathBuf>,
+    config: ContainerConfig,
+}
+
+fn oci_dir() -> Result<PathBuf> {
+    Ok(temp::dir()?.with_file_name("oci"))
+}
+
+/// The directory the image tarballs are extracted into, by their digest.
+pub fn oci_archives_dir() -> Result<PathBuf> {
+    Ok(oci_dir()?.join("archives"))
+}
+
+// the tag or digest of `name:tag` or `name@digest`.
+fn tag(name: &str) -> Option<&str> {
+    let (_, last) = name.rsplit_once('/').unwrap_or(("", name));
+    last.split_once([':', '@']).map(|(_, tag)| tag)
+}
+
+/// How an image in a layout is matched to the requested name, from the most
+/// to the least specific.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RefMatch {
+    /// The full reference of the image.
+    Name,
+    /// Only the tag, if the layout contains a single image.
+    Tag,
+    /// Any image, if it's the only image provided.
+    Unnamed,
+}
+
+impl RefMatch {
+    fn find<'a, T>(
+        self,
+        images: &'a [T],
+        refs: impl Fn(&T) -> Vec<&str>,
+        name: &str,
+    ) -> Option<&'a T> {
+        match (self, images) {
+            (RefMatch::Name, _) => images.iter().find(|i| refs(i).contains(&name)),
+            (RefMatch::Tag, [image]) => {
+                tag(name).filter(|t| refs(image).contains(t)).map(|_| image)
+            }
+            (RefMatch::Unnamed, [image]) => Some(image),
+            _ => None,
+        }
+    }
+}
+
+fn matches_platform(platform: Option<&Platform>, expected: &ImagePlatform) -> bool {
+    let platform = match platform {
+        Some(platform) => platform,
+        // no platform means that the image is for any platform.
+        None => return true,
+    };
+    platform.os == expected.os.to_string()
+        && Architecture::new(&platform.architecture).ok().as_ref() == Some(&expected.architecture)
+        && (platform.variant.is_none()
+            || expected.variant.is_none()
+            || platform.variant == expected.variant)
+}
+
+fn blob_path(dir: &Path, digest: &str) -> Result<PathBuf> {
+    let (algorithm, hex) = digest
+        .split_once(':')
+        .ok_or_else(|| eyre::eyre!("invalid digest `{digest}`"))?;
+    Ok(dir.join("blobs").join(algorithm).join(hex))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let file = fs::File::open(path).wrap_err_with(|| format!("when opening {path:?}"))?;
+    serde_json::from_reader(BufReader::new(file)).wrap_err_with(|| format!("when parsing {path:?}"))
+}
+
+/// Find the image `name` for `platform` in the images in `CROSS_OCI_IMAGE`.
+fn find_image(
+    name: &str,
+    platform: &ImagePlatform,
+    msg_info: &mut MessageInfo,
+) -> Result<OciImage> {
+    let sources = env::var_os(CROSS_OCI_IMAGE)
+        .ok_or_else(|| eyre::eyre!("`{CROSS_OCI_IMAGE}` must be set to use an OCI runtime"))
+        .with_suggestion(|| {
+            format!("save the image with `docker save {name} -o image.tar`, and set `{CROSS_OCI_IMAGE}=image.tar`")
+        })?;
+    let dirs = env::split_paths(&sources)
+        .map(|source| match source.is_dir() {
+            true => Ok(source),
+            false => extract_archive(&source, msg_info),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(image) = find_in_layouts(&dirs, name, platform)? {
+        return Ok(image);
+    }
+
+    Err(eyre::eyre!(
+        "image `{name}` for `{}` not found in `{CROSS_OCI_IMAGE}`",
+        platform.docker_platform()
+    ))
+    .with_suggestion(|| format!("save the image with `docker save {name} -o image.tar`"))
+}
+
+// find the image in the layouts, looking for the full reference in all of
+// them before falling back to the tag, so an image with the same tag in an
+// earlier layout isn't used instead.
+fn find_in_layouts(
+    dirs: &[PathBuf],
+    name: &str,
+    platform: &ImagePlatform,
+) -> Result<Option<OciImage>> {
+    for kind in [RefMatch::Name, RefMatch::Tag, RefMatch::Unnamed] {
+        // an image without a name is only used if it's the only image provided.
+        if kind == RefMatch::Unnamed && dirs.len() != 1 {
+            break;
+        }
+        for dir in dirs {
+            if let Some(image) = find_in_layout(dir, name, platform, kind)? {
+                return Ok(Some(image));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn find_in_layout(
+    dir: &Path,
+    name: &str,
+    platform: &ImagePlatform,
+    kind: RefMatch,
+) -> Result<Option<OciImage>> {
+    let index_path = dir.join("index.json");
+    if index_path.exists() {
+        let index: Index = read_json(&index_path)?;
+        return match kind.find(&index.manifests, Descriptor::refs, name) {
+            Some(descriptor) => find_manifest(dir, descriptor, platform),
+            None => Ok(None),
+        };
+    }
+
+    let manifest_path = dir.join("manifest.json");
+    if manifest_path.exists() {
+        let manifests: Vec<ArchiveManifest> = read_json(&manifest_path)?;
+        let manifest = match kind.find(&manifests, ArchiveManifest::refs, name) {
+            Some(manifest) => manifest,
+            None => return Ok(None),
+        };
+        let config_path = dir.join(&manifest.config);
+        let layers = manifest.layers.iter().map(|l| dir.join(l)).collect();
+        return image_from(&config_path, layers).map(Some);
+    }
+
+    eyre::bail!("{dir:?} is not an OCI layout or an image saved with `docker save`")
+}
+
+fn find_manifest(
+    dir: &Path,
+    descriptor: &Descriptor,
+    platform: &ImagePlatform,
+) -> Result<Option<OciImage>> {
+    let path = blob_path(dir, &descriptor.digest)?;
+    if descriptor.media_type.contains("index") || descriptor.media_type.contains("manifest.list") {
+        let index: Index = read_json(&path)?;
+        return match index
+            .manifests
+            .iter()
+            .find(|m| matches_platform(m.platform.as_ref(), platform))
+        {
+            Some(descriptor) => find_manifest(dir, descriptor, platform),
+            None => Ok(None),
+        };
+    }
+
+    let manifest: Manifest = read_json(&path)?;
+    let layers = manifest
+        .layers
+        .iter()
+        .map(|l| blob_path(dir, &l.digest))
+        .collect::<Result<_>>()?;
+    image_from(&blob_path(dir, &manifest.config.digest)?, layers).map(Some)
+}
+
+fn image_from(config_path: &Path, layers: Vec<PathBuf>) -> Result<OciImage> {
+    let contents =
+        fs::read(config_path).wrap_err_with(|| format!("when reading {config_path:?}"))?;
+    let config: ImageConfig = serde_json::from_slice(&contents)
+        .wrap_err_with(|| format!("when parsing {config_path:?}"))?;
+    let mut hasher = sha1_smol::Sha1::from(&contents);
+    for layer in &layers {
+        hasher.update(layer.to_utf8()?.as_bytes());
+    }
+
+    Ok(OciImage {
+        id: hasher.digest().to_string(),
+        layers,
+        config: config.config,
+    })
+}
+
+// open a possibly compressed tarball.
+fn open_tarball(path: &Path) -> Result<tar::Archive<Box<dyn Read>>> {
+    let mut file = fs::File::open(path).wrap_err_with(|| format!("when opening {path:?}"))?;
+    let mut magic = [0u8; 4];
+    let count = file.read(&mut magic)?;
+    let file = io::Cursor::new(magic[..count].to_vec()).chain(BufReader::new(file));
+    let reader: Box<dyn Read> = match magic {
+        [0x1f, 0x8b, ..] => Box::new(flate2::read::GzDecoder::new(file)),
+        #[cfg(feature = "zstd")]
+        [0x28, 0xb5, 0x2f, 0xfd] => Box::new(zstd::Decoder::new(file)?),
+        #[cfg(not(feature = "zstd"))]
+        [0x28, 0xb5, 0x2f, 0xfd] => {
+            return Err(super::archive::zstd_unsupported())
+                .wrap_err_with(|| format!("when opening {path:?}"))
+        }
+        _ => Box::new(file),
+    };
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.set_overwrite(true);
+    archive.set_unpack_xattrs(false);
+
+    Ok(archive)
+}
+
+// unpack into `dir` through a temporary directory, so an interrupted unpack
+// is never used.
+fn unpack_once(
+    dir: &Path,
+    msg_info: &mut MessageInfo,
+    unpack: impl FnOnce(&Path) -> Result<()>,
+) -> Result<()> {
+    if dir.exists() {
+        return Ok(());
+    }
+    let tmp = dir.with_extension(format!("tmp-{}", std::process::id()));
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp)?;
+    }
+    file::create_dir_all(&tmp)?;
+    msg_info.info(format_args!("Unpacking into {}", dir.to_utf8()?))?;
+    unpack(&tmp)?;
+    fs::rename(&tmp, dir).wrap_err_with(|| format!("when renaming {tmp:?} to {dir:?}"))
+}
+
+// the digest of the contents of a file.
+fn file_digest(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).wrap_err_with(|| format!("when opening {path:?}"))?;
+    let mut hasher = sha1_smol::Sha1::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+
+    Ok(hasher.digest().to_string())
+}
+
+// extract the tarball once for its contents, so a changed or moved tarball
+// doesn't leave another copy behind. they're removed by `cross-util clean`.
+fn extract_archive(path: &Path, msg_info: &mut MessageInfo) -> Result<PathBuf> {
+    let dir = oci_archives_dir()?.join(file_digest(path)?);
+    unpack_once(&dir, msg_info, |tmp| {
+        open_tarball(path)?
+            .unpack(tmp)
+            .wrap_err_with(|| format!("when extracting {path:?}"))
+    })?;
+
+    Ok(dir)
+}
+
+/// Unpack the layers of the image into its rootfs, if not already unpacked.
+fn unpack(image: &OciImage, msg_info: &mut MessageInfo) -> Result<PathBuf> {
+    let rootfs = oci_dir()?.join("rootfs").join(&image.id);
+    unpack_once(&rootfs, msg_info, |tmp| {
+        for layer in &image.layers {
+            apply_layer(tmp, layer).wrap_err_with(|| format!("when unpacking layer {layer:?}"))?;
+        }
+        // the files of the host are mounted over these.
+        for dst in ["/etc/resolv.conf", "/etc/hosts"] {
+            let path = tmp.join(&dst[1..]);
+            if path.is_symlink() || path.is_dir() {
+                remove_path(&path)?;
+            }
+            create_mount_point(tmp, dst, true)?;
+        }
+        Ok(())
+    })?;
+
+    Ok(rootfs)
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    let result = if path.is_dir() && !path.is_symlink() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).wrap_err_with(|| format!("when removing {path:?}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+// the directory `dir` of a layer inside the rootfs, without following any
+// symlinks in the rootfs, which could point outside of it. returns `None`
+// if a component of `dir` is a symlink.
+fn layer_dir(rootfs: &Path, dir: &Path) -> Result<Option<PathBuf>> {
+    let mut path = rootfs.to_owned();
+    for component in dir.components() {
+        match component {
+            Component::Normal(name) => {
+                path.push(name);
+                if path.is_symlink() {
+                    return Ok(None);
+                }
+            }
+            Component::CurDir => (),
+            _ => eyre::bail!("invalid path {dir:?} in layer"),
+        }
+    }
+
+    Ok(Some(path))
+}
+
+fn apply_layer(rootfs: &Path, layer: &Path) -> Result<()> {
+    let mut archive = open_tarball(layer)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let (parent, name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => continue,
+        };
+        let parent = layer_dir(rootfs, parent)?;
+        // whiteouts remove the contents of lower layers.
+        if let Some(whiteout) = name.to_str().and_then(|n| n.strip_prefix(".wh.")) {
+            let parent = match parent {
+                Some(parent) => parent,
+                None => continue,
+            };
+            if whiteout == ".wh..opq" {
+                if let Ok(entries) = fs::read_dir(&parent) {
+                    for entry in entries {
+                        remove_path(&entry?.path())?;
+                    }
+                }
+            } else if !matches!(whiteout, "" | "." | "..") {
+                remove_path(&parent.join(whiteout))?;
+            } else {
+                eyre::bail!("invalid whiteout {path:?} in layer");
+            }
+            continue;
+        }
+        // device nodes can't be created without privileges.
+        if matches!(
+            entry.header().entry_type(),
+            tar::EntryType::Char | tar::EntryType::Block | tar::EntryType::Fifo
+        ) {
+            continue;
+        }
+        let dst = parent.map(|parent| parent.join(name));
+        if let Some(dst) = &dst {
+            if entry.header().entry_type().is_dir() {
+                if dst.exists() && !dst.is_dir() || dst.is_symlink() {
+                    remove_path(dst)?;
+                }
+            } else if dst.is_dir() && !dst.is_symlink() {
+                remove_path(dst)?;
+            }
+        }
+        entry.unpack_in(rootfs)?;
+        // directories must stay writable to apply the next layers.
+        #[cfg(unix)]
+        if let (Some(dst), true) = (&dst, entry.header().entry_type().is_dir()) {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mut permissions = fs::symlink_metadata(dst)?.permissions();
+            permissions.set_mode(permissions.mode() | 0o700);
+            fs::set_permissions(dst, permissions)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&a| a.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_from_run_args() -> Result<()> {
+        let spec = Spec::from_run_args(
+            args(&[
+                "--userns",
+                "host",
+                "-e",
+                "CARGO_TARGET_DIR=/target",
+                "-e",
+                "TERM",
+                "-e",
+                "UNSET",
+                "-v",
+                "/home/user/.cargo:/cargo:z",
+                "-v",
+                "/cargo/bin",
+                "-v",
+                "/rust:/rust:z,ro",
+                "-w",
+                "/project",
+                "--user",
+                "1000:1000",
+                "--name",
+                "cross-id",
+                "--rm",
+                "--network",
+                "none",
+                "-p",
+                "127.0.0.1:1234:1234",
+                "image:tag",
+                "sh",
+                "-c",
+                "cargo build",
+            ]),
+            |key| (key == "TERM").then(|| "xterm".to_owned()),
+        )?;
+        assert_eq!(
+            spec,
+            Spec {
+                binds: vec![
+                    Bind {
+                        src: PathBuf::from("/home/user/.cargo"),
+                        dst: "/cargo".to_owned(),
+                        readonly: false,
+                    },
+                    Bind {
+                        src: PathBuf::from("/rust"),
+                        dst: "/rust".to_owned(),
+                        readonly: true,
+                    },
+                ],
+                tmpfs: vec!["/cargo/bin".to_owned()],
+                env: vec![
+                    ("CARGO_TARGET_DIR".to_owned(), "/target".to_owned()),
+                    ("TERM".to_owned(), "xterm".to_owned()),
+                ],
+                cwd: Some("/project".to_owned()),
+                user: Some((1000, 1000)),
+                network: Some("none".to_owned()),
+                ports: vec!["127.0.0.1:1234:1234".to_owned()],
+                image: "image:tag".to_owned(),
+                args: args(&["sh", "-c", "cargo build"]),
+            }
+        );
+
+        assert!(Spec::from_run_args(args(&["--privileged", "image"]), |_| None).is_err());
+        assert!(Spec::from_run_args(args(&["--rm"]), |_| None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_layer() -> Result<()> {
+        fn layer(dir: &Path, name: &str, files: &[&str]) -> Result<PathBuf> {
+            let path = dir.join(name);
+            let mut builder = tar::Builder::new(fs::File::create(&path)?);
+            for file in files {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(0);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append_data(&mut header, file, io::empty())?;
+            }
+            builder.finish()?;
+            Ok(path)
+        }
+
+        let tmp = tempfile::tempdir()?;
+        let rootfs = tmp.path().join("rootfs");
+        file::create_dir_all(&rootfs)?;
+        let lower = layer(tmp.path(), "lower.tar", &["a/1", "a/2", "b/1", "c"])?;
+        let upper = layer(
+            tmp.path(),
+            "upper.tar",
+            &["a/.wh.1", "b/.wh..wh..opq", "b/2"],
+        )?;
+        apply_layer(&rootfs, &lower)?;
+        apply_layer(&rootfs, &upper)?;
+
+        assert!(!rootfs.join("a/1").exists());
+        assert!(rootfs.join("a/2").exists());
+        assert!(!rootfs.join("b/1").exists());
+        assert!(rootfs.join("b/2").exists());
+        assert!(rootfs.join("c").exists());
+        assert!(!rootfs.join("b/.wh..wh..opq").exists());
+
+        Ok(())
+    }
+
+    // whiteouts must not remove files outside of the rootfs, through
+    // parent directories or symlinks in the rootfs.
+    #[test]
+    #[cfg(unix)]
+    fn test_apply_malicious_layer() -> Result<()> {
+        // the tar builder rejects these paths, so write the headers directly.
+        fn layer(dir: &Path, name: &str, files: &[&str]) -> Result<PathBuf> {
+            let path = dir.join(name);
+            let mut builder = tar::Builder::new(fs::File::create(&path)?);
+            for file in files {
+                let mut header = tar::Header::new_gnu();
+                header.as_old_mut().name[..file.len()].copy_from_slice(file.as_bytes());
+                header.set_size(0);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append(&header, io::empty())?;
+            }
+            builder.finish()?;
+            Ok(path)
+        }
+
+        let tmp = tempfile::tempdir()?;
+        let rootfs = tmp.path().join("rootfs");
+        let outside = tmp.path().join("outside");
+        file::create_dir_all(&rootfs)?;
+        file::create_dir_all(&outside)?;
+        fs::write(outside.join("victim"), b"")?;
+        std::os::unix::fs::symlink(&outside, rootfs.join("evil"))?;
+
+        let symlink = layer(
+            tmp.path(),
+            "symlink.tar",
+            &["evil/.wh.victim", "evil/.wh..wh..opq"],
+        )?;
+        apply_layer(&rootfs, &symlink)?;
+        assert!(outside.join("victim").exists());
+
+        let parent = layer(tmp.path(), "parent.tar", &["../outside/.wh.victim"])?;
+        assert!(apply_layer(&rootfs, &parent).is_err());
+        assert!(outside.join("victim").exists());
+
+        let whiteout = layer(tmp.path(), "whiteout.tar", &["dir/.wh..."])?;
+        assert!(apply_layer(&rootfs, &whiteout).is_err());
+        assert!(outside.join("victim").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_in_layouts() -> Result<()> {
+        // an OCI layout with an image per reference annotations, each with
+        // the working directory of the config set to `/<layout>/<index>`.
+        fn layout(dir: &Path, images: &[&[(&str, &str)]]) -> Result<PathBuf> {
+            let source = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let blobs = dir.join("blobs").join("sha256");
+            file::create_dir_all(&blobs)?;
+            let mut manifests = vec![];
+            for (index, annotations) in images.iter().enumerate() {
+                let config = format!("config{index}");
+                let manifest = format!("manifest{index}");
+                fs::write(
+                    blobs.join(&config),
+                    format!(r#"{{"config":{{"WorkingDir":"/{source}/{index}"}}}}"#),
+                )?;
+                fs::write(
+                    blobs.join(&manifest),
+                    format!(r#"{{"config":{{"digest":"sha256:{config}"}},"layers":[]}}"#),
+                )?;
+                let annotations: BTreeMap<_, _> = annotations.iter().copied().collect();
+                manifests.push(serde_json::json!({
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": format!("sha256:{manifest}"),
+                    "annotations": annotations,
+                }));
+            }
+            fs::write(
+                dir.join("index.json"),
+                serde_json::json!({ "manifests": manifests }).to_string(),
+            )?;
+            Ok(dir.to_owned())
+        }
+        fn find(dirs: &[PathBuf], name: &str) -> Result<Option<String>> {
+            let platform = ImagePlatform::X86_64_UNKNOWN_LINUX_GNU;
+            Ok(find_in_layouts(dirs, name, &platform)?.and_then(|i| i.config.working_dir))
+        }
+
+        let tmp = tempfile::tempdir()?;
+        let ref_name = "org.opencontainers.image.ref.name";
+        let image_name = "io.containerd.image.name";
+        // two sources with an image tagged `main`, the second being named.
+        let tagged = layout(&tmp.path().join("tagged"), &[&[(ref_name, "main")]])?;
+        let named = layout(
+            &tmp.path().join("named"),
+            &[&[(ref_name, "main"), (image_name, "ghcr.io/cross-rs/b:main")]],
+        )?;
+        let dirs = [tagged.clone(), named];
+        assert_eq!(
+            find(&dirs, "ghcr.io/cross-rs/b:main")?.as_deref(),
+            Some("/named/0")
+        );
+        assert_eq!(
+            find(&dirs, "ghcr.io/cross-rs/c:main")?.as_deref(),
+            Some("/tagged/0")
+        );
+        assert_eq!(
+            find(&dirs[1..], "ghcr.io/cross-rs/c:main")?.as_deref(),
+            Some("/named/0")
+        );
+        assert_eq!(find(&dirs, "ghcr.io/cross-rs/a:edge")?, None);
+        // the tag alone is ambiguous with several images in the layout.
+        let several = layout(
+            &tmp.path().join("several"),
+            &[&[(ref_name, "main")], &[(ref_name, "edge")]],
+        )?;
+        assert_eq!(find(&[several.clone()], "ghcr.io/cross-rs/a:main")?, None);
+        assert_eq!(find(&[several], "edge")?.as_deref(), Some("/several/1"));
+        // an unnamed image is only used if it's the only image.
+        let unnamed = layout(&tmp.path().join("unnamed"), &[&[]])?;
+        assert_eq!(
+            find(&[unnamed.clone()], "image")?.as_deref(),
+            Some("/unnamed/0")
+        );
+        assert_eq!(find(&[tagged, unnamed], "image")?, None);
+        assert_eq!(tag("localhost:5000/image"), None);
+
+        Ok(())
+    }
+}
@@ -1,15 +1,19 @@
 use std::collections::BTreeMap;
 use std::io::{self, BufRead, Read, Write};
-use std::path::Path;
-use std::process::{Command, ExitStatus};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 use std::{env, fs, time};
 
 use eyre::Context;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 
-use super::engine::Engine;
+use super::engine::{Engine, EngineCommand};
+use super::report::{ReportWriter, TestCollector};
 use super::shared::*;
+use super::{archive, crash, install, kubernetes};
 use crate::config::bool_from_envvar;
-use crate::errors::Result;
+use crate::cross_toml::RemoteArtifacts;
+use crate::errors::{Result, Section};
 use crate::extensions::CommandExt;
 use crate::file::{self, PathExt, ToUtf8};
 use crate::rustc::{self, QualifiedToolchain, VersionMetaExt};
@@ -30,11 +34,25 @@ macro_rules! bail_container_exited {
 }
 
 #[track_caller]
-fn subcommand_or_exit(engine: &Engine, cmd: &str) -> Result<Command> {
+fn subcommand_or_exit(engine: &Engine, cmd: &str) -> Result<EngineCommand> {
     bail_container_exited!();
     Ok(engine.subcommand(cmd))
 }
 
+// the `exec` command for the container, to which the command is appended.
+#[track_caller]
+fn exec_or_exit(engine: &Engine, container: &str, interactive: bool) -> Result<EngineCommand> {
+    let mut command = subcommand_or_exit(engine, "exec")?;
+    if interactive {
+        command.arg("-i");
+    }
+    command.arg(container);
+    if engine.kind.is_kubernetes() {
+        command.arg("--");
+    }
+    Ok(command)
+}
+
 pub fn posix_parent(path: &str) -> Option<&str> {
     Path::new(path).parent()?.to_str()
 }
@@ -51,12 +69,32 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         msg_info: &mut MessageInfo,
     ) -> Result<ExitStatus> {
         // make our parent directory if needed
-        subcommand_or_exit(self.engine, "exec")?
-            .arg(self.container)
+        exec_or_exit(self.engine, self.container, false)?
             .args(["sh", "-c", &format!("mkdir -p '{mount_prefix}/{reldir}'")])
             .run_and_get_status(msg_info, false)
     }
 
+    /// Stream an archive to a docker volume, extracting it to `reldst`.
+    ///
+    /// `reldst` has the same caveats as `reldir` in [`Self::create_dir`].
+    #[track_caller]
+    fn send_archive<T>(
+        &self,
+        reldst: &str,
+        mount_prefix: &str,
+        msg_info: &mut MessageInfo,
+        build: impl FnOnce(&mut archive::Builder<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let compression = archive::Compression::from_env()?;
+        let mut command = exec_or_exit(self.engine, self.container, true)?;
+        command.args([
+            "sh",
+            "-c",
+            &compression.extract_script(&format!("{mount_prefix}/{reldst}")),
+        ]);
+        archive::send(command, compression, msg_info, build)
+    }
+
     /// Copy files for a docker volume
     ///
     /// `reldst` has the same caveats as `reldir` in [`Self::create_dir`].
@@ -72,11 +110,12 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         reldst: &str,
         mount_prefix: &str,
         msg_info: &mut MessageInfo,
-    ) -> Result<ExitStatus> {
+    ) -> Result<()> {
+        let is_contents = is_contents_path(src);
         if let Some((_, rel)) = reldst.rsplit_once('/') {
             if msg_info.cross_debug
                 && src.is_dir()
-                && !src.to_string_lossy().ends_with("/.")
+                && !is_contents
                 && rel
                     == src
                         .file_name()
@@ -90,36 +129,47 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
                 ))?;
             }
         }
-        subcommand_or_exit(self.engine, "cp")?
-            .arg("-a")
-            .arg(src.to_utf8()?)
-            .arg(format!("{}:{mount_prefix}/{reldst}", self.container))
-            .run_and_get_status(msg_info, false)
+        let name = if is_contents {
+            PathBuf::new()
+        } else {
+            PathBuf::from(src.file_name().expect("source should have a file name"))
+        };
+        self.send_archive(reldst, mount_prefix, msg_info, |builder| {
+            if src.is_dir() {
+                archive::append_dir(builder, src, &name, 0, |_, _| false)?;
+            } else {
+                builder.append_path_with_name(src, &name)?;
+            }
+            Ok(())
+        })
     }
 
     /// copy files for a docker volume, does not include cache directories
+    /// unless `copy_cache` is set, or files excluded by `.crossignore`.
+    /// returns the number of bytes transferred.
     ///
-    /// ## Note
-    ///
-    /// if copying from a src directory to dst directory with docker, to
-    /// copy the contents from `src` into `dst`, `src` must end with `/.`
+    /// `src` is the directory whose contents are copied into `reldst`.
     #[track_caller]
-    fn copy_files_nocache(
+    fn copy_files_filtered(
         &self,
         src: &Path,
         reldst: &str,
         mount_prefix: &str,
-        copy_symlinks: bool,
+        copy_cache: bool,
+        ignore: Option<&Gitignore>,
         msg_info: &mut MessageInfo,
-    ) -> Result<ExitStatus> {
+    ) -> Result<u64> {
         // avoid any cached directories when copying
         // see https://bford.info/cachedir/
-        // SAFETY: safe, single-threaded execution.
-        let tempdir = unsafe { temp::TempDir::new()? };
-        let temppath = tempdir.path();
-        let had_symlinks = copy_dir(src, temppath, copy_symlinks, 0, |e, _| is_cachedir(e))?;
+        let (had_symlinks, bytes) =
+            self.send_archive(reldst, mount_prefix, msg_info, |builder| {
+                archive::append_dir(builder, src, Path::new(""), 0, |e, _| {
+                    (!copy_cache && is_cachedir(e)) || is_crossignored(ignore, e)
+                })
+            })?;
         warn_symlinks(had_symlinks, msg_info)?;
-        self.copy_files(&temppath.join("."), reldst, mount_prefix, msg_info)
+
+        Ok(bytes)
     }
 
     // copy files for a docker volume, for remote host support
@@ -132,18 +182,15 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         mount_prefix: &str,
         files: &[&str],
         msg_info: &mut MessageInfo,
-    ) -> Result<ExitStatus> {
-        // SAFETY: safe, single-threaded execution.
-        let tempdir = unsafe { temp::TempDir::new()? };
-        let temppath = tempdir.path();
-        for file in files {
-            let src_path = src.join(file);
-            let dst_path = temppath.join(file);
-            file::create_dir_all(dst_path.parent().expect("must have parent"))?;
-            fs::copy(src_path, &dst_path)?;
-        }
-
-        self.copy_files(&temppath.join("."), reldst, mount_prefix, msg_info)
+    ) -> Result<()> {
+        self.send_archive(reldst, mount_prefix, msg_info, |builder| {
+            for file in files {
+                builder
+                    .append_path_with_name(src.join(file), file)
+                    .wrap_err_with(|| format!("when archiving file {file:?}"))?;
+            }
+            Ok(())
+        })
     }
 
     // removed files from a docker volume, for remote host support
@@ -156,37 +203,28 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         files: &[&str],
         msg_info: &mut MessageInfo,
     ) -> Result<ExitStatus> {
-        const PATH: &str = "/tmp/remove_list";
         let mut script = vec![];
         if msg_info.is_verbose() {
             script.push("set -x".to_owned());
         }
-        script.push(format!(
-            "cat \"{PATH}\" | while read line; do
-        rm -f \"${{line}}\"
+        script.push(
+            "while read -r line; do
+        rm -f \"${line}\"
     done
-
-    rm \"{PATH}\"
     "
-        ));
+            .to_owned(),
+        );
 
-        // SAFETY: safe, single-threaded execution.
-        let mut tempfile = unsafe { temp::TempFile::new()? };
+        // need to avoid having hundreds of files on the command, so
+        // just provide the list through stdin.
+        let mut list = vec![];
         for file in files {
-            writeln!(tempfile.file(), "{mount_prefix}/{reldst}/{file}")?;
+            writeln!(list, "{mount_prefix}/{reldst}/{file}")?;
         }
 
-        // need to avoid having hundreds of files on the command, so
-        // just provide a single file name.
-        subcommand_or_exit(self.engine, "cp")?
-            .arg(tempfile.path())
-            .arg(format!("{}:{PATH}", self.container))
-            .run_and_get_status(msg_info, true)?;
-
-        subcommand_or_exit(self.engine, "exec")?
-            .arg(self.container)
+        exec_or_exit(self.engine, self.container, true)?
             .args(["sh", "-c", &script.join("\n")])
-            .run_and_get_status(msg_info, true)
+            .run_and_get_status_with_stdin(&list, msg_info, true)
     }
 
     #[track_caller]
@@ -196,8 +234,7 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         mount_prefix: &str,
         msg_info: &mut MessageInfo,
     ) -> Result<bool> {
-        Ok(subcommand_or_exit(self.engine, "exec")?
-            .arg(self.container)
+        Ok(exec_or_exit(self.engine, self.container, false)?
             .args([
                 "bash",
                 "-c",
@@ -269,43 +306,45 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
     #[track_caller]
     fn copy_rust_base(&self, mount_prefix: &str, msg_info: &mut MessageInfo) -> Result<()> {
         let dirs = &self.toolchain_dirs;
+        let sysroot = dirs.get_sysroot();
 
         // the rust toolchain is quite large, but most of it isn't needed
         // we need the bin, libexec, and etc directories, and part of the lib directory.
         let reldst = dirs.sysroot_mount_path_relative()?;
         let rustlib = "lib/rustlib";
-        self.create_dir(&format!("{reldst}/{}", rustlib), mount_prefix, msg_info)?;
-        for basename in ["bin", "libexec", "etc"] {
-            let file = dirs.get_sysroot().join(basename);
-            self.copy_files(&file, &reldst, mount_prefix, msg_info)?;
-        }
+        let had_symlinks = self.send_archive(&reldst, mount_prefix, msg_info, |builder| {
+            let mut had_symlinks = false;
+            for basename in ["bin", "libexec", "etc"] {
+                let file = sysroot.join(basename);
+                if file.exists() {
+                    let name = Path::new(basename);
+                    had_symlinks |= archive::append_dir(builder, &file, name, 0, |_, _| false)?.0;
+                }
+            }
 
-        // the lib directories are rather large, so we want only a subset.
-        // now, we use a temp directory for everything else in the libdir
-        // we can pretty safely assume we don't have symlinks here.
-
-        // first, copy the shared libraries inside lib, all except rustlib.
-        // SAFETY: safe, single-threaded execution.
-        let tempdir = unsafe { temp::TempDir::new()? };
-        let temppath = tempdir.path();
-        file::create_dir_all(temppath.join(rustlib))?;
-        let mut had_symlinks = copy_dir(
-            &dirs.get_sysroot().join("lib"),
-            &temppath.join("lib"),
-            true,
-            0,
-            |e, d| d == 0 && e.file_name() == "rustlib",
-        )?;
-
-        // next, copy the src/etc directories inside rustlib
-        had_symlinks |= copy_dir(
-            &dirs.get_sysroot().join(rustlib),
-            &temppath.join(rustlib),
-            true,
-            0,
-            |e, d| d == 0 && !(e.file_name() == "src" || e.file_name() == "etc"),
-        )?;
-        self.copy_files(&temppath.join("lib"), &reldst, mount_prefix, msg_info)?;
+            // the lib directories are rather large, so we want only a subset.
+            // first, copy the shared libraries inside lib, all except rustlib.
+            had_symlinks |= archive::append_dir(
+                builder,
+                &sysroot.join("lib"),
+                Path::new("lib"),
+                0,
+                |e, d| d == 0 && e.file_name() == "rustlib",
+            )?
+            .0;
+
+            // next, copy the src/etc directories inside rustlib
+            had_symlinks |= archive::append_dir(
+                builder,
+                &sysroot.join(rustlib),
+                Path::new(rustlib),
+                0,
+                |e, d| d == 0 && !(e.file_name() == "src" || e.file_name() == "etc"),
+            )?
+            .0;
+
+            Ok(had_symlinks)
+        })?;
 
         warn_symlinks(had_symlinks, msg_info)
     }
@@ -318,19 +357,15 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         // these are small text files containing names/paths to toolchains
         let reldst = dirs.sysroot_mount_path_relative()?;
         let rustlib = "lib/rustlib";
-
-        // SAFETY: safe, single-threaded execution.
-        let tempdir = unsafe { temp::TempDir::new()? };
-        let temppath = tempdir.path();
-        file::create_dir_all(temppath.join(rustlib))?;
-        let had_symlinks = copy_dir(
-            &dirs.get_sysroot().join(rustlib),
-            &temppath.join(rustlib),
-            true,
-            0,
-            |e, d| d != 0 || e.file_type().map(|t| !t.is_file()).unwrap_or(true),
-        )?;
-        self.copy_files(&temppath.join("lib"), &reldst, mount_prefix, msg_info)?;
+        let (had_symlinks, _) = self.send_archive(&reldst, mount_prefix, msg_info, |builder| {
+            archive::append_dir(
+                builder,
+                &dirs.get_sysroot().join(rustlib),
+                Path::new(rustlib),
+                0,
+                |e, d| d != 0 || e.file_type().map_or(true, |t| !t.is_file()),
+            )
+        })?;
 
         warn_symlinks(had_symlinks, msg_info)
     }
@@ -405,53 +440,100 @@ impl<'a, 'b, 'c> ContainerDataVolume<'a, 'b, 'c> {
         copy_cache: bool,
         msg_info: &mut MessageInfo,
     ) -> Result<()> {
-        let copy_all = |info: &mut MessageInfo| {
-            if copy_cache {
-                self.copy_files(&src.join("."), reldst, mount_prefix, info)
-            } else {
-                self.copy_files_nocache(&src.join("."), reldst, mount_prefix, true, info)
-            }
+        let ignore = read_crossignore(src)?;
+        let parent = temp::dir()?;
+        file::create_dir_all(&parent)?;
+
+        let toolchain = &self.toolchain_dirs.toolchain();
+        let filename = toolchain.unique_mount_identifier(src)?;
+        // the fingerprints of discarded volumes are kept apart, since they
+        // don't describe the contents of the persistent volume.
+        let fingerprint = match volume {
+            VolumeId::Keep(_) => parent.join(filename),
+            VolumeId::Discard => parent.join(format!("{filename}-discard")),
+        };
+        // the previous fingerprint avoids hashing unmodified files, and is
+        // diffed against if its files are in the volume. need to check if the
+        // container path exists, otherwise we might have stale data: the
+        // persistent volume was deleted & recreated. a discarded volume is
+        // created empty for every run. fingerprints from older versions
+        // cannot be parsed, and so require a full copy.
+        let previous = if fingerprint.exists() {
+            Fingerprint::read_file(&fingerprint).ok()
+        } else {
+            None
         };
-        match volume {
+        let in_volume = match volume {
             VolumeId::Keep(_) => {
-                let parent = temp::dir()?;
-                file::create_dir_all(&parent)?;
-
-                let toolchain = &self.toolchain_dirs.toolchain();
-                let filename = toolchain.unique_mount_identifier(src)?;
-                let fingerprint = parent.join(filename);
-                let current = Fingerprint::read_dir(src, copy_cache)?;
-                // need to check if the container path exists, otherwise we might
-                // have stale data: the persistent volume was deleted & recreated.
-                if fingerprint.exists()
-                    && self.container_path_exists(reldst, mount_prefix, msg_info)?
-                {
-                    let previous = Fingerprint::read_file(&fingerprint)?;
-                    let (to_copy, to_remove) = previous.difference(&current);
-                    if !to_copy.is_empty() {
-                        self.copy_file_list(src, reldst, mount_prefix, &to_copy, msg_info)?;
-                    }
-                    if !to_remove.is_empty() {
-                        self.remove_file_list(reldst, mount_prefix, &to_remove, msg_info)?;
-                    }
-
-                    // write fingerprint afterwards, in case any failure so we
-                    // ensure any changes will be made on subsequent runs
-                    current.write_file(&fingerprint)?;
-                } else {
-                    current.write_file(&fingerprint)?;
-                    copy_all(msg_info)?;
-                }
+                previous.is_some() && self.container_path_exists(reldst, mount_prefix, msg_info)?
             }
-            VolumeId::Discard => {
-                copy_all(msg_info)?;
+            VolumeId::Discard => false,
+        };
+        let current = Fingerprint::read_dir(src, copy_cache, ignore.as_ref(), previous.as_ref())?;
+        let bytes = match previous.filter(|_| in_volume) {
+            Some(previous) => {
+                let (to_copy, to_remove) = previous.difference(&current);
+                if !to_copy.is_empty() {
+                    self.copy_file_list(src, reldst, mount_prefix, &to_copy, msg_info)?;
+                }
+                if !to_remove.is_empty() {
+                    self.remove_file_list(reldst, mount_prefix, &to_remove, msg_info)?;
+                }
+                current.size_of(&to_copy)
             }
-        }
+            None => self.copy_files_filtered(
+                &src.join("."),
+                reldst,
+                mount_prefix,
+                copy_cache,
+                ignore.as_ref(),
+                msg_info,
+            )?,
+        };
+        // write fingerprint afterwards, in case any failure so we
+        // ensure any changes will be made on subsequent runs
+        current.write_file(&fingerprint)?;
+        msg_info.note(format_args!(
+            "synced {} to {mount_prefix}/{reldst}, transferred {bytes} bytes.",
+            src.to_utf8()?
+        ))?;
 
         Ok(())
     }
 }
 
+// read the `.crossignore` file at the root of a copied directory,
+// which uses gitignore syntax to exclude files from remote copies.
+fn read_crossignore(root: &Path) -> Result<Option<Gitignore>> {
+    let path = root.join(".crossignore");
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(error) = builder.add(&path) {
+        return Err(error).wrap_err_with(|| format!("when parsing {path:?}"));
+    }
+    Ok(Some(
+        builder
+            .build()
+            .wrap_err_with(|| format!("when parsing {path:?}"))?,
+    ))
+}
+
+fn is_crossignored(ignore: Option<&Gitignore>, entry: &fs::DirEntry) -> bool {
+    ignore.map_or(false, |ignore| {
+        let is_dir = matches!(entry.file_type(), Ok(t) if t.is_dir());
+        ignore.matched(entry.path(), is_dir).is_ignore()
+    })
+}
+
+// if the path ends with `/.`, meaning to copy the contents of the directory
+fn is_contents_path(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.ends_with("/.") || (cfg!(windows) && path.ends_with("\\."))
+}
+
 fn is_cachedir_tag(path: &Path) -> Result<bool> {
     let mut buffer = [b'0'; 43];
     let mut file = fs::OpenOptions::new().read(true).open(path)?;
@@ -471,65 +553,6 @@ fn is_cachedir(entry: &fs::DirEntry) -> bool {
     }
 }
 
-// recursively copy a directory into another
-fn copy_dir<Skip>(
-    src: &Path,
-    dst: &Path,
-    copy_symlinks: bool,
-    depth: u32,
-    skip: Skip,
-) -> Result<bool>
-where
-    Skip: Copy + Fn(&fs::DirEntry, u32) -> bool,
-{
-    let mut had_symlinks = false;
-
-    for entry in fs::read_dir(src).wrap_err_with(|| format!("when reading directory {src:?}"))? {
-        let file = entry?;
-        if skip(&file, depth) {
-            continue;
-        }
-
-        let src_path = file.path();
-        let dst_path = dst.join(file.file_name());
-        if file.file_type()?.is_file() {
-            fs::copy(&src_path, &dst_path)
-                .wrap_err_with(|| format!("when copying file {src_path:?} -> {dst_path:?}"))?;
-        } else if file.file_type()?.is_dir() {
-            fs::create_dir(&dst_path).ok();
-            had_symlinks = copy_dir(&src_path, &dst_path, copy_symlinks, depth + 1, skip)?;
-        } else if copy_symlinks {
-            had_symlinks = true;
-            let link_dst = fs::read_link(src_path)?;
-
-            #[cfg(target_family = "unix")]
-            {
-                std::os::unix::fs::symlink(link_dst, dst_path)?;
-            }
-
-            #[cfg(target_family = "windows")]
-            {
-                let link_dst_absolute = if link_dst.is_absolute() {
-                    link_dst.clone()
-                } else {
-                    // we cannot fail even if the linked to path does not exist.
-                    src.join(&link_dst)
-                };
-                if link_dst_absolute.is_dir() {
-                    std::os::windows::fs::symlink_dir(link_dst, dst_path)?;
-                } else {
-                    // symlink_file handles everything that isn't a directory
-                    std::os::windows::fs::symlink_file(link_dst, dst_path)?;
-                }
-            }
-        } else {
-            had_symlinks = true;
-        }
-    }
-
-    Ok(had_symlinks)
-}
-
 fn warn_symlinks(had_symlinks: bool, msg_info: &mut MessageInfo) -> Result<()> {
     if had_symlinks {
         msg_info.warn("copied directory contained symlinks. if the volume the link points to was not mounted, the remote build may fail")
@@ -538,9 +561,16 @@ fn warn_symlinks(had_symlinks: bool, msg_info: &mut MessageInfo) -> Result<()> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileFingerprint {
+    modified: time::SystemTime,
+    size: u64,
+    hash: String,
+}
+
 #[derive(Debug)]
 struct Fingerprint {
-    map: BTreeMap<String, time::SystemTime>,
+    map: BTreeMap<String, FileFingerprint>,
 }
 
 impl Fingerprint {
@@ -556,11 +586,22 @@ impl Fingerprint {
         let mut map = BTreeMap::new();
         for line in reader.lines() {
             let line = line?;
-            let (timestamp, relpath) = line
-                .split_once('\t')
-                .ok_or_else(|| eyre::eyre!("unable to parse fingerprint line '{line}'"))?;
-            let modified = time_from_millis(timestamp.parse::<u64>()?);
-            map.insert(relpath.to_owned(), modified);
+            let mut split = line.splitn(4, '\t');
+            let (timestamp, size, hash, relpath) =
+                match (split.next(), split.next(), split.next(), split.next()) {
+                    (Some(timestamp), Some(size), Some(hash), Some(relpath)) => {
+                        (timestamp, size, hash, relpath)
+                    }
+                    _ => eyre::bail!("unable to parse fingerprint line '{line}'"),
+                };
+            map.insert(
+                relpath.to_owned(),
+                FileFingerprint {
+                    modified: time_from_millis(timestamp.parse::<u64>()?),
+                    size: size.parse::<u64>()?,
+                    hash: hash.to_owned(),
+                },
+            );
         }
 
         Ok(Self { map })
@@ -572,48 +613,91 @@ impl Fingerprint {
             .truncate(true)
             .create(true)
             .open(path)?;
-        for (relpath, modified) in &self.map {
-            let timestamp = time_to_millis(modified)?;
-            writeln!(file, "{timestamp}\t{relpath}")?;
+        for (relpath, entry) in &self.map {
+            let timestamp = time_to_millis(&entry.modified)?;
+            writeln!(
+                file,
+                "{timestamp}\t{}\t{}\t{relpath}",
+                entry.size, entry.hash
+            )?;
         }
 
         Ok(())
     }
 
-    fn _read_dir(&mut self, home: &Path, path: &Path, copy_cache: bool) -> Result<()> {
+    fn _read_dir(
+        &mut self,
+        home: &Path,
+        path: &Path,
+        copy_cache: bool,
+        ignore: Option<&Gitignore>,
+        previous: Option<&Fingerprint>,
+    ) -> Result<()> {
         for entry in fs::read_dir(path)? {
             let file = entry?;
             let file_type = file.file_type()?;
+            if is_crossignored(ignore, &file) {
+                continue;
+            }
             // only parse known files types: 0 or 1 of these tests can pass.
             if file_type.is_dir() {
                 if copy_cache || !is_cachedir(&file) {
-                    self._read_dir(home, &path.join(file.file_name()), copy_cache)?;
+                    self._read_dir(
+                        home,
+                        &path.join(file.file_name()),
+                        copy_cache,
+                        ignore,
+                        previous,
+                    )?;
                 }
             } else if file_type.is_file() || file_type.is_symlink() {
                 // we're mounting to the same location, so this should fine
                 // we need to round the modified date to millis.
-                let modified = file.metadata()?.modified()?;
-                let rounded = time_from_millis(time_to_millis(&modified)?);
+                let metadata = file.metadata()?;
+                let modified = time_from_millis(time_to_millis(&metadata.modified()?)?);
+                let size = metadata.len();
                 let relpath = file.path().strip_prefix(home)?.as_posix_relative()?;
-                self.map.insert(relpath, rounded);
+                // hashing is expensive, so only rehash if the file might have changed.
+                let hash = match previous.and_then(|p| p.map.get(&relpath)) {
+                    Some(entry) if entry.modified == modified && entry.size == size => {
+                        entry.hash.clone()
+                    }
+                    _ => hash_file(&file.path(), file_type)?,
+                };
+                self.map.insert(
+                    relpath,
+                    FileFingerprint {
+                        modified,
+                        size,
+                        hash,
+                    },
+                );
             }
         }
 
         Ok(())
     }
 
-    fn read_dir(home: &Path, copy_cache: bool) -> Result<Fingerprint> {
+    // previous is an older fingerprint of the same directory, used to
+    // avoid hashing files that have not been modified.
+    fn read_dir(
+        home: &Path,
+        copy_cache: bool,
+        ignore: Option<&Gitignore>,
+        previous: Option<&Fingerprint>,
+    ) -> Result<Fingerprint> {
         let mut result = Fingerprint::new();
-        result._read_dir(home, home, copy_cache)?;
+        result._read_dir(home, home, copy_cache, ignore, previous)?;
         Ok(result)
     }
 
     // returns to_copy (added + modified) and to_remove (removed).
+    // files are only considered modified if their contents changed.
     fn difference<'a, 'b>(&'a self, current: &'b Fingerprint) -> (Vec<&'b str>, Vec<&'a str>) {
         let to_copy: Vec<&str> = current
             .map
             .iter()
-            .filter(|(k, v1)| self.map.get(*k).map_or(true, |v2| v1 != &v2))
+            .filter(|(k, v1)| self.map.get(*k).map_or(true, |v2| v1.hash != v2.hash))
             .map(|(k, _)| k.as_str())
             .collect();
         let to_remove: Vec<&str> = self
@@ -624,6 +708,152 @@ impl Fingerprint {
             .collect();
         (to_copy, to_remove)
     }
+
+    // the total size of the given files, in bytes.
+    fn size_of(&self, files: &[&str]) -> u64 {
+        files
+            .iter()
+            .filter_map(|f| self.map.get(*f))
+            .map(|e| e.size)
+            .sum()
+    }
+}
+
+fn hash_file(path: &Path, file_type: fs::FileType) -> Result<String> {
+    let mut hasher = sha1_smol::Sha1::new();
+    if file_type.is_symlink() {
+        hasher.update(fs::read_link(path)?.to_string_lossy().as_bytes());
+    } else {
+        let mut file = fs::File::open(path).wrap_err_with(|| format!("when hashing {path:?}"))?;
+        let mut buffer = [0; 8192];
+        loop {
+            let count = file.read(&mut buffer)?;
+            if count == 0 {
+                break;
+            }
+            hasher.update(&buffer[..count]);
+        }
+    }
+
+    Ok(hasher.digest().to_string())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CargoMessage {
+    reason: String,
+    manifest_path: Option<String>,
+    #[serde(default)]
+    target: CargoMessageTarget,
+    #[serde(default)]
+    filenames: Vec<String>,
+    executable: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct CargoMessageTarget {
+    #[serde(default)]
+    kind: Vec<String>,
+}
+
+impl CargoMessage {
+    // only the artifacts of the workspace members are final, and not the
+    // ones of the dependencies or the build scripts. the package ids contain
+    // the paths on the host, so the members are found by their manifests.
+    fn is_member_artifact(&self, members: &[String]) -> bool {
+        self.reason == "compiler-artifact"
+            && self
+                .manifest_path
+                .as_ref()
+                .map_or(false, |m| members.contains(m))
+            && !self.target.kind.iter().any(|k| k == "custom-build")
+    }
+}
+
+// forward the stdout of cargo, collecting the artifacts from any
+// `compiler-artifact` messages of the workspace members. if `forward_json`
+// is not set, the messages were requested by us and are not forwarded.
+fn filter_cargo_messages(
+    mut reader: impl BufRead,
+    writer: &mut impl Write,
+    forward_json: bool,
+    members: &[String],
+    artifacts: &mut Vec<String>,
+) -> Result<()> {
+    let mut line = vec![];
+    while reader.read_until(b'\n', &mut line)? != 0 {
+        match serde_json::from_slice::<CargoMessage>(&line) {
+            Ok(message) => {
+                if message.is_member_artifact(members) {
+                    artifacts.extend(message.filenames);
+                    artifacts.extend(message.executable);
+                }
+                if forward_json {
+                    writer.write_all(&line)?;
+                }
+            }
+            Err(_) => writer.write_all(&line)?,
+        }
+        writer.flush()?;
+        line.clear();
+    }
+
+    Ok(())
+}
+
+// get the message format requested by the user, if any.
+fn message_format(args: &[String]) -> Option<&str> {
+    let mut iter = args.iter().take_while(|a| *a != "--");
+    while let Some(arg) = iter.next() {
+        if arg == "--message-format" {
+            return iter.next().map(String::as_str);
+        } else if let Some(format) = arg.strip_prefix("--message-format=") {
+            return Some(format);
+        }
+    }
+
+    None
+}
+
+// list all files in the target directory matching any of the globs.
+fn find_artifact_globs(
+    engine: &Engine,
+    container: &str,
+    mount_target_dir: &str,
+    globs: &[String],
+    msg_info: &mut MessageInfo,
+) -> Result<Vec<String>> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(
+            globset::GlobBuilder::new(glob)
+                .literal_separator(true)
+                .build()
+                .wrap_err_with(|| format!("invalid artifact glob `{glob}`"))?,
+        );
+    }
+    let set = builder.build()?;
+    let stdout = exec_or_exit(engine, container, false)?
+        .args([
+            "sh",
+            "-c",
+            &format!(
+                "cd {} && find . -type f -o -type l",
+                shell_escape::unix::escape(mount_target_dir.into())
+            ),
+        ])
+        .run_and_get_stdout(msg_info)?;
+
+    Ok(stdout
+        .lines()
+        .filter_map(|l| l.strip_prefix("./"))
+        .filter(|l| set.is_match(l))
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+// a script printing the files to copy, one per line.
+fn file_list_script(files: &[String]) -> String {
+    format!("cat <<'CROSS_EOF'\n{}\nCROSS_EOF", files.join("\n"))
 }
 
 impl QualifiedToolchain {
@@ -656,6 +886,18 @@ impl QualifiedToolchain {
         Ok(format!("{toolchain_id}-{triple}-{cwd_path}-{system_time}"))
     }
 
+    // identifier for a persistent session container. unlike the container
+    // identifier, this is stable across runs for the same target and project.
+    pub fn unique_session_identifier(
+        &self,
+        triple: &TargetTriple,
+        project: &Path,
+    ) -> Result<String> {
+        let toolchain_id = self.unique_toolchain_identifier()?;
+        let project_hash = path_hash(project, PATH_HASH_SHORT)?;
+        Ok(format!("{toolchain_id}-{triple}-{project_hash}-session"))
+    }
+
     // unique identifier for a given mounted volume
     pub fn unique_mount_identifier(&self, path: &Path) -> Result<String> {
         let toolchain_id = self.unique_toolchain_identifier()?;
@@ -664,6 +906,59 @@ impl QualifiedToolchain {
     }
 }
 
+// the reason custom images cannot be built for the engine, if any.
+fn custom_image_unsupported(engine: &Engine) -> Option<String> {
+    if engine.kind.is_kubernetes() {
+        // the image is built by a local engine, which the cluster cannot pull from.
+        Some("custom images are not supported with Kubernetes".to_owned())
+    } else {
+        // the build context is on the local filesystem, which the
+        // engine on the build host cannot access.
+        engine.ssh.as_ref().map(|ssh| {
+            format!(
+                "cannot build custom images on the build host `{}`",
+                ssh.destination
+            )
+        })
+    }
+}
+
+// run cargo, collecting the artifacts it reports from stdout, and the
+// tests if a collector is provided.
+fn run_and_collect_artifacts(
+    mut docker: EngineCommand,
+    forward_json: bool,
+    members: &[String],
+    artifacts: &mut Vec<String>,
+    collector: Option<&mut TestCollector>,
+    msg_info: &mut MessageInfo,
+) -> Result<ExitStatus> {
+    docker.debug(msg_info)?;
+    let mut child = docker
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .wrap_err("when running cargo")?;
+    let stdout = io::BufReader::new(child.stdout.take().expect("stdout should be piped"));
+    let result = match collector {
+        Some(collector) => {
+            let mut writer = ReportWriter::new(collector, io::stdout());
+            filter_cargo_messages(stdout, &mut writer, forward_json, members, artifacts)
+                .and_then(|_| Ok(writer.finish()?))
+        }
+        None => filter_cargo_messages(stdout, &mut io::stdout(), forward_json, members, artifacts),
+    };
+    let status = child.wait()?;
+    result?;
+
+    Ok(status)
+}
+
+// lists the final build artifacts in a target directory, ignoring all
+// intermediate artifacts. every profile directory contains a `.cargo-lock`.
+const FINAL_ARTIFACTS_SCRIPT: &str = r#"find . -mindepth 2 -maxdepth 3 -name .cargo-lock | while read -r lock; do
+    find "${lock%/*}" -maxdepth 1 -type f ! -name '.*' ! -name '*.d'
+done"#;
+
 pub(crate) fn run(
     options: DockerOptions,
     paths: DockerPaths,
@@ -678,7 +973,11 @@ pub(crate) fn run(
 
     let mount_prefix = MOUNT_PREFIX;
 
-    if options.in_docker() {
+    if options.keep_alive {
+        msg_info.warn("`--keep-alive` is not supported with remote cross, ignoring it.")?;
+    }
+    // without a path mapping, data volumes are used on purpose.
+    if options.in_docker() && !paths.mount_finder.is_empty() {
         msg_info.warn("remote and docker-in-docker are unlikely to work together when using cross. remote cross uses data volumes, so docker-in-docker should not be required.")?;
     }
 
@@ -705,8 +1004,13 @@ pub(crate) fn run(
     // note that since we use `docker run --rm`, it's very
     // unlikely the container state existed before.
     let toolchain_id = toolchain_dirs.unique_toolchain_identifier()?;
-    let container_id = toolchain_dirs.unique_container_identifier(target.target())?;
-    let volume = {
+    let mut container_id = toolchain_dirs.unique_container_identifier(target.target())?;
+    if engine.kind.is_kubernetes() {
+        container_id = kubernetes::pod_name(&container_id);
+    }
+    let volume = if engine.kind.is_kubernetes() {
+        kubernetes::volume(toolchain_id)
+    } else {
         let existing = DockerVolume::existing(engine, toolchain_dirs.toolchain(), msg_info)?;
         if existing.iter().any(|v| v == &toolchain_id) {
             VolumeId::Keep(toolchain_id)
@@ -760,8 +1064,20 @@ pub(crate) fn run(
         .wrap_err("could not determine mount points")?;
 
     docker
-        .add_seccomp(engine.kind, target, &paths.metadata)
+        .add_seccomp(
+            engine,
+            target,
+            &paths.metadata,
+            options.config.sandbox()?,
+            msg_info,
+        )
         .wrap_err("when copying seccomp profile")?;
+    docker.add_resources(&options, msg_info)?;
+    if engine.kind.is_kubernetes() {
+        // the variables are stored in the secret of the pod, since
+        // passing them to `kubectl exec` would expose them in the arguments.
+        docker.add_envvars(&options, &paths, msg_info)?;
+    }
 
     // Prevent `bin` from being mounted inside the Docker container.
     docker.args(["-v", &format!("{mount_prefix}/cargo/bin")]);
@@ -782,6 +1098,14 @@ pub(crate) fn run(
     let mut image_name = options.image.name.clone();
 
     if options.needs_custom_image() {
+        if let Some(reason) = custom_image_unsupported(engine) {
+            return Err(eyre::eyre!(reason)).with_suggestion(|| {
+                format!(
+                    "build and push the image separately, and use it with `target.{}.image`",
+                    target.triple()
+                )
+            });
+        }
         image_name = options
             .custom_image_build(&paths, msg_info)
             .wrap_err("when building custom image")?;
@@ -800,7 +1124,12 @@ pub(crate) fn run(
 
     // store first, since failing to non-existing container is fine
     ChildContainer::create(engine.clone(), container_id.clone())?;
-    docker.run_and_get_status(msg_info, true)?;
+    if engine.kind.is_kubernetes() {
+        kubernetes::create_pod(engine, &container_id, &docker, msg_info)
+            .wrap_err("when creating the pod")?;
+    } else {
+        docker.run_and_get_status(msg_info, true)?;
+    }
 
     // 4. copy all mounted volumes over
     let data_volume = ContainerDataVolume::new(engine, &container_id, toolchain_dirs);
@@ -810,7 +1139,18 @@ pub(crate) fn run(
     let copy = |src, reldst: &str, info: &mut MessageInfo| {
         data_volume.copy_mount(src, reldst, mount_prefix, &volume, copy_cache, info)
     };
-    if let VolumeId::Discard = volume {
+    // persistent volume claims are populated by the first build, rather
+    // than with `cross-util volumes create`.
+    let populated = match volume {
+        VolumeId::Keep(_) if engine.kind.is_kubernetes() => data_volume.container_path_exists(
+            &toolchain_dirs.sysroot_mount_path_relative()?,
+            mount_prefix,
+            msg_info,
+        )?,
+        VolumeId::Keep(_) => true,
+        VolumeId::Discard => false,
+    };
+    if !populated {
         data_volume
             .copy_xargo(mount_prefix, msg_info)
             .wrap_err("when copying xargo")?;
@@ -895,12 +1235,35 @@ pub(crate) fn run(
         }
     }
 
-    let mut cmd = options.command_variant.safe_command();
+    let mount_target_dir = format!("{}/{}", package_dirs.mount_root(), target_dir);
+    let mut cmd = options.safe_command(&mount_target_dir, subcommand.as_ref());
+    let is_install = subcommand == Some(crate::Subcommand::Install);
 
     if msg_info.should_fail() {
         return Ok(None);
     }
 
+    // determine which artifacts to copy back to the host, if using cargo
+    // to report them, we need JSON messages from cargo.
+    let mut artifacts = options.config.remote_artifacts();
+    let mut forward_json = false;
+    if artifacts == Some(RemoteArtifacts::Cargo) {
+        let supported = !options.command_variant.is_shell()
+            && subcommand
+                .clone()
+                .map_or(false, |s| s.supports_message_format());
+        match message_format(args) {
+            _ if !supported => artifacts = None,
+            Some(format) if format.starts_with("json") => forward_json = true,
+            Some(format) => {
+                msg_info.warn(format_args!("cannot determine build artifacts with `--message-format={format}`, copying the entire target directory."))?;
+                artifacts = None;
+            }
+            None => {}
+        }
+    }
+    let capture_artifacts = artifacts == Some(RemoteArtifacts::Cargo);
+
     if !options.command_variant.is_shell() {
         // `clean` doesn't handle symlinks: it will just unlink the target
         // directory, so we should just substitute it our target directory
@@ -924,10 +1287,21 @@ pub(crate) fn run(
                 final_args.push(arg);
             }
         }
-        if !has_target_dir && subcommand.map_or(true, |s| s.needs_target_in_command()) {
+        if !has_target_dir
+            && subcommand
+                .clone()
+                .map_or(true, |s| s.needs_target_in_command())
+        {
             final_args.push("--target-dir".to_owned());
             final_args.push(target_dir.clone());
         }
+        if capture_artifacts && !forward_json {
+            let index = final_args
+                .iter()
+                .position(|a| a == "--")
+                .unwrap_or(final_args.len());
+            final_args.insert(index, "--message-format=json-render-diagnostics".to_owned());
+        }
 
         cmd.args(final_args);
     } else {
@@ -968,8 +1342,7 @@ symlink_recurse \"${{prefix}}\"
     for (src, dst) in to_symlink {
         symlink.push(format!("ln -s \"{src}\" \"{dst}\"",));
     }
-    subcommand_or_exit(engine, "exec")?
-        .arg(&container_id)
+    exec_or_exit(engine, &container_id, false)?
         .args(["sh", "-c", &symlink.join("\n")])
         .run_and_get_status(msg_info, false)
         .wrap_err("when creating symlinks to provide consistent host/mount paths")?;
@@ -977,7 +1350,10 @@ symlink_recurse \"${{prefix}}\"
     // 6. execute our cargo command inside the container
     let mut docker = engine.subcommand("exec");
     docker.add_user_id(engine.is_rootless);
-    docker.add_envvars(&options, toolchain_dirs, msg_info)?;
+    if !engine.kind.is_kubernetes() {
+        docker.add_envvars(&options, &paths, msg_info)?;
+    }
+    docker.add_runner_command(&options, &paths, subcommand)?;
     docker.add_cwd(&paths)?;
     docker.arg(&container_id);
     docker.add_build_command(toolchain_dirs, &cmd);
@@ -985,9 +1361,30 @@ symlink_recurse \"${{prefix}}\"
     if options.interactive {
         docker.arg("-i");
     }
+    if engine.kind.is_kubernetes() {
+        docker = kubernetes::exec_command(engine, &docker)?;
+    }
 
     bail_container_exited!();
-    let status = docker.run_and_get_status(msg_info, false);
+    let mut cargo_artifacts = vec![];
+    let status = if capture_artifacts {
+        let mut collector = TestCollector::new();
+        let collect_tests = !options.tests.reports.is_empty();
+        let status = run_and_collect_artifacts(
+            docker,
+            forward_json,
+            &paths.mount_member_manifests()?,
+            &mut cargo_artifacts,
+            collect_tests.then_some(&mut collector),
+            msg_info,
+        );
+        if collect_tests {
+            options.write_reports(&collector)?;
+        }
+        status
+    } else {
+        options.run_and_get_status(&mut docker, msg_info)
+    };
 
     // 7. copy data from our target dir back to host
     // this might not exist if we ran `clean`.
@@ -995,24 +1392,209 @@ symlink_recurse \"${{prefix}}\"
         .map(|s| bool_from_envvar(&s))
         .unwrap_or_default();
     bail_container_exited!();
-    let mount_target_dir = format!("{}/{}", package_dirs.mount_root(), target_dir);
+    let mut copied_target_dir = false;
     if !skip_artifacts
         && data_volume.container_path_exists(&mount_target_dir, mount_prefix, msg_info)?
     {
-        subcommand_or_exit(engine, "cp")?
-            .arg("-a")
-            .arg(&format!("{container_id}:{mount_target_dir}",))
-            .arg(
-                package_dirs
-                    .target()
-                    .parent()
-                    .expect("target directory should have a parent"),
-            )
-            .run_and_get_status(msg_info, false)
-            .map_err::<eyre::ErrReport, _>(Into::into)?;
+        let compression = archive::Compression::from_env()?;
+        let final_artifacts = env::var("CROSS_REMOTE_COPY_FINAL_ARTIFACTS")
+            .map(|s| bool_from_envvar(&s))
+            .unwrap_or_default();
+        // incremental and dependency files are left in the volume.
+        let files = match artifacts {
+            Some(RemoteArtifacts::Cargo) => {
+                let prefix = format!("{mount_target_dir}/");
+                let mut files: Vec<_> = cargo_artifacts
+                    .iter()
+                    .filter_map(|f| f.strip_prefix(&prefix))
+                    .map(ToOwned::to_owned)
+                    .collect();
+                files.sort();
+                files.dedup();
+                Some(files)
+            }
+            Some(RemoteArtifacts::Globs(globs)) => Some(find_artifact_globs(
+                engine,
+                &container_id,
+                &mount_target_dir,
+                &globs,
+                msg_info,
+            )?),
+            None => None,
+        };
+        let script = match &files {
+            Some(files) => Some(file_list_script(files)),
+            None if final_artifacts => Some(FINAL_ARTIFACTS_SCRIPT.to_owned()),
+            None => None,
+        };
+        copied_target_dir = script.is_none();
+        if files.map_or(true, |f| !f.is_empty()) {
+            let mut command = exec_or_exit(engine, &container_id, false)?;
+            command.args([
+                "sh",
+                "-c",
+                &compression.create_script(&mount_target_dir, script.as_deref()),
+            ]);
+            archive::receive(command, compression, package_dirs.target(), msg_info)
+                .wrap_err("when copying build artifacts")?;
+        }
+    }
+
+    // crashes are collected when the build fails, and are otherwise only
+    // copied with the entire target directory.
+    let crashed = status.as_ref().map_or(false, |s| !s.success());
+    let crash_dir = crash::crash_dir(&mount_target_dir, target);
+    if options.tests.collect_crashes
+        && crashed
+        && !copied_target_dir
+        && data_volume.container_path_exists(&crash_dir, mount_prefix, msg_info)?
+    {
+        let compression = archive::Compression::from_env()?;
+        let mut command = exec_or_exit(engine, &container_id, false)?;
+        command.args(["sh", "-c", &compression.create_script(&crash_dir, None)]);
+        archive::receive(
+            command,
+            compression,
+            &crash::host_crash_dir(package_dirs.target(), target),
+            msg_info,
+        )
+        .wrap_err("when copying crash artifacts")?;
+    }
+
+    // the installed binaries are copied to the host by `cross install`.
+    let install_dir = install::install_dir(&mount_target_dir, target);
+    if is_install
+        && status.as_ref().map_or(false, ExitStatus::success)
+        && !copied_target_dir
+        && data_volume.container_path_exists(&install_dir, mount_prefix, msg_info)?
+    {
+        let compression = archive::Compression::from_env()?;
+        let mut command = exec_or_exit(engine, &container_id, false)?;
+        command.args(["sh", "-c", &compression.create_script(&install_dir, None)]);
+        archive::receive(
+            command,
+            compression,
+            &install::host_install_dir(package_dirs.target(), target),
+            msg_info,
+        )
+        .wrap_err("when copying installed binaries")?;
     }
 
     ChildContainer::finish_static(is_tty, msg_info);
 
     status.map(Some)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, contents: &str) -> Result<()> {
+        file::create_dir_all(path.parent().expect("must have parent"))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint_difference() -> Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let home = tempdir.path();
+        write(&home.join("src/lib.rs"), "fn a() {}")?;
+        write(&home.join("src/main.rs"), "fn main() {}")?;
+        write(&home.join("README.md"), "# readme")?;
+
+        let previous = Fingerprint::read_dir(home, false, None, None)?;
+        let path = home.join("fingerprint");
+        previous.write_file(&path)?;
+        let previous = Fingerprint::read_file(&path)?;
+        fs::remove_file(&path)?;
+
+        // rewriting a file with the same contents should not copy it
+        write(&home.join("src/lib.rs"), "fn a() {}")?;
+        write(&home.join("src/main.rs"), "fn main() { a() }")?;
+        fs::remove_file(home.join("README.md"))?;
+        write(&home.join("src/b.rs"), "fn b() {}")?;
+
+        let current = Fingerprint::read_dir(home, false, None, Some(&previous))?;
+        let (to_copy, to_remove) = previous.difference(&current);
+        assert_eq!(to_copy, ["src/b.rs", "src/main.rs"]);
+        assert_eq!(to_remove, ["README.md"]);
+        assert_eq!(current.size_of(&to_copy), 26);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_cargo_messages() -> Result<()> {
+        let stdout = concat!(
+            r#"{"reason":"compiler-artifact","manifest_path":"/cargo/registry/src/bar/Cargo.toml","target":{"kind":["lib"]},"filenames":["/project/target/debug/deps/libbar.rlib","/project/target/debug/deps/libbar.rmeta"],"executable":null}"#,
+            "\n",
+            r#"{"reason":"compiler-artifact","manifest_path":"/project/Cargo.toml","target":{"kind":["custom-build"]},"filenames":["/project/target/debug/build/foo/build-script-build"],"executable":null}"#,
+            "\n",
+            r#"{"reason":"compiler-artifact","manifest_path":"/project/Cargo.toml","target":{"kind":["lib"]},"filenames":["/project/target/debug/libfoo.rlib"],"executable":null}"#,
+            "\n",
+            "Hello, world!\n",
+            r#"{"reason":"compiler-artifact","manifest_path":"/project/Cargo.toml","target":{"kind":["bin"]},"filenames":["/project/target/debug/foo"],"executable":"/project/target/debug/foo"}"#,
+            "\n",
+            r#"{"reason":"build-finished","success":true}"#,
+            "\n",
+        );
+        let members = ["/project/Cargo.toml".to_owned()];
+
+        let mut out = vec![];
+        let mut artifacts = vec![];
+        filter_cargo_messages(stdout.as_bytes(), &mut out, false, &members, &mut artifacts)?;
+        assert_eq!(out, b"Hello, world!\n");
+        assert_eq!(
+            artifacts,
+            [
+                "/project/target/debug/libfoo.rlib",
+                "/project/target/debug/foo",
+                "/project/target/debug/foo"
+            ]
+        );
+
+        let mut out = vec![];
+        filter_cargo_messages(stdout.as_bytes(), &mut out, true, &members, &mut vec![])?;
+        assert_eq!(out, stdout.as_bytes());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_message_format() {
+        let args = |args: &[&str]| args.iter().map(|&s| s.to_owned()).collect::<Vec<_>>();
+        assert_eq!(message_format(&args(&["build", "--release"])), None);
+        assert_eq!(
+            message_format(&args(&["build", "--message-format", "json"])),
+            Some("json")
+        );
+        assert_eq!(
+            message_format(&args(&["build", "--message-format=short"])),
+            Some("short")
+        );
+        assert_eq!(
+            message_format(&args(&["run", "--", "--message-format=short"])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_crossignore() -> Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let home = tempdir.path();
+        write(&home.join(".crossignore"), "/assets/\n*.log\n!keep.log\n")?;
+        write(&home.join("src/lib.rs"), "")?;
+        write(&home.join("assets/large.bin"), "")?;
+        write(&home.join("src/debug.log"), "")?;
+        write(&home.join("keep.log"), "")?;
+
+        let ignore = read_crossignore(home)?;
+        assert!(ignore.is_some());
+        let fingerprint = Fingerprint::read_dir(home, false, ignore.as_ref(), None)?;
+        let files: Vec<_> = fingerprint.map.keys().map(String::as_str).collect();
+        assert_eq!(files, [".crossignore", "keep.log", "src/lib.rs"]);
+
+        Ok(())
+    }
+}
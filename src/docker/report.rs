@@ -0,0 +1,623 @@
+//! Extract test reports from the output of `cross test`.
+//!
+//! The output of the test binaries is parsed as it's forwarded from the
+//! container to the host. With nightly toolchains, the test binaries
+//! use libtest's JSON output, which is rendered like the text output.
+//! Otherwise, the text output is parsed.
+
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+use crate::file;
+
+/// The line printed by the test wrapper before running a test binary.
+pub(crate) const BINARY_MARKER: &str = "cross-test-binary: ";
+/// The line printed by the test wrapper with the runner for the target.
+pub(crate) const RUNNER_MARKER: &str = "cross-test-runner: ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+/// A report requested with `--report format=path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for TestReport {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, path) = s
+            .split_once('=')
+            .filter(|(_, path)| !path.is_empty())
+            .ok_or_else(|| eyre::eyre!("invalid report `{s}`"))
+            .with_suggestion(|| {
+                "reports are of the form `format=path`, such as `junit=report.xml`"
+            })?;
+        let format = match format {
+            "junit" => ReportFormat::Junit,
+            "json" => ReportFormat::Json,
+            _ => {
+                return Err(eyre::eyre!("invalid report format `{format}`"))
+                    .with_suggestion(|| "valid report formats are `junit` and `json`")
+            }
+        };
+
+        Ok(TestReport {
+            format,
+            path: file::absolute_path(path)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestCase {
+    pub name: String,
+    pub status: TestStatus,
+    /// The execution time in seconds, only available with JSON output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+    /// The captured output of a failed test.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// The tests run by a single test binary, or partition of a test binary.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestSuite {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<f64>,
+    pub tests: Vec<TestCase>,
+}
+
+impl TestSuite {
+    fn count(&self, status: TestStatus) -> usize {
+        self.tests.iter().filter(|t| t.status == status).count()
+    }
+}
+
+/// An event from libtest's JSON output.
+#[derive(Debug, Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    name: Option<String>,
+    test_count: Option<u64>,
+    stdout: Option<String>,
+    message: Option<String>,
+    exec_time: Option<f64>,
+    passed: Option<u64>,
+    failed: Option<u64>,
+    ignored: Option<u64>,
+    measured: Option<u64>,
+    filtered_out: Option<u64>,
+}
+
+/// Collects the results of the tests from their output.
+#[derive(Debug, Default)]
+pub(crate) struct TestCollector {
+    suites: Vec<TestSuite>,
+    runner: Option<String>,
+    // the name of the test binary about to be run
+    binary: Option<String>,
+    // the suite currently running
+    running: bool,
+    // the failed test whose captured output is being read
+    output: Option<String>,
+}
+
+impl TestCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Process a line of output, without its line ending. Returns the
+    /// text to print instead of the line, if it shouldn't be forwarded.
+    pub fn process_line(&mut self, line: &str) -> Option<String> {
+        if let Some(binary) = line.strip_prefix(BINARY_MARKER) {
+            self.binary = Some(suite_name(binary).to_owned());
+            return Some(String::new());
+        } else if let Some(runner) = line.strip_prefix(RUNNER_MARKER) {
+            self.runner = Some(runner.to_owned()).filter(|r| !r.is_empty());
+            return Some(String::new());
+        }
+        if line.starts_with('{') {
+            if let Ok(event) = serde_json::from_str::<Event>(line) {
+                return Some(self.process_event(event));
+            }
+        }
+        self.process_text(line);
+        None
+    }
+
+    fn start_suite(&mut self) {
+        let name = self.binary.take().unwrap_or_else(|| "doc-tests".to_owned());
+        self.suites.push(TestSuite {
+            name,
+            time: None,
+            tests: vec![],
+        });
+        self.running = true;
+        self.output = None;
+    }
+
+    fn current(&mut self) -> Option<&mut TestSuite> {
+        match self.running {
+            true => self.suites.last_mut(),
+            false => None,
+        }
+    }
+
+    fn process_text(&mut self, line: &str) {
+        let count = line
+            .strip_prefix("running ")
+            .and_then(|s| s.strip_suffix(" tests").or_else(|| s.strip_suffix(" test")));
+        if count.map_or(false, |c| c.parse::<u64>().is_ok()) {
+            self.start_suite();
+            return;
+        }
+
+        let output = self.output.clone();
+        let suite = match self.current() {
+            Some(suite) => suite,
+            None => return,
+        };
+        if let Some(result) = line.strip_prefix("test result: ") {
+            suite.time = result
+                .rsplit_once("finished in ")
+                .and_then(|(_, time)| time.strip_suffix('s'))
+                .and_then(|time| time.parse().ok());
+            self.running = false;
+            self.output = None;
+        } else if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|s| s.strip_suffix(" stdout ----"))
+        {
+            self.output = Some(name.to_owned());
+        } else if matches!(line, "failures:" | "successes:") {
+            self.output = None;
+        } else if let Some(name) = output {
+            if let Some(test) = suite.tests.iter_mut().rev().find(|t| t.name == name) {
+                let output = test.output.get_or_insert_with(String::new);
+                output.push_str(line);
+                output.push('\n');
+            }
+        } else if let Some((name, result)) = line
+            .strip_prefix("test ")
+            .and_then(|s| s.rsplit_once(" ... "))
+        {
+            let status = match result {
+                "ok" => TestStatus::Passed,
+                "FAILED" => TestStatus::Failed,
+                r if r.starts_with("ignored") => TestStatus::Ignored,
+                _ => return,
+            };
+            suite.tests.push(TestCase {
+                name: name.to_owned(),
+                status,
+                time: None,
+                output: None,
+            });
+        }
+    }
+
+    // returns the event rendered like the text output.
+    fn process_event(&mut self, event: Event) -> String {
+        let mut text = String::new();
+        match (event.kind.as_str(), event.event.as_str()) {
+            ("suite", "started") => {
+                self.start_suite();
+                let count = event.test_count.unwrap_or_default();
+                let plural = if count == 1 { "" } else { "s" };
+                let _ = writeln!(text, "\nrunning {count} test{plural}");
+            }
+            ("suite", result) => {
+                let suite = match self.current() {
+                    Some(suite) => suite,
+                    None => return text,
+                };
+                suite.time = event.exec_time;
+                let failures: Vec<_> = suite
+                    .tests
+                    .iter()
+                    .filter(|t| t.status == TestStatus::Failed)
+                    .collect();
+                if !failures.is_empty() {
+                    text.push_str("\nfailures:\n\n");
+                    for test in &failures {
+                        let _ = writeln!(text, "---- {} stdout ----", test.name);
+                        text.push_str(test.output.as_deref().unwrap_or_default());
+                        text.push('\n');
+                    }
+                    text.push_str("\nfailures:\n");
+                    for test in &failures {
+                        let _ = writeln!(text, "    {}", test.name);
+                    }
+                }
+                let _ = write!(
+                    text,
+                    "\ntest result: {}. {} passed; {} failed; {} ignored; {} measured; {} filtered out",
+                    if result == "ok" { "ok" } else { "FAILED" },
+                    event.passed.unwrap_or_default(),
+                    event.failed.unwrap_or_default(),
+                    event.ignored.unwrap_or_default(),
+                    event.measured.unwrap_or_default(),
+                    event.filtered_out.unwrap_or_default(),
+                );
+                if let Some(time) = event.exec_time {
+                    let _ = write!(text, "; finished in {time:.2}s");
+                }
+                text.push_str("\n\n");
+                self.running = false;
+            }
+            ("test", "started") => {}
+            ("test", result) => {
+                let name = event.name.unwrap_or_default();
+                let status = match result {
+                    "ok" => TestStatus::Passed,
+                    "failed" => TestStatus::Failed,
+                    "ignored" => TestStatus::Ignored,
+                    _ => {
+                        let _ = writeln!(text, "test {name} ... {result}");
+                        return text;
+                    }
+                };
+                let _ = writeln!(
+                    text,
+                    "test {name} ... {}",
+                    match status {
+                        TestStatus::Passed => "ok",
+                        TestStatus::Failed => "FAILED",
+                        TestStatus::Ignored => "ignored",
+                    }
+                );
+                let output = match (event.stdout, event.message) {
+                    (Some(stdout), Some(message)) => Some(format!("{stdout}{message}\n")),
+                    (stdout, message) => stdout.or(message),
+                };
+                if let Some(suite) = self.current() {
+                    suite.tests.push(TestCase {
+                        name,
+                        status,
+                        time: event.exec_time,
+                        output: output.filter(|_| status == TestStatus::Failed),
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        text
+    }
+
+    /// Write the requested reports for the collected tests. Without a
+    /// configured runner, the runner used by the image is reported.
+    pub fn write_reports(
+        &self,
+        reports: &[TestReport],
+        target: &str,
+        runner: Option<&str>,
+    ) -> Result<()> {
+        let runner = runner.or(self.runner.as_deref()).unwrap_or("native");
+        for report in reports {
+            let contents = match report.format {
+                ReportFormat::Junit => self.junit(target, runner),
+                ReportFormat::Json => serde_json::to_string_pretty(&JsonReport {
+                    target,
+                    runner,
+                    suites: &self.suites,
+                })?,
+            };
+            write_report(&report.path, &contents)
+                .wrap_err_with(|| format!("when writing test report to {:?}", report.path))?;
+        }
+
+        Ok(())
+    }
+
+    fn junit(&self, target: &str, runner: &str) -> String {
+        let count = |status| -> usize { self.suites.iter().map(|s| s.count(status)).sum() };
+        let tests: usize = self.suites.iter().map(|s| s.tests.len()).sum();
+        let time: f64 = self.suites.iter().filter_map(|s| s.time).sum();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"cross test\" tests=\"{tests}\" failures=\"{}\" skipped=\"{}\" time=\"{time:.3}\">",
+            count(TestStatus::Failed),
+            count(TestStatus::Ignored),
+        );
+        for suite in &self.suites {
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+                xml_escape(&suite.name),
+                suite.tests.len(),
+                suite.count(TestStatus::Failed),
+                suite.count(TestStatus::Ignored),
+                suite.time.unwrap_or_default(),
+            );
+            xml.push_str("    <properties>\n");
+            for (name, value) in [("target", target), ("runner", runner)] {
+                let _ = writeln!(
+                    xml,
+                    "      <property name=\"{name}\" value=\"{}\"/>",
+                    xml_escape(value)
+                );
+            }
+            xml.push_str("    </properties>\n");
+            for test in &suite.tests {
+                let _ = write!(
+                    xml,
+                    "    <testcase name=\"{}\" classname=\"{}\"",
+                    xml_escape(&test.name),
+                    xml_escape(&suite.name),
+                );
+                if let Some(time) = test.time {
+                    let _ = write!(xml, " time=\"{time:.3}\"");
+                }
+                match test.status {
+                    TestStatus::Passed => xml.push_str("/>\n"),
+                    TestStatus::Ignored => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+                    TestStatus::Failed => {
+                        let _ = writeln!(
+                            xml,
+                            ">\n      <failure message=\"test failed\">{}</failure>\n    </testcase>",
+                            xml_escape(test.output.as_deref().unwrap_or_default())
+                        );
+                    }
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+
+        xml
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    target: &'a str,
+    runner: &'a str,
+    suites: &'a [TestSuite],
+}
+
+fn write_report(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        file::create_dir_all(parent)?;
+    }
+    file::write_file(path, true)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+// cargo appends a hash to the test binaries, like `it-1923fce0d18e18f3`.
+fn suite_name(binary: &str) -> &str {
+    let name = binary.rsplit('/').next().unwrap_or(binary);
+    match name.rsplit_once('-') {
+        Some((stem, hash)) if hash.len() == 16 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            stem
+        }
+        _ => name,
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // control characters, like color codes, are invalid in XML.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Forwards output to `writer`, line by line, while collecting the tests.
+pub(crate) struct ReportWriter<'a, W: Write> {
+    collector: &'a mut TestCollector,
+    writer: W,
+    line: Vec<u8>,
+}
+
+impl<'a, W: Write> ReportWriter<'a, W> {
+    pub fn new(collector: &'a mut TestCollector, writer: W) -> Self {
+        ReportWriter {
+            collector,
+            writer,
+            line: vec![],
+        }
+    }
+
+    fn process_line(&mut self) -> io::Result<()> {
+        let line = String::from_utf8_lossy(&self.line);
+        match self
+            .collector
+            .process_line(line.trim_end_matches(['\n', '\r']))
+        {
+            Some(text) => self.writer.write_all(text.as_bytes())?,
+            None => self.writer.write_all(&self.line)?,
+        }
+        self.line.clear();
+        Ok(())
+    }
+
+    /// Process any output without a trailing newline.
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.process_line()?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Write for ReportWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(index) = rest.iter().position(|&b| b == b'\n') {
+            self.line.extend_from_slice(&rest[..=index]);
+            self.process_line()?;
+            rest = &rest[index + 1..];
+        }
+        self.line.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(output: &str) -> (TestCollector, String) {
+        let mut collector = TestCollector::new();
+        let mut forwarded = vec![];
+        let mut writer = ReportWriter::new(&mut collector, &mut forwarded);
+        writer.write_all(output.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        (collector, String::from_utf8(forwarded).unwrap())
+    }
+
+    #[test]
+    fn test_text_output() {
+        let (collector, forwarded) = collect(
+            "cross-test-runner: qemu-aarch64
+cross-test-binary: /target/aarch64-unknown-linux-gnu/debug/deps/it-1923fce0d18e18f3
+
+running 3 tests
+test x ... ok
+test y ... FAILED
+test z ... ignored, slow
+
+failures:
+
+---- y stdout ----
+thread 'y' panicked at 'assertion failed', tests/it.rs:2:5
+
+failures:
+    y
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.25s
+",
+        );
+        assert!(!forwarded.contains("cross-test-"));
+        assert!(forwarded.contains("test y ... FAILED\n"));
+        assert_eq!(collector.runner.as_deref(), Some("qemu-aarch64"));
+        assert_eq!(
+            collector.suites,
+            [TestSuite {
+                name: "it".to_owned(),
+                time: Some(0.25),
+                tests: vec![
+                    TestCase {
+                        name: "x".to_owned(),
+                        status: TestStatus::Passed,
+                        time: None,
+                        output: None,
+                    },
+                    TestCase {
+                        name: "y".to_owned(),
+                        status: TestStatus::Failed,
+                        time: None,
+                        output: Some(
+                            "thread 'y' panicked at 'assertion failed', tests/it.rs:2:5\n\n"
+                                .to_owned()
+                        ),
+                    },
+                    TestCase {
+                        name: "z".to_owned(),
+                        status: TestStatus::Ignored,
+                        time: None,
+                        output: None,
+                    },
+                ],
+            }]
+        );
+
+        let junit = collector.junit("aarch64-unknown-linux-gnu", "qemu-user");
+        assert!(junit
+            .contains(r#"<testsuite name="it" tests="3" failures="1" skipped="1" time="0.250">"#));
+        assert!(junit.contains(r#"<property name="runner" value="qemu-user"/>"#));
+        assert!(junit.contains("panicked at &apos;assertion failed&apos;"));
+    }
+
+    #[test]
+    fn test_json_output() {
+        let (collector, forwarded) = collect(
+            r#"cross-test-binary: /target/debug/deps/lib-8eba86052325b706
+{ "type": "suite", "event": "started", "test_count": 2 }
+{ "type": "test", "event": "started", "name": "tests::a" }
+{ "type": "test", "name": "tests::a", "event": "ok", "exec_time": 0.5 }
+{ "type": "test", "event": "started", "name": "tests::b" }
+{ "type": "test", "name": "tests::b", "event": "failed", "exec_time": 0.25, "stdout": "oops\n" }
+{ "type": "suite", "event": "failed", "passed": 1, "failed": 1, "ignored": 0, "measured": 0, "filtered_out": 0, "exec_time": 0.75 }
+"#,
+        );
+        assert_eq!(
+            forwarded,
+            "
+running 2 tests
+test tests::a ... ok
+test tests::b ... FAILED
+
+failures:
+
+---- tests::b stdout ----
+oops
+
+
+failures:
+    tests::b
+
+test result: FAILED. 1 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.75s
+
+"
+        );
+        let suite = &collector.suites[0];
+        assert_eq!(suite.name, "lib");
+        assert_eq!(suite.time, Some(0.75));
+        assert_eq!(suite.tests[1].time, Some(0.25));
+        assert_eq!(suite.tests[1].output.as_deref(), Some("oops\n"));
+    }
+
+    #[test]
+    fn test_parse_report() -> Result<()> {
+        let report: TestReport = "junit=report.xml".parse()?;
+        assert_eq!(report.format, ReportFormat::Junit);
+        assert!(report.path.is_absolute());
+        assert!("xml=report.xml".parse::<TestReport>().is_err());
+        assert!("junit=".parse::<TestReport>().is_err());
+        assert!("junit".parse::<TestReport>().is_err());
+
+        Ok(())
+    }
+}
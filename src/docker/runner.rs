@@ -0,0 +1,250 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::*;
+
+/// The runners supported by the images provided by cross, selecting how
+/// binaries for the target are executed inside the container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunnerKind {
+    Native,
+    QemuUser,
+    QemuSystem,
+}
+
+impl RunnerKind {
+    pub const ALL: &'static [RunnerKind] = &[
+        RunnerKind::Native,
+        RunnerKind::QemuUser,
+        RunnerKind::QemuSystem,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunnerKind::Native => "native",
+            RunnerKind::QemuUser => "qemu-user",
+            RunnerKind::QemuSystem => "qemu-system",
+        }
+    }
+}
+
+impl fmt::Display for RunnerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RunnerKind {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RunnerKind::ALL
+            .iter()
+            .find(|kind| kind.as_str() == s)
+            .copied()
+            .ok_or_else(|| eyre::eyre!("invalid runner `{s}`"))
+            .with_suggestion(|| {
+                format!(
+                    "valid runners are {}",
+                    RunnerKind::ALL
+                        .iter()
+                        .map(|kind| format!("`{kind}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// The `target.{}.runner` configuration. If `kind` isn't provided, the
+/// image picks the runner, usually `native` or `qemu-user`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Runner {
+    pub kind: Option<RunnerKind>,
+    /// The CPU model to emulate, for `qemu-user` and `qemu-system`.
+    pub cpu: Option<String>,
+    /// The memory size of the virtual machine, for `qemu-system`.
+    pub memory: Option<String>,
+    /// The number of virtual CPUs, for `qemu-system`.
+    pub smp: Option<u32>,
+    /// Additional kernel command-line arguments, for `qemu-system`.
+    pub kernel_args: Option<Vec<String>>,
+    /// A wrapper command used to run binaries instead of the image's
+    /// runner, such as a script uploading and running them on a device.
+    pub command: Option<Vec<String>>,
+}
+
+impl From<RunnerKind> for Runner {
+    fn from(kind: RunnerKind) -> Self {
+        Runner {
+            kind: Some(kind),
+            ..Runner::default()
+        }
+    }
+}
+
+impl Runner {
+    /// Ensure the runner options are valid for the runner kind, and if
+    /// the image provided by cross is known, that it supports the runner.
+    /// An empty list of `supported` runners skips the latter check.
+    pub fn validate(&self, target: &str, supported: &[&str]) -> Result<()> {
+        if let Some(kind) = self.kind {
+            if !supported.is_empty() && !supported.contains(&kind.as_str()) {
+                return Err(eyre::eyre!(
+                    "runner `{kind}` is not supported by the image for target `{target}`"
+                ))
+                .with_suggestion(|| {
+                    format!(
+                        "the image supports {}",
+                        supported
+                            .iter()
+                            .map(|kind| format!("`{kind}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                });
+            }
+        }
+
+        let system_options = [
+            ("memory", self.memory.is_some()),
+            ("smp", self.smp.is_some()),
+            ("kernel-args", self.kernel_args.is_some()),
+        ];
+        if self.kind != Some(RunnerKind::QemuSystem) {
+            if let Some((option, _)) = system_options.iter().find(|(_, set)| *set) {
+                return Err(eyre::eyre!(
+                    "`target.{target}.runner.{option}` requires the `qemu-system` runner"
+                ))
+                .with_suggestion(|| {
+                    format!("set `target.{target}.runner.kind = \"qemu-system\"`")
+                });
+            }
+        }
+        if self.kind == Some(RunnerKind::Native) && self.cpu.is_some() {
+            eyre::bail!("`target.{target}.runner.cpu` cannot be used with the `native` runner");
+        }
+        if self.smp == Some(0) {
+            eyre::bail!("`target.{target}.runner.smp` must be at least 1");
+        }
+        if let Some(command) = &self.command {
+            if self.kind.is_some() || self.cpu.is_some() {
+                eyre::bail!(
+                    "`target.{target}.runner.command` cannot be combined with other runner options"
+                );
+            }
+            if command.is_empty() {
+                eyre::bail!("`target.{target}.runner.command` cannot be empty");
+            }
+            // cargo splits runners provided by environment variables on whitespace.
+            if let Some(arg) = command.iter().find(|arg| arg.contains(char::is_whitespace)) {
+                eyre::bail!(
+                    "`target.{target}.runner.command` cannot contain whitespace, got \"{arg}\""
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The wrapper command, if provided, and its arguments.
+    pub fn command(&self) -> Option<(&str, &[String])> {
+        self.command
+            .as_deref()
+            .and_then(|command| command.split_first())
+            .map(|(program, args)| (program.as_str(), args))
+    }
+
+    /// The environment variables passed to the runner in the container.
+    pub fn envvars(&self) -> Vec<(&'static str, String)> {
+        let mut envvars = vec![(
+            "CROSS_RUNNER",
+            self.kind.map(|k| k.to_string()).unwrap_or_default(),
+        )];
+        if let Some(cpu) = &self.cpu {
+            envvars.push(("CROSS_RUNNER_CPU", cpu.clone()));
+        }
+        if let Some(memory) = &self.memory {
+            envvars.push(("CROSS_RUNNER_MEMORY", memory.clone()));
+        }
+        if let Some(smp) = self.smp {
+            envvars.push(("CROSS_RUNNER_SMP", smp.to_string()));
+        }
+        if let Some(kernel_args) = &self.kernel_args {
+            envvars.push(("CROSS_RUNNER_KERNEL_ARGS", kernel_args.join(" ")));
+        }
+
+        envvars
+    }
+
+    /// Returns `true` if the environment variable is reserved for the runner.
+    pub fn is_reserved_envvar(key: &str) -> bool {
+        key == "CROSS_RUNNER" || key.starts_with("CROSS_RUNNER_")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        let runner = Runner {
+            kind: Some(RunnerKind::QemuSystem),
+            cpu: Some("cortex-a72".to_owned()),
+            memory: Some("2G".to_owned()),
+            smp: Some(4),
+            kernel_args: Some(vec!["nokaslr".to_owned(), "quiet".to_owned()]),
+            command: None,
+        };
+        runner.validate("aarch64-unknown-linux-gnu", &[])?;
+        runner.validate("aarch64-unknown-linux-gnu", &["native", "qemu-system"])?;
+        assert!(runner
+            .validate("aarch64-unknown-linux-gnu", &["native", "qemu-user"])
+            .is_err());
+        assert_eq!(
+            runner.envvars(),
+            [
+                ("CROSS_RUNNER", "qemu-system".to_owned()),
+                ("CROSS_RUNNER_CPU", "cortex-a72".to_owned()),
+                ("CROSS_RUNNER_MEMORY", "2G".to_owned()),
+                ("CROSS_RUNNER_SMP", "4".to_owned()),
+                ("CROSS_RUNNER_KERNEL_ARGS", "nokaslr quiet".to_owned()),
+            ]
+        );
+
+        let runner = Runner {
+            kind: Some(RunnerKind::QemuUser),
+            memory: Some("2G".to_owned()),
+            ..Runner::default()
+        };
+        assert!(runner.validate("aarch64-unknown-linux-gnu", &[]).is_err());
+
+        let mut runner = Runner {
+            command: Some(vec!["./run-on-board.sh".to_owned(), "--board".to_owned()]),
+            ..Runner::default()
+        };
+        runner.validate("aarch64-unknown-linux-gnu", &[])?;
+        assert_eq!(
+            runner.command(),
+            Some(("./run-on-board.sh", &["--board".to_owned()][..]))
+        );
+        runner.command = Some(vec!["./run on board.sh".to_owned()]);
+        assert!(runner.validate("aarch64-unknown-linux-gnu", &[]).is_err());
+        runner.command = Some(vec![]);
+        assert!(runner.validate("aarch64-unknown-linux-gnu", &[]).is_err());
+        assert_eq!(
+            Runner::default().envvars(),
+            [("CROSS_RUNNER", String::new())]
+        );
+
+        assert_eq!("qemu-user".parse::<RunnerKind>()?, RunnerKind::QemuUser);
+        assert!("qemu".parse::<RunnerKind>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+//! The sandbox of the container, selected with `build.sandbox`.
+//!
+//! The strict sandbox is meant to build untrusted code: the container runs
+//! without capabilities, network access or new privileges, and with the
+//! seccomp profile for every target. Since the network isn't available,
+//! the dependencies are fetched on the host beforehand, and the cargo home
+//! is mounted read-only.
+//!
+//! Builds with `--offline-container` share the network isolation and the
+//! read-only cargo home, without the other restrictions.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::EngineCommand;
+use crate::cargo::{cargo_command, CargoMetadata};
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::shell::MessageInfo;
+use crate::Target;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sandbox {
+    #[default]
+    Default,
+    Strict,
+}
+
+impl Sandbox {
+    #[must_use]
+    pub const fn is_strict(self) -> bool {
+        matches!(self, Self::Strict)
+    }
+}
+
+impl FromStr for Sandbox {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "default" => Sandbox::Default,
+            "strict" => Sandbox::Strict,
+            s => eyre::bail!("unknown sandbox `{s}`: expected `default` or `strict`"),
+        })
+    }
+}
+
+/// Fetch the dependencies of the workspace for `target` on the host, with
+/// the host's credentials and registries, since the container has no
+/// network access.
+pub(crate) fn fetch_dependencies(
+    metadata: &CargoMetadata,
+    target: &Target,
+    msg_info: &mut MessageInfo,
+) -> Result<()> {
+    let manifest_path = metadata.workspace_root.join("Cargo.toml");
+    if !manifest_path.exists() {
+        // not a package, such as with `cross install`.
+        return Ok(());
+    }
+
+    cargo_command()
+        .arg("fetch")
+        .arg("--manifest-path")
+        .arg(&manifest_path)
+        .args(["--target", target.triple()])
+        .run(msg_info, false)
+        .wrap_err("when fetching dependencies on the host")
+}
+
+/// Run the container without network access, with cargo in offline mode.
+pub(crate) fn add_offline_options(docker: &mut EngineCommand) {
+    docker
+        .args(["--network", "none"])
+        .args(["-e", "CARGO_NET_OFFLINE=true"]);
+}
+
+/// Add the options of the strict sandbox, other than the mounts and the
+/// seccomp profile.
+pub(crate) fn add_strict_options(docker: &mut EngineCommand) {
+    docker
+        .args(["--cap-drop", "ALL"])
+        .args(["--security-opt", "no-new-privileges"]);
+    add_offline_options(docker);
+}
+
+/// Explain a failed build without network access, which is most often
+/// caused by a crate that wasn't fetched on the host.
+pub fn note_offline_failure(target: &Target, msg_info: &mut MessageInfo) -> Result<()> {
+    msg_info.note(format_args!(
+        "the container had no network access: if a crate could not be found, \
+make sure `cargo fetch --target {target}` succeeds on the host, and that no build \
+script or tool downloads files at build time"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() -> Result<()> {
+        assert_eq!("strict".parse::<Sandbox>()?, Sandbox::Strict);
+        assert_eq!("default".parse::<Sandbox>()?, Sandbox::Default);
+        assert!("none".parse::<Sandbox>().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,676 @@
+//! Split the tests run by `cross test` into shards.
+//!
+//! Running tests under an emulated runner is slow, so the tests can be
+//! split across CI jobs with `--shard`, or run in parallel partitions
+//! with `--partition`. The tests are listed from the built test binaries
+//! inside the container: cargo runs every test binary through a wrapper
+//! runner, which lists its tests and only runs those in the shard. The
+//! wrapper also marks the output of each test binary for test reports.
+//! Each partition runs as a shard in its own container, and their results
+//! are merged here.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::process::{ExitStatus, Stdio};
+use std::thread;
+
+use serde::Deserialize;
+
+use super::report::{ReportWriter, TestCollector};
+use super::shared::{runner_envvar, TestOptions};
+use super::EngineCommand;
+use crate::errors::*;
+use crate::extensions::{CommandExt, SafeCommand};
+use crate::shell::MessageInfo;
+use crate::{CommandVariant, Target};
+
+/// The variable with the shard a partition runs, set by its engine invocation.
+pub(crate) const SHARD_INDEX_VAR: &str = "CROSS_TEST_INDEX";
+
+/// How the tests of `cross test` are split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestSplit {
+    /// Only run the tests in shard `index` of `count`, starting at 1.
+    Shard { index: u32, count: u32 },
+    /// Run all tests, split into `count` partitions running in parallel.
+    Partition(u32),
+}
+
+impl TestSplit {
+    /// Parse the `--shard i/n` argument.
+    pub fn parse_shard(value: &str) -> Result<Self> {
+        let parse = || -> Option<(u32, u32)> {
+            let (index, count) = value.split_once('/')?;
+            Some((index.parse().ok()?, count.parse().ok()?))
+        };
+        match parse() {
+            Some((index, count)) if index >= 1 && index <= count => {
+                Ok(TestSplit::Shard { index, count })
+            }
+            _ => Err(eyre::eyre!("invalid shard `{value}`"))
+                .with_suggestion(|| "shards are of the form `i/n`, where `1 <= i <= n`"),
+        }
+    }
+
+    /// Parse the `--partition n` argument.
+    pub fn parse_partition(value: &str) -> Result<Self> {
+        match value.parse() {
+            Ok(count) if count >= 1 => Ok(TestSplit::Partition(count)),
+            _ => Err(eyre::eyre!("invalid number of partitions `{value}`"))
+                .with_suggestion(|| "the number of partitions must be at least 1"),
+        }
+    }
+}
+
+/// The command running `variant` with the wrapper runner. The arguments
+/// to the cargo command are added to the returned command. Crashes and
+/// coverage are collected into `crash_dir` and `coverage_dir` in the
+/// container, if enabled.
+pub(crate) fn safe_command(
+    tests: &TestOptions,
+    target: &Target,
+    variant: CommandVariant,
+    crash_dir: &str,
+    coverage_dir: &str,
+) -> SafeCommand {
+    let (mode, index, count) = match tests.split {
+        Some(TestSplit::Shard { index, count }) => ("shard", index.to_string(), count),
+        Some(TestSplit::Partition(count)) => ("shard", String::new(), count),
+        None => ("all", "1".to_owned(), 1),
+    };
+    let report = match (tests.reports.is_empty(), tests.json) {
+        (true, _) => "none",
+        (false, false) => "text",
+        (false, true) => "json",
+    };
+    let mut cmd = SafeCommand::new("sh");
+    cmd.args(["-c", SHARD_SCRIPT, "cross-test", mode])
+        .arg(&index)
+        .arg(&count)
+        .arg(&runner_envvar(target))
+        .arg(&report)
+        .arg(&if tests.collect_crashes { crash_dir } else { "" })
+        .arg(&if tests.coverage { coverage_dir } else { "" })
+        .arg(&variant.to_str());
+    cmd
+}
+
+/// Run the tests in `count` partitions in parallel, each running a shard
+/// with its own engine invocation from `command`. The output of each
+/// partition is printed once all of them completed, followed by the merged
+/// results of all partitions. The status is that of the first failed
+/// partition, if any.
+pub(crate) fn run_partitions(
+    count: u32,
+    mut command: impl FnMut(u32) -> EngineCommand,
+    collector: Option<&mut TestCollector>,
+    msg_info: &mut MessageInfo,
+) -> Result<ExitStatus> {
+    let mut partitions = vec![];
+    for index in 1..=count {
+        let mut docker = command(index);
+        docker.debug(msg_info)?;
+        let mut child = docker
+            .stdout(Stdio::piped())
+            .spawn()
+            .wrap_err("when running cargo")?;
+        let mut stdout = child.stdout.take().expect("stdout should be piped");
+        // read the output as it's written, so no partition blocks on its pipe.
+        let reader = thread::spawn(move || -> io::Result<Vec<u8>> {
+            let mut output = vec![];
+            stdout.read_to_end(&mut output)?;
+            Ok(output)
+        });
+        partitions.push((child, reader));
+    }
+
+    let mut outputs = vec![];
+    let mut status = None;
+    for (mut child, reader) in partitions {
+        let output = reader
+            .join()
+            .expect("reading the output should not panic")?;
+        let partition_status = child.wait()?;
+        if status.map_or(true, |s: ExitStatus| s.success()) {
+            status = Some(partition_status);
+        }
+        outputs.push(output);
+    }
+    print_partitions(&outputs, collector, io::stdout())?;
+
+    Ok(status.expect("must have at least one partition"))
+}
+
+// prints the output of each partition, collecting the tests if requested,
+// followed by the merged results.
+fn print_partitions(
+    outputs: &[Vec<u8>],
+    mut collector: Option<&mut TestCollector>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut summary = TestSummary::default();
+    for (index, output) in (1..).zip(outputs) {
+        writeln!(writer, "partition {index}/{}:", outputs.len())?;
+        match collector.as_deref_mut() {
+            Some(collector) => {
+                let mut report_writer = ReportWriter::new(collector, &mut writer);
+                report_writer.write_all(output)?;
+                report_writer.finish()?;
+            }
+            None => writer.write_all(output)?,
+        }
+        for line in String::from_utf8_lossy(output).lines() {
+            summary.add_line(line);
+        }
+    }
+    let split = TestSplit::Partition(outputs.len() as u32);
+    if let Some(result) = summary.result(&format!("cross test result for {split}")) {
+        writeln!(writer, "{result}")?;
+    }
+    writer.flush()
+}
+
+/// The merged results of test binaries, from the summary printed by
+/// libtest after running each of them.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct TestSummary {
+    found: bool,
+    failed: bool,
+    counts: TestCounts,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+struct TestCounts {
+    passed: u64,
+    failed: u64,
+    ignored: u64,
+    measured: u64,
+}
+
+// the summary of a test binary with `--format json`.
+#[derive(Deserialize)]
+struct SuiteEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    #[serde(flatten)]
+    counts: TestCounts,
+}
+
+impl TestSummary {
+    /// Add the results from a line of output, if it's a summary.
+    fn add_line(&mut self, line: &str) {
+        let line = line.trim_end_matches('\r');
+        if let Some(rest) = line.strip_prefix("test result: ") {
+            let (result, counts) = rest.split_once(". ").unwrap_or((rest, ""));
+            self.failed |= result != "ok";
+            for part in counts.split(';') {
+                let (count, key): (u64, _) = match part.trim().split_once(' ') {
+                    Some((count, key)) => (count.parse().unwrap_or_default(), key),
+                    None => continue,
+                };
+                match key {
+                    "passed" => self.counts.passed += count,
+                    "failed" => self.counts.failed += count,
+                    "ignored" => self.counts.ignored += count,
+                    "measured" => self.counts.measured += count,
+                    _ => continue,
+                }
+            }
+            self.found = true;
+        } else if let Ok(suite) = serde_json::from_str::<SuiteEvent>(line) {
+            if suite.kind != "suite" || !matches!(suite.event.as_str(), "ok" | "failed") {
+                return;
+            }
+            self.failed |= suite.event == "failed";
+            self.counts.passed += suite.counts.passed;
+            self.counts.failed += suite.counts.failed;
+            self.counts.ignored += suite.counts.ignored;
+            self.counts.measured += suite.counts.measured;
+            self.found = true;
+        }
+    }
+
+    /// The merged results of the test binaries, labelled with `label`.
+    fn result(&self, label: &str) -> Option<String> {
+        let counts = &self.counts;
+        self.found.then(|| {
+            format!(
+                "{label}: {}. {} passed; {} failed; {} ignored; {} measured",
+                if self.failed { "FAILED" } else { "ok" },
+                counts.passed,
+                counts.failed,
+                counts.ignored,
+                counts.measured,
+            )
+        })
+    }
+}
+
+impl fmt::Display for TestSplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestSplit::Shard { index, count } => write!(f, "shard {index}/{count}"),
+            TestSplit::Partition(count) => write!(f, "{count} partitions"),
+        }
+    }
+}
+
+// runs the cargo command given as arguments, with every test binary run
+// through the wrapper runner, and merges the results of every binary.
+// arguments are the mode, the shard index, which is empty for partitions,
+// and count, the name of the cargo runner environment variable, the report
+// format, and the directories to collect crashes and coverage into, if any,
+// followed by the cargo command.
+// the markers must match those in `report`.
+const SHARD_SCRIPT: &str = r#"set -e
+mode="$1"
+# the shard of a partition is set by the engine invocation running it.
+index="${2:-$CROSS_TEST_INDEX}"
+count="$3"
+runner_var="$4"
+report="$5"
+crash_dir="$6"
+coverage_dir="$7"
+shift 7
+
+work=$(mktemp -d)
+trap 'rm -rf "$work"' EXIT
+
+cat > "$work/summary.awk" <<'EOF'
+/^test result:/ {
+    if ($3 != "ok.") failed = 1
+    for (i = 4; i < NF; i++) {
+        if ($i ~ /^[0-9]+$/) {
+            key = $(i + 1)
+            sub(/;$/, "", key)
+            counts[key] += $i
+        }
+    }
+    found = 1
+}
+# the summary of a test binary with `--format json`.
+/"type": *"suite"/ && /"event": *"(ok|failed)"/ {
+    if ($0 ~ /"event": *"failed"/) failed = 1
+    split("passed failed ignored measured", keys, " ")
+    for (k in keys) {
+        if (match($0, "\"" keys[k] "\": *[0-9]+")) {
+            value = substr($0, RSTART, RLENGTH)
+            sub(/.*: */, "", value)
+            counts[keys[k]] += value
+        }
+    }
+    found = 1
+}
+END {
+    if (found) {
+        printf "%s: %s. %d passed; %d failed; %d ignored; %d measured\n", prefix, failed ? "FAILED" : "ok", counts["passed"], counts["failed"], counts["ignored"], counts["measured"]
+    }
+}
+EOF
+
+cat > "$work/runner" <<'EOF'
+set -f
+bin="$1"
+shift
+
+run() {
+    # keep the instrumented binaries, to export the coverage.
+    case "${bin##*/}" in
+        rust_out*) ;;
+        *) [ -z "$CROSS_COVERAGE_DIR" ] || echo "$bin" >> "$CROSS_COVERAGE_DIR/binaries" ;;
+    esac
+    if [ -z "$CROSS_CRASH_DIR" ]; then
+        $CROSS_TEST_RUNNER "$bin" "$@"
+        return
+    fi
+
+    # qemu writes core dumps to the working directory, like the kernel
+    # usually does, and its logs to a file if requested.
+    logs=$(mktemp -d "$CROSS_TEST_WORK/crash.XXXXXX")
+    touch "$logs/start"
+    if [ -n "${QEMU_STRACE+x}${QEMU_LOG+x}" ]; then
+        QEMU_LOG_FILENAME="$logs/qemu-%d.log" $CROSS_TEST_RUNNER "$bin" "$@"
+    else
+        $CROSS_TEST_RUNNER "$bin" "$@"
+    fi
+    status=$?
+    if [ "$status" -gt 128 ]; then
+        dir="$CROSS_CRASH_DIR/${bin##*/}-$(date +%Y%m%d-%H%M%S)-$$"
+        mkdir -p "$dir"
+        find . -maxdepth 1 -type f -newer "$logs/start" \
+            \( -name 'qemu_*.core' -o -name core -o -name 'core.[0-9]*' \) \
+            -exec mv {} "$dir/" \;
+        find "$logs" -name 'qemu-*.log' -exec mv {} "$dir/" \;
+    fi
+    rm -rf "$logs"
+    return "$status"
+}
+
+# mark the output of the test binary for test reports.
+mark() {
+    [ "$CROSS_TEST_REPORT" = none ] || echo "cross-test-binary: $bin"
+}
+
+if [ "$CROSS_TEST_MODE" = all ] && [ -z "$CROSS_TEST_ARGS" ]; then
+    mark
+    run "$@"
+    exit $?
+fi
+
+# run a command, keeping its test results.
+record() {
+    out="$CROSS_TEST_WORK/out.$$"
+    { "$@"; echo $? > "$out.status"; } | tee "$out"
+    status=$(cat "$out.status")
+    grep -E "$CROSS_TEST_RESULT" "$out" >> "$CROSS_TEST_WORK/results" || true
+    rm -f "$out" "$out.status"
+    return "$status"
+}
+
+# the tests in a shard, assigned round-robin.
+select_tests() {
+    printf '%s\n' "$tests" | awk -v shard="$1" -v count="$CROSS_TEST_COUNT" \
+        'NF && (NR - 1) % count == shard - 1'
+}
+
+# doctests and test binaries without the libtest harness can't be listed,
+# so they run unsplit in the first shard. `--quiet` omits the footer.
+list=
+case "${bin##*/}" in
+    rust_out*) ;;
+    *) list=$(run --list "$@" 2>/dev/null) || list= ;;
+esac
+if [ -z "$list" ] || printf '%s\n' "$list" |
+    grep -Evq '^$|: (test|benchmark)$|^[0-9]+ tests?, [0-9]+ benchmarks?$'; then
+    if [ "$CROSS_TEST_INDEX" -le 1 ]; then
+        mark
+        record run "$@"
+        exit $?
+    fi
+    exit 0
+fi
+if [ "$CROSS_TEST_MODE" = all ]; then
+    mark
+    run "$@" $CROSS_TEST_ARGS
+    exit $?
+fi
+tests=$(printf '%s\n' "$list" | sed -n -e 's/: test$//p' -e 's/: benchmark$//p')
+
+# the filters given by the user were already applied when listing, and
+# would match more tests with `--exact`, so only keep the options.
+n=$#
+value=
+while [ "$n" -gt 0 ]; do
+    arg="$1"
+    shift
+    n=$((n - 1))
+    if [ -n "$value" ]; then
+        set -- "$@" "$arg"
+        value=
+        continue
+    fi
+    case "$arg" in
+        --skip | --test-threads | --logfile | --format | --color | --shuffle-seed | -Z)
+            set -- "$@" "$arg"
+            value=1
+            ;;
+        -*) set -- "$@" "$arg" ;;
+    esac
+done
+
+names=$(select_tests "$CROSS_TEST_INDEX")
+if [ -z "$names" ]; then
+    echo "no tests from ${bin##*/} in shard $CROSS_TEST_INDEX/$CROSS_TEST_COUNT"
+    exit 0
+fi
+mark
+record run "$@" $CROSS_TEST_ARGS --exact $names
+EOF
+
+CROSS_TEST_RUNNER=$(printenv "$runner_var" || true)
+CROSS_TEST_ARGS=
+if [ "$report" = json ]; then
+    CROSS_TEST_ARGS="-Zunstable-options --format json --report-time"
+fi
+if [ "$report" != none ]; then
+    echo "cross-test-runner: $CROSS_TEST_RUNNER"
+fi
+if [ -n "$crash_dir" ]; then
+    ulimit -c unlimited 2>/dev/null || ulimit -c "$(ulimit -H -c)" 2>/dev/null || true
+fi
+# the summary of each test binary, in the text or JSON output of libtest.
+CROSS_TEST_RESULT='^test result:|"type": *"suite"'
+export CROSS_TEST_RUNNER CROSS_TEST_WORK="$work" CROSS_TEST_REPORT="$report" CROSS_TEST_ARGS
+export CROSS_TEST_RESULT
+export CROSS_CRASH_DIR="$crash_dir" CROSS_COVERAGE_DIR="$coverage_dir"
+if [ -n "$coverage_dir" ]; then
+    rm -rf "$coverage_dir"
+    mkdir -p "$coverage_dir/profraw"
+    # instrument the crates of the workspace built for the target with a
+    # rustc wrapper, which keeps the rustflags of the environment and the
+    # cargo configuration, unlike setting `RUSTFLAGS`.
+    cat > "$work/rustc" <<'EOF'
+#!/bin/sh
+wrapper="$CROSS_RUSTC_WORKSPACE_WRAPPER"
+for arg; do
+    if [ "$arg" = --target ]; then
+        exec ${wrapper:+"$wrapper"} "$@" -C instrument-coverage
+    fi
+done
+exec ${wrapper:+"$wrapper"} "$@"
+EOF
+    chmod +x "$work/rustc"
+    export CROSS_RUSTC_WORKSPACE_WRAPPER="${RUSTC_WORKSPACE_WRAPPER:-}"
+    export RUSTC_WORKSPACE_WRAPPER="$work/rustc"
+    export LLVM_PROFILE_FILE="$coverage_dir/profraw/%p-%m.profraw"
+fi
+export CROSS_TEST_MODE="$mode" CROSS_TEST_INDEX="$index" CROSS_TEST_COUNT="$count"
+export "$runner_var=sh $work/runner"
+
+status=0
+"$@" || status=$?
+if [ "$mode" = shard ] && [ -f "$work/results" ]; then
+    awk -v prefix="cross test result for shard $index/$count" -f "$work/summary.awk" "$work/results"
+fi
+
+# merge the profiles with the `llvm-tools` of the toolchain, and export
+# them with the sources of the project, but not of dependencies.
+if [ -n "$coverage_dir" ] && [ -s "$coverage_dir/binaries" ]; then
+    host=$(rustc -vV | sed -n 's/^host: //p')
+    tools="$(rustc --print sysroot)/lib/rustlib/$host/bin"
+    find "$coverage_dir/profraw" -name '*.profraw' > "$work/profiles"
+    if [ ! -s "$work/profiles" ]; then
+        echo "warning: no coverage profiles were written by the test binaries." >&2
+    else
+        objects=
+        for object in $(sort -u "$coverage_dir/binaries"); do
+            [ -f "$object" ] && objects="$objects${objects:+ -object }$object"
+        done
+        "$tools/llvm-profdata" merge -sparse -f "$work/profiles" \
+            -o "$coverage_dir/coverage.profdata" || status=101
+        "$tools/llvm-cov" export -format=lcov \
+            -instr-profile="$coverage_dir/coverage.profdata" \
+            -ignore-filename-regex='/registry/src/|/git/checkouts/|/rustc/|/lib/rustlib/' \
+            $objects > "$coverage_dir/lcov.info" || status=101
+    fi
+fi
+exit "$status"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::report::{ReportFormat, TestReport, BINARY_MARKER, RUNNER_MARKER};
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(
+            TestSplit::parse_shard("2/3")?,
+            TestSplit::Shard { index: 2, count: 3 }
+        );
+        assert!(TestSplit::parse_shard("0/3").is_err());
+        assert!(TestSplit::parse_shard("4/3").is_err());
+        assert!(TestSplit::parse_shard("2").is_err());
+        assert_eq!(TestSplit::parse_partition("4")?, TestSplit::Partition(4));
+        assert!(TestSplit::parse_partition("0").is_err());
+
+        let target = Target::new_built_in("aarch64-unknown-linux-gnu");
+        let tests = TestOptions {
+            split: Some(TestSplit::Partition(4)),
+            ..TestOptions::default()
+        };
+        let cmd = safe_command(
+            &tests,
+            &target,
+            CommandVariant::Cargo,
+            "/target",
+            "/target/coverage",
+        );
+        let command = format!("{cmd:?}");
+        assert!(command.starts_with("sh -c "));
+        assert!(SHARD_SCRIPT.contains(&format!("echo \"{BINARY_MARKER}$bin\"")));
+        assert!(SHARD_SCRIPT.contains(&format!("echo \"{RUNNER_MARKER}$CROSS_TEST_RUNNER\"")));
+        assert!(command.ends_with(
+            " cross-test shard '' 4 CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_RUNNER none '' '' cargo"
+        ));
+        assert!(SHARD_SCRIPT.contains(&format!("index=\"${{2:-${SHARD_INDEX_VAR}}}\"")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut summary = TestSummary::default();
+        assert_eq!(summary.result("total"), None);
+        summary.add_line("running 3 tests");
+        summary.add_line(
+            "test result: ok. 2 passed; 0 failed; 1 ignored; 0 measured; 4 filtered out; finished in 0.01s",
+        );
+        assert_eq!(
+            summary.result("total").as_deref(),
+            Some("total: ok. 2 passed; 0 failed; 1 ignored; 0 measured")
+        );
+
+        summary.add_line(
+            "test result: FAILED. 1 passed; 2 failed; 0 ignored; 1 measured; 0 filtered out\r",
+        );
+        summary.add_line(r#"{ "type": "suite", "event": "started", "test_count": 3 }"#);
+        summary.add_line(
+            r#"{ "type": "suite", "event": "ok", "passed": 3, "failed": 0, "ignored": 0, "measured": 0, "filtered_out": 0, "exec_time": 0.1 }"#,
+        );
+        summary.add_line(r#"{ "type": "test", "event": "ok", "name": "foo" }"#);
+        assert_eq!(
+            summary.result("total").as_deref(),
+            Some("total: FAILED. 6 passed; 2 failed; 1 ignored; 1 measured")
+        );
+    }
+
+    #[test]
+    fn test_print_partitions() -> Result<()> {
+        let outputs = [
+            "cross-test-runner: qemu-aarch64\n\
+             cross-test-binary: /target/debug/deps/it-0123\n\
+             running 1 test\n\
+             test foo ... ok\n\
+             test result: ok. 1 passed; 0 failed; 0 ignored; 0 measured; 1 filtered out\n",
+            "cross-test-runner: qemu-aarch64\n\
+             cross-test-binary: /target/debug/deps/it-0123\n\
+             running 1 test\n\
+             test bar ... FAILED\n\
+             test result: FAILED. 0 passed; 1 failed; 0 ignored; 0 measured; 1 filtered out\n",
+        ]
+        .map(|output| output.as_bytes().to_vec());
+
+        let mut printed = vec![];
+        print_partitions(&outputs, None, &mut printed)?;
+        let printed = String::from_utf8(printed)?;
+        assert!(printed.starts_with("partition 1/2:\ncross-test-runner: "));
+        assert!(printed.contains("\npartition 2/2:\n"));
+        assert!(printed.ends_with(
+            "\ncross test result for 2 partitions: FAILED. 1 passed; 1 failed; 0 ignored; 0 measured\n"
+        ));
+
+        let mut collector = TestCollector::new();
+        print_partitions(&outputs, Some(&mut collector), io::sink())?;
+        let tempdir = tempfile::tempdir()?;
+        let path = tempdir.path().join("report.json");
+        let reports = [TestReport {
+            format: ReportFormat::Json,
+            path: path.clone(),
+        }];
+        collector.write_reports(&reports, "aarch64-unknown-linux-gnu", None)?;
+        let report = std::fs::read_to_string(path)?;
+        assert!(report.contains("\"foo\""));
+        assert!(report.contains("\"bar\""));
+
+        Ok(())
+    }
+
+    // a fake test binary echoing its arguments or its JSON results, run in shards.
+    #[test]
+    #[cfg(unix)]
+    fn test_shard_script() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use std::process::Command;
+
+        use crate::file::ToUtf8;
+
+        let tempdir = tempfile::tempdir()?;
+        let bin = tempdir.path().join("tests-0123");
+        fs::write(
+            &bin,
+            r#"#!/bin/sh
+case " $* " in
+    *" --list "*) printf 'foo: test\nfoo_bar: test\nfoo_baz: test\n' ;;
+    *" --format json "*)
+        echo '{ "type": "suite", "event": "started", "test_count": 2 }'
+        echo '{ "type": "suite", "event": "ok", "passed": 2, "failed": 0, "ignored": 1, "measured": 0, "filtered_out": 0 }'
+        ;;
+    *)
+        echo "args: $*"
+        echo "test result: ok. 2 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out"
+        ;;
+esac
+"#,
+        )?;
+        fs::set_permissions(&bin, fs::Permissions::from_mode(0o755))?;
+
+        let run = |index: &str, report: &str| -> Result<Vec<String>> {
+            let output = Command::new("sh")
+                .args(["-c", SHARD_SCRIPT, "cross-test", "shard", index, "2"])
+                .args(["TEST_RUNNER", report, "", ""])
+                .args(["sh", "-c", r#"$TEST_RUNNER "$0" foo --nocapture"#])
+                .arg(bin.to_utf8()?)
+                .env_remove("TEST_RUNNER")
+                .env(SHARD_INDEX_VAR, "2")
+                .output()?;
+            assert!(output.status.success());
+            Ok(String::from_utf8(output.stdout)?
+                .lines()
+                .map(ToOwned::to_owned)
+                .collect())
+        };
+        assert_eq!(
+            run("1", "none")?,
+            [
+                "args: --nocapture --exact foo foo_baz",
+                "test result: ok. 2 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out",
+                "cross test result for shard 1/2: ok. 2 passed; 0 failed; 0 ignored; 0 measured",
+            ]
+        );
+        assert_eq!(
+            run("1", "json")?.last().map(String::as_str),
+            Some("cross test result for shard 1/2: ok. 2 passed; 0 failed; 1 ignored; 0 measured")
+        );
+        // the shard of a partition is set by its engine invocation.
+        assert_eq!(
+            run("", "none")?.first().map(String::as_str),
+            Some("args: --nocapture --exact foo_bar")
+        );
+
+        Ok(())
+    }
+}
@@ -4,12 +4,24 @@ use std::process::{Command, ExitStatus, Output};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{env, fs, time};
 
+use super::coverage;
+use super::crash;
+use super::credentials;
 use super::custom::{Dockerfile, PreBuild};
+use super::debug::DebugOptions;
 use super::engine::*;
 use super::image::PossibleImage;
+use super::install;
+use super::kubernetes;
+use super::miri;
+use super::nerdctl;
+use super::report::{ReportWriter, TestCollector, TestReport};
+use super::runner::Runner;
+use super::sandbox::Sandbox;
+use super::shard::{self, TestSplit};
 use super::Image;
 use super::PROVIDED_IMAGES;
-use crate::cargo::CargoMetadata;
+use crate::cargo::{CargoMetadata, Subcommand};
 use crate::config::Config;
 use crate::errors::*;
 use crate::extensions::{CommandExt, SafeCommand};
@@ -17,6 +29,7 @@ use crate::file::{self, write_file, PathExt, ToUtf8};
 use crate::id;
 use crate::rustc::QualifiedToolchain;
 use crate::shell::{ColorChoice, MessageInfo, Verbosity};
+use crate::temp;
 use crate::{CommandVariant, OutputExt, Target, TargetTriple};
 
 use rustc_version::Version as RustcVersion;
@@ -37,9 +50,37 @@ pub struct DockerOptions {
     // not all toolchains will provide this
     pub rustc_version: Option<RustcVersion>,
     pub interactive: bool,
+    // reuse a long-running container for the target and project
+    pub keep_alive: bool,
+    // run without network access, with the dependencies fetched on the host
+    pub offline: bool,
+    pub tests: TestOptions,
+    // run the binary under a debug server, for `cross debug`
+    pub debug: Option<DebugOptions>,
+}
+
+/// The options for the tests run by `cross test`.
+#[derive(Debug, Clone, Default)]
+pub struct TestOptions {
+    pub split: Option<TestSplit>,
+    pub reports: Vec<TestReport>,
+    /// Run the tests with libtest's unstable JSON output.
+    pub json: bool,
+    /// Collect core dumps and `qemu` logs from crashed binaries.
+    pub collect_crashes: bool,
+    /// Collect the coverage of the tests.
+    pub coverage: bool,
+}
+
+impl TestOptions {
+    #[must_use]
+    pub fn needs_wrapper(&self) -> bool {
+        self.split.is_some() || !self.reports.is_empty() || self.collect_crashes || self.coverage
+    }
 }
 
 impl DockerOptions {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         engine: Engine,
         target: Target,
@@ -48,6 +89,10 @@ impl DockerOptions {
         cargo_variant: CommandVariant,
         rustc_version: Option<RustcVersion>,
         interactive: bool,
+        keep_alive: bool,
+        offline: bool,
+        tests: TestOptions,
+        debug: Option<DebugOptions>,
     ) -> DockerOptions {
         DockerOptions {
             engine,
@@ -57,6 +102,10 @@ impl DockerOptions {
             command_variant: cargo_variant,
             rustc_version,
             interactive,
+            keep_alive,
+            offline,
+            tests,
+            debug,
         }
     }
 
@@ -70,6 +119,120 @@ impl DockerOptions {
         self.engine.is_remote
     }
 
+    /// The command run inside the container, before its arguments.
+    /// `target_dir` is the target directory inside the container.
+    pub(crate) fn safe_command(
+        &self,
+        target_dir: &str,
+        subcommand: Option<&Subcommand>,
+    ) -> SafeCommand {
+        match subcommand {
+            Some(Subcommand::External(name, external)) => {
+                return super::subcommand::safe_command(name, external, self.command_variant);
+            }
+            Some(Subcommand::Miri) => {
+                return miri::safe_command(&self.target, self.command_variant);
+            }
+            Some(Subcommand::Install) => {
+                return install::safe_command(
+                    &install::install_dir(target_dir, &self.target),
+                    self.command_variant,
+                );
+            }
+            _ => {}
+        }
+        if let Some(debug) = &self.debug {
+            return debug.safe_command(&self.target, self.command_variant, target_dir);
+        }
+        match self.tests.needs_wrapper() {
+            true => shard::safe_command(
+                &self.tests,
+                &self.target,
+                self.command_variant,
+                &crash::crash_dir(target_dir, &self.target),
+                &coverage::coverage_dir(target_dir, &self.target),
+            ),
+            false => self.command_variant.safe_command(),
+        }
+    }
+
+    /// The name of the runner used for test reports, if configured.
+    pub(crate) fn runner_name(&self) -> Result<Option<String>> {
+        let runner = self.config.runner(&self.target)?.unwrap_or_default();
+        Ok(match (runner.command(), runner.kind) {
+            (Some((program, _)), _) => Some(program.to_owned()),
+            (None, Some(kind)) => Some(kind.to_string()),
+            (None, None) => None,
+        })
+    }
+
+    /// Run the command in the container, writing the test reports from
+    /// its output if any were requested.
+    pub(crate) fn run_and_get_status(
+        &self,
+        command: &mut EngineCommand,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        if self.tests.reports.is_empty() {
+            return command.run_and_get_status(msg_info, false);
+        }
+
+        command.debug(msg_info)?;
+        let mut child = command
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .wrap_err("when running cargo")?;
+        let mut stdout = child.stdout.take().expect("stdout should be piped");
+        let mut collector = TestCollector::new();
+        let mut writer = ReportWriter::new(&mut collector, std::io::stdout());
+        let result = std::io::copy(&mut stdout, &mut writer).and_then(|_| writer.finish());
+        let status = child.wait()?;
+        result?;
+        self.write_reports(&collector)?;
+
+        Ok(status)
+    }
+
+    /// The number of partitions the tests run in, if split with `--partition`.
+    #[must_use]
+    pub fn partitions(&self) -> Option<u32> {
+        match self.tests.split {
+            Some(TestSplit::Partition(count)) => Some(count),
+            _ => None,
+        }
+    }
+
+    /// Run the tests in `count` partitions, each with its own engine
+    /// invocation from `command`, and write the reports of all of them.
+    pub(crate) fn run_partitions(
+        &self,
+        count: u32,
+        command: impl FnMut(u32) -> EngineCommand,
+        msg_info: &mut MessageInfo,
+    ) -> Result<ExitStatus> {
+        let mut collector = TestCollector::new();
+        let collect_tests = !self.tests.reports.is_empty();
+        let status = shard::run_partitions(
+            count,
+            command,
+            collect_tests.then_some(&mut collector),
+            msg_info,
+        )?;
+        if collect_tests {
+            self.write_reports(&collector)?;
+        }
+
+        Ok(status)
+    }
+
+    pub(crate) fn write_reports(&self, collector: &TestCollector) -> Result<()> {
+        collector.write_reports(
+            &self.tests.reports,
+            self.target.triple(),
+            self.runner_name()?.as_deref(),
+        )
+    }
+
     #[must_use]
     pub fn needs_custom_image(&self) -> bool {
         self.config
@@ -116,11 +279,14 @@ impl DockerOptions {
                     self.config
                         .dockerfile_build_args(&self.target)?
                         .unwrap_or_default(),
+                    &self.config.dockerfile_secrets(&self.target),
                     msg_info,
                 )
                 .wrap_err("when building dockerfile")?;
         }
         let pre_build = self.config.pre_build(&self.target)?;
+        let pre_build_secrets = self.config.pre_build_secrets(&self.target);
+        let mounts = super::custom::secret_mounts(&pre_build_secrets);
 
         if let Some(pre_build) = pre_build {
             match pre_build {
@@ -140,7 +306,7 @@ impl DockerOptions {
                 ARG CROSS_TARGET
                 COPY $CROSS_SCRIPT /pre-build-script
                 RUN chmod +x /pre-build-script
-                RUN ./pre-build-script $CROSS_TARGET"#
+                RUN{mounts} ./pre-build-script $CROSS_TARGET"#
                         ),
                         runs_with: &image.platform,
                     };
@@ -153,6 +319,7 @@ impl DockerOptions {
                                 ("CROSS_SCRIPT", &*pre_build_script),
                                 ("CROSS_TARGET", self.target.triple()),
                             ],
+                            &pre_build_secrets,
                             msg_info,
                         )
                         .wrap_err("when pre-building")
@@ -171,7 +338,7 @@ impl DockerOptions {
                 FROM {image}
                 ARG CROSS_DEB_ARCH=
                 ARG CROSS_CMD
-                RUN eval "${{CROSS_CMD}}""#
+                RUN{mounts} eval "${{CROSS_CMD}}""#
                             ),
                             runs_with: &image.platform,
                         };
@@ -180,6 +347,7 @@ impl DockerOptions {
                                 self,
                                 paths,
                                 Some(("CROSS_CMD", pre_build.join("\n"))),
+                                &pre_build_secrets,
                                 msg_info,
                             )
                             .wrap_err("when pre-building")
@@ -202,13 +370,11 @@ pub struct DockerPaths {
 
 impl DockerPaths {
     pub fn create(
-        engine: &Engine,
+        mount_finder: MountFinder,
         metadata: CargoMetadata,
         cwd: PathBuf,
         toolchain: QualifiedToolchain,
-        msg_info: &mut MessageInfo,
     ) -> Result<Self> {
-        let mount_finder = MountFinder::create(engine, msg_info)?;
         let (directories, metadata) =
             Directories::assemble(&mount_finder, metadata, &cwd, toolchain)?;
         Ok(Self {
@@ -249,6 +415,16 @@ impl DockerPaths {
     pub fn host_root(&self) -> &Path {
         self.directories.package_directories().host_root()
     }
+
+    /// The manifests of the workspace members, as seen in the container.
+    pub fn mount_member_manifests(&self) -> Result<Vec<String>> {
+        self.metadata
+            .packages
+            .iter()
+            .filter(|p| self.metadata.workspace_members.contains(&p.id))
+            .map(|p| self.mount_finder.find_path(&p.manifest_path, false))
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -335,6 +511,14 @@ impl ToolchainDirectories {
         self.toolchain.unique_container_identifier(triple)
     }
 
+    pub fn unique_session_identifier(
+        &self,
+        triple: &TargetTriple,
+        project: &Path,
+    ) -> Result<String> {
+        self.toolchain.unique_session_identifier(triple, project)
+    }
+
     pub fn toolchain(&self) -> &QualifiedToolchain {
         &self.toolchain
     }
@@ -707,20 +891,40 @@ impl<'a, 'b> DockerVolume<'a, 'b> {
         Self { engine, name }
     }
 
+    /// Volumes are subdirectories of the persistent volume claim with
+    /// Kubernetes, which aren't managed by cross.
+    pub fn check_engine(engine: &Engine) -> Result<()> {
+        if engine.kind.is_kubernetes() {
+            return Err(eyre::eyre!("volumes are not supported with Kubernetes")).with_suggestion(
+                || {
+                    format!(
+                        "manage the toolchains in the persistent volume claim of `{}` instead",
+                        kubernetes::CROSS_KUBERNETES_PVC
+                    )
+                },
+            );
+        }
+
+        Ok(())
+    }
+
     #[track_caller]
     pub fn create(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        Self::check_engine(self.engine)?;
         self.engine
             .run_and_get_status(&["volume", "create", self.name], msg_info)
     }
 
     #[track_caller]
     pub fn remove(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        Self::check_engine(self.engine)?;
         self.engine
             .run_and_get_status(&["volume", "rm", self.name], msg_info)
     }
 
     #[track_caller]
     pub fn exists(&self, msg_info: &mut MessageInfo) -> Result<bool> {
+        Self::check_engine(self.engine)?;
         self.engine
             .run_and_get_output(&["volume", "inspect", self.name], msg_info)
             .map(|output| output.status.success())
@@ -732,6 +936,7 @@ impl<'a, 'b> DockerVolume<'a, 'b> {
         toolchain: &QualifiedToolchain,
         msg_info: &mut MessageInfo,
     ) -> Result<Vec<String>> {
+        Self::check_engine(engine)?;
         let list = engine
             .run_and_get_output(
                 &[
@@ -766,6 +971,9 @@ impl<'a, 'b> DockerContainer<'a, 'b> {
     }
 
     pub fn stop(&self, timeout: u32, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        if self.engine.kind.is_kubernetes() {
+            return kubernetes::delete_pod(self.engine, self.name, timeout, msg_info);
+        }
         self.engine.run_and_get_status(
             &["stop", self.name, "--time", &timeout.to_string()],
             msg_info,
@@ -784,12 +992,19 @@ impl<'a, 'b> DockerContainer<'a, 'b> {
     /// the container was killed, we need to cleanup the exited container.
     /// just silence any warnings.
     pub fn remove(&self, msg_info: &mut MessageInfo) -> Result<ExitStatus> {
+        if self.engine.kind.is_kubernetes() {
+            // pods are removed when they are stopped.
+            return kubernetes::delete_pod(self.engine, self.name, DEFAULT_TIMEOUT, msg_info);
+        }
         self.engine
             .run_and_get_output(&["rm", self.name], msg_info)
             .map(|output| output.status)
     }
 
     pub fn state(&self, msg_info: &mut MessageInfo) -> Result<ContainerState> {
+        if self.engine.kind.is_kubernetes() {
+            return kubernetes::pod_state(self.engine, self.name, msg_info);
+        }
         let stdout = self
             .engine
             .command()
@@ -799,6 +1014,79 @@ impl<'a, 'b> DockerContainer<'a, 'b> {
             .run_and_get_stdout(msg_info)?;
         ContainerState::new(stdout.trim())
     }
+
+    /// get the value of a label on the container, if the label is present.
+    pub fn label(&self, key: &str, msg_info: &mut MessageInfo) -> Result<Option<String>> {
+        let stdout = self
+            .engine
+            .subcommand("inspect")
+            .args([
+                "--format",
+                &format!("{{{{ index .Config.Labels \"{key}\" }}}}"),
+            ])
+            .arg(self.name)
+            .run_and_get_stdout(msg_info)?;
+        // missing labels are formatted as `<no value>` by docker and podman
+        Ok(Some(stdout.trim())
+            .filter(|v| !v.is_empty() && *v != "<no value>")
+            .map(ToOwned::to_owned))
+    }
+}
+
+/// The label marking a persistent session container. The value is a hash
+/// of the container configuration, so stale sessions can be recreated.
+pub fn session_label() -> String {
+    format!("{}.session", crate::CROSS_LABEL_DOMAIN)
+}
+
+// get the hash of the `run` command used to create a session container.
+// environment variables are provided on every `exec`, so they are ignored.
+pub(crate) fn session_hash(docker: &Command) -> Result<String> {
+    let mut args = vec![];
+    let mut iter = docker.get_args();
+    while let Some(arg) = iter.next() {
+        if arg == "-e" {
+            iter.next();
+        } else {
+            args.push(arg.to_utf8()?);
+        }
+    }
+    Ok(sha1_smol::Sha1::from(args.join("\0"))
+        .digest()
+        .to_string()
+        .get(..PATH_HASH_UNIQUE)
+        .expect("sha1 is expected to be at least 10 characters long")
+        .to_owned())
+}
+
+fn session_record(name: &str) -> Result<PathBuf> {
+    Ok(temp::dir()?.join("sessions").join(name))
+}
+
+/// Record that a session container was just used.
+pub fn touch_session(name: &str) -> Result<()> {
+    let path = session_record(name)?;
+    write_file(&path, true)?.write_all(now_as_millis()?.to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Get the last time a session container was used, if known.
+pub fn session_last_used(name: &str) -> Result<Option<time::SystemTime>> {
+    let path = session_record(name)?;
+    if path.exists() {
+        let millis = file::read(&path)?.trim().parse::<u64>()?;
+        Ok(Some(time_from_millis(millis)))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn remove_session_record(name: &str) -> Result<()> {
+    let path = session_record(name)?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
 }
 
 pub(crate) fn time_to_millis(timestamp: &time::SystemTime) -> Result<u64> {
@@ -834,8 +1122,19 @@ pub fn create_target_dir(path: &Path) -> Result<()> {
 }
 
 impl Engine {
-    pub fn command(&self) -> Command {
-        let mut command = Command::new(&self.path);
+    pub fn command(&self) -> EngineCommand {
+        let mut command = match &self.ssh {
+            Some(ssh) => ssh.command(&self.path),
+            None => EngineCommand::new(&self.path),
+        };
+        let namespace = match self.kind {
+            EngineType::Nerdctl => nerdctl::namespace(),
+            EngineType::Kubernetes => kubernetes::namespace(),
+            _ => None,
+        };
+        if let Some(namespace) = namespace {
+            command.args(["--namespace", &namespace]);
+        }
         if self.needs_remote() {
             // if we're using podman and not podman-remote, need `--remote`.
             command.arg("--remote");
@@ -843,7 +1142,7 @@ impl Engine {
         command
     }
 
-    pub fn subcommand(&self, cmd: &str) -> Command {
+    pub fn subcommand(&self, cmd: &str) -> EngineCommand {
         let mut command = self.command();
         command.arg(cmd);
         command
@@ -898,6 +1197,20 @@ impl Engine {
     }
 }
 
+// the cgroup version of the engine host, as `1` or `2`. podman formats
+// the information of the host differently.
+fn cgroup_version(engine: &Engine, msg_info: &mut MessageInfo) -> Option<String> {
+    let format = match engine.kind.is_podman() {
+        true => "{{.Host.CgroupsVersion}}",
+        false => "{{.CgroupVersion}}",
+    };
+    engine
+        .run_and_get_output(&["info", "-f", format], msg_info)
+        .ok()
+        .and_then(|output| output.stdout().ok())
+        .map(|version| version.trim().trim_start_matches('v').to_owned())
+}
+
 fn validate_env_var<'a>(
     var: &'a str,
     warned: &mut bool,
@@ -922,10 +1235,8 @@ fn validate_env_var<'a>(
         *warned = true;
     }
 
-    if key == "CROSS_RUNNER" {
-        eyre::bail!(
-            "CROSS_RUNNER environment variable name is reserved and cannot be pass through"
-        );
+    if Runner::is_reserved_envvar(key) {
+        eyre::bail!("{key} environment variable name is reserved and cannot be pass through");
     }
 
     Ok((key, value))
@@ -937,35 +1248,52 @@ impl CommandVariant {
     }
 }
 
+/// The environment variable cargo reads the runner for the target from.
+pub(crate) fn runner_envvar(target: &Target) -> String {
+    format!(
+        "CARGO_TARGET_{}_RUNNER",
+        target.triple().to_uppercase().replace(['-', '.'], "_")
+    )
+}
+
 pub(crate) trait DockerCommandExt {
     fn add_configuration_envvars(&mut self);
     fn add_envvars(
         &mut self,
         options: &DockerOptions,
-        dirs: &ToolchainDirectories,
+        paths: &DockerPaths,
         msg_info: &mut MessageInfo,
     ) -> Result<()>;
     fn add_cwd(&mut self, paths: &DockerPaths) -> Result<()>;
+    fn add_runner_command(
+        &mut self,
+        options: &DockerOptions,
+        paths: &DockerPaths,
+        subcommand: Option<Subcommand>,
+    ) -> Result<()>;
     fn add_build_command(&mut self, dirs: &ToolchainDirectories, cmd: &SafeCommand) -> &mut Self;
     fn add_user_id(&mut self, is_rootless: bool);
     fn add_userns(&mut self);
     fn add_seccomp(
         &mut self,
-        engine_type: EngineType,
+        engine: &Engine,
         target: &Target,
         metadata: &CargoMetadata,
+        sandbox: Sandbox,
+        msg_info: &mut MessageInfo,
     ) -> Result<()>;
+    fn add_resources(&mut self, options: &DockerOptions, msg_info: &mut MessageInfo) -> Result<()>;
     fn add_mounts(
         &mut self,
         options: &DockerOptions,
         paths: &DockerPaths,
-        mount_cb: impl Fn(&mut Command, &Path, &Path) -> Result<()>,
+        mount_cb: impl Fn(&mut EngineCommand, &Path, &Path) -> Result<()>,
         store_cb: impl FnMut((String, String)),
         msg_info: &mut MessageInfo,
     ) -> Result<()>;
 }
 
-impl DockerCommandExt for Command {
+impl DockerCommandExt for EngineCommand {
     fn add_configuration_envvars(&mut self) {
         let other = &[
             "http_proxy",
@@ -977,6 +1305,7 @@ impl DockerCommandExt for Command {
             "HTTP_TIMEOUT",
             "https_proxy",
             "QEMU_STRACE",
+            "MIRIFLAGS",
         ];
         let cargo_prefix_skip = &[
             "CARGO_HOME",
@@ -988,7 +1317,6 @@ impl DockerCommandExt for Command {
             "CARGO_BUILD_RUSTDOC",
         ];
         let cross_prefix_skip = &[
-            "CROSS_RUNNER",
             "CROSS_RUSTC_MAJOR_VERSION",
             "CROSS_RUSTC_MINOR_VERSION",
             "CROSS_RUSTC_PATCH_VERSION",
@@ -996,14 +1324,16 @@ impl DockerCommandExt for Command {
         let is_passthrough = |key: &str| -> bool {
             other.contains(&key)
                 || key.starts_with("CARGO_") && !cargo_prefix_skip.contains(&key)
-                || key.starts_with("CROSS_") && !cross_prefix_skip.contains(&key)
+                || key.starts_with("CROSS_")
+                    && !cross_prefix_skip.contains(&key)
+                    && !Runner::is_reserved_envvar(key)
         };
 
         // also need to accept any additional flags used to configure
         // cargo or cross, but only pass what's actually present.
         for (key, _) in env::vars() {
             if is_passthrough(&key) {
-                self.args(["-e", &key]);
+                self.pass_env(&key);
             }
         }
     }
@@ -1011,9 +1341,10 @@ impl DockerCommandExt for Command {
     fn add_envvars(
         &mut self,
         options: &DockerOptions,
-        dirs: &ToolchainDirectories,
+        paths: &DockerPaths,
         msg_info: &mut MessageInfo,
     ) -> Result<()> {
+        let dirs = paths.directories.toolchain_directories();
         let mut warned = false;
         for ref var in options
             .config
@@ -1030,24 +1361,30 @@ impl DockerCommandExt for Command {
 
             // Only specifying the environment variable name in the "-e"
             // flag forwards the value from the parent shell
-            self.args(["-e", var]);
+            self.pass_env(var);
         }
 
-        let runner = options.config.runner(&options.target)?;
-        let cross_runner = format!("CROSS_RUNNER={}", runner.unwrap_or_default());
+        let runner = options.config.runner(&options.target)?.unwrap_or_default();
+        runner.validate(options.target.triple(), &[])?;
+        for (key, value) in runner.envvars() {
+            self.args(["-e", &format!("{key}={value}")]);
+        }
         self.args(["-e", &format!("XARGO_HOME={}", dirs.xargo_mount_path())])
             .args(["-e", &format!("CARGO_HOME={}", dirs.cargo_mount_path())])
             .args([
                 "-e",
                 &format!("CROSS_RUST_SYSROOT={}", dirs.sysroot_mount_path()),
             ])
-            .args(["-e", "CARGO_TARGET_DIR=/target"])
-            .args(["-e", &cross_runner]);
+            .args(["-e", "CARGO_TARGET_DIR=/target"]);
         if options.command_variant.uses_zig() {
             // otherwise, zig has a permission error trying to create the cache
             self.args(["-e", "XDG_CACHE_HOME=/target/.zig-cache"]);
         }
         self.add_configuration_envvars();
+        if options.config.forward_credentials() {
+            credentials::add_registry_tokens(self, dirs.cargo(), &paths.metadata, msg_info)
+                .wrap_err("when forwarding registry tokens")?;
+        }
 
         if let Some(username) = id::username().wrap_err("could not get username")? {
             self.args(["-e", &format!("USER={username}")]);
@@ -1084,6 +1421,55 @@ impl DockerCommandExt for Command {
         Ok(())
     }
 
+    fn add_runner_command(
+        &mut self,
+        options: &DockerOptions,
+        paths: &DockerPaths,
+        subcommand: Option<Subcommand>,
+    ) -> Result<()> {
+        if !subcommand.map_or(false, Subcommand::needs_interpreter) {
+            return Ok(());
+        }
+        let runner = options.config.runner(&options.target)?;
+        let (program, args) = match runner.as_ref().and_then(Runner::command) {
+            Some(command) => command,
+            None => return Ok(()),
+        };
+
+        // relative paths are relative to the workspace root, and must be
+        // inside the mounted project. otherwise, the program is looked up
+        // in the image's `PATH`.
+        let program = if program.contains('/') && !Path::new(program).is_absolute() {
+            let host_path = file::canonicalize(paths.workspace_root().join(program))
+                .wrap_err("when finding the runner command")?;
+            let package_dirs = paths.directories.package_directories();
+            match paths.mount_finder.find_project_path(
+                &host_path,
+                package_dirs.host_root(),
+                package_dirs.mount_root(),
+            )? {
+                Some(path) => path,
+                None => eyre::bail!(
+                    "runner command `{program}` must be inside the project root {:?}",
+                    paths.host_root()
+                ),
+            }
+        } else {
+            program.to_owned()
+        };
+
+        let mut command = vec![program.as_str()];
+        command.extend(args.iter().map(String::as_str));
+        // the runner overrides the one configured by the image, and cargo
+        // propagates its exit status.
+        self.args([
+            "-e",
+            &format!("{}={}", runner_envvar(&options.target), command.join(" ")),
+        ]);
+
+        Ok(())
+    }
+
     fn add_build_command(&mut self, dirs: &ToolchainDirectories, cmd: &SafeCommand) -> &mut Self {
         let build_command = format!(
             "PATH=\"$PATH\":\"{}/bin\" {:?}",
@@ -1116,9 +1502,11 @@ impl DockerCommandExt for Command {
     #[allow(unused_mut, clippy::let_and_return)]
     fn add_seccomp(
         &mut self,
-        engine_type: EngineType,
+        engine: &Engine,
         target: &Target,
         metadata: &CargoMetadata,
+        sandbox: Sandbox,
+        msg_info: &mut MessageInfo,
     ) -> Result<()> {
         // secured profile based off the docker documentation for denied syscalls:
         // https://docs.docker.com/engine/security/seccomp/#significant-syscalls-blocked-by-the-default-profile
@@ -1127,8 +1515,16 @@ impl DockerCommandExt for Command {
         const SECCOMP: &str = include_str!("seccomp.json");
 
         // docker uses seccomp now on all installations
-        if target.needs_docker_seccomp() {
-            let seccomp = if engine_type.is_docker() && cfg!(target_os = "windows") {
+        let engine_type = engine.kind;
+        // the strict sandbox uses the profile for every target.
+        if target.needs_docker_seccomp() || sandbox.is_strict() {
+            let seccomp = if let Some(ssh) = &engine.ssh {
+                // the profile must be readable by the engine on the build host.
+                let hash = sha1_smol::Sha1::from(SECCOMP).digest().to_string();
+                let path = format!("/tmp/cross-seccomp-{}.json", &hash[..PATH_HASH_SHORT]);
+                ssh.upload(&path, SECCOMP.as_bytes(), msg_info)?;
+                path
+            } else if engine_type.is_docker() && cfg!(target_os = "windows") {
                 // docker on windows fails due to a bug in reading the profile
                 // https://github.com/docker/for-win/issues/12760
                 "unconfined".to_owned()
@@ -1156,11 +1552,56 @@ impl DockerCommandExt for Command {
         Ok(())
     }
 
+    fn add_resources(&mut self, options: &DockerOptions, msg_info: &mut MessageInfo) -> Result<()> {
+        let resources = options.config.resources(&options.target);
+        if resources.is_empty() {
+            return Ok(());
+        }
+        let engine = &options.engine;
+        if engine.kind.is_oci_runtime() {
+            return msg_info
+                .warn("resource limits are not supported with OCI runtimes, ignoring them.");
+        }
+        // rootless engines can only limit resources with cgroup v2: podman
+        // ignores the limits otherwise, while docker and nerdctl fail.
+        if engine.is_rootless
+            && !engine.kind.is_kubernetes()
+            && cgroup_version(engine, msg_info).as_deref() == Some("1")
+        {
+            return msg_info.warn(format_args!(
+                "resource limits need cgroup v2 with a rootless container engine, ignoring them."
+            ));
+        }
+
+        if let Some(cpus) = &resources.cpus {
+            if !cpus.parse::<f64>().map_or(false, |c| c > 0.0) {
+                eyre::bail!("invalid `resources.cpus` of `{cpus}`, must be a positive number");
+            }
+            self.args(["--cpus", cpus]);
+        }
+        // the sizes are validated by the engine, in its own format.
+        if let Some(memory) = &resources.memory {
+            self.args(["--memory", memory]);
+        }
+        if let Some(shm_size) = &resources.shm_size {
+            self.args(["--shm-size", shm_size]);
+        }
+        if let Some(pids) = resources.pids {
+            if engine.kind.is_kubernetes() {
+                msg_info.warn("the process limit of pods is set by the nodes with Kubernetes, ignoring `resources.pids`.")?;
+            } else {
+                self.args(["--pids-limit", &pids.to_string()]);
+            }
+        }
+
+        Ok(())
+    }
+
     fn add_mounts(
         &mut self,
         options: &DockerOptions,
         paths: &DockerPaths,
-        mount_cb: impl Fn(&mut Command, &Path, &Path) -> Result<()>,
+        mount_cb: impl Fn(&mut EngineCommand, &Path, &Path) -> Result<()>,
         mut store_cb: impl FnMut((String, String)),
         msg_info: &mut MessageInfo,
     ) -> Result<()> {
@@ -1203,6 +1644,16 @@ impl DockerCommandExt for Command {
             }
         }
 
+        // the socket can't be copied into the data volumes of a remote engine.
+        if options.config.forward_credentials() && !options.is_remote() {
+            if let Some(socket) = credentials::ssh_agent_socket() {
+                let host_path = paths.mount_finder.find_path(&socket, true)?;
+                let mount_path = Path::new(credentials::SSH_AUTH_SOCK_MOUNT);
+                mount_cb(self, host_path.as_ref(), mount_path)?;
+                self.args(["-e", &format!("SSH_AUTH_SOCK={}", mount_path.to_utf8()?)]);
+            }
+        }
+
         for path in paths.workspace_dependencies() {
             // NOTE: we use canonical paths here since cargo metadata
             // always canonicalizes paths, so these should be relative
@@ -1350,6 +1801,12 @@ pub fn get_image(
         ));
     };
 
+    if let Some(runner) = config.runner(target).map_err(GetImageError::Other)? {
+        runner
+            .validate(target.triple(), pick.runners)
+            .map_err(GetImageError::Other)?;
+    }
+
     let mut image: PossibleImage = pick.image_name(CROSS_IMAGE, version).into();
 
     if pick.platforms.is_empty() {
@@ -1360,13 +1817,39 @@ pub fn get_image(
     Ok(image)
 }
 
+pub const CROSS_CONTAINER_PATH_MAP: &str = "CROSS_CONTAINER_PATH_MAP";
+
+// the mounts given by `CROSS_CONTAINER_PATH_MAP`, as a comma-separated list
+// of `container-path:host-path`.
+fn parse_path_map(map: &str) -> Result<Vec<MountDetail>> {
+    map.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (destination, source) = entry
+                .trim()
+                .split_once(':')
+                .ok_or_else(|| eyre::eyre!("invalid path mapping `{entry}`"))
+                .with_suggestion(|| "use `container-path:host-path`")?;
+            let (destination, source) = (PathBuf::from(destination), PathBuf::from(source));
+            if !destination.is_absolute() || !source.is_absolute() {
+                eyre::bail!("paths must be absolute in path mapping `{entry}`");
+            }
+            Ok(MountDetail {
+                source,
+                destination,
+            })
+        })
+        .collect::<Result<_>>()
+        .wrap_err_with(|| format!("when parsing `{CROSS_CONTAINER_PATH_MAP}`"))
+}
+
 fn docker_read_mount_paths(
     engine: &Engine,
     msg_info: &mut MessageInfo,
 ) -> Result<Vec<MountDetail>> {
     let hostname = env::var("HOSTNAME").wrap_err("HOSTNAME environment variable not found")?;
 
-    let mut docker: Command = {
+    let mut docker: EngineCommand = {
         let mut command = engine.subcommand("inspect");
         command.arg(hostname);
         command
@@ -1374,16 +1857,38 @@ fn docker_read_mount_paths(
 
     let output = docker.run_and_get_stdout(msg_info)?;
     let info = serde_json::from_str(&output).wrap_err("failed to parse docker inspect output")?;
-    dockerinfo_parse_mounts(&info)
+    dockerinfo_parse_mounts(&info, engine.kind)
 }
 
-fn dockerinfo_parse_mounts(info: &serde_json::Value) -> Result<Vec<MountDetail>> {
+fn dockerinfo_parse_mounts(info: &serde_json::Value, kind: EngineType) -> Result<Vec<MountDetail>> {
     let mut mounts = dockerinfo_parse_user_mounts(info);
-    let root_info = dockerinfo_parse_root_mount_path(info)?;
+    let root_info = match kind {
+        EngineType::Nerdctl => dockerinfo_parse_root_mount_path(info)
+            .or_else(|_| nerdctlinfo_parse_root_mount_path(info))?,
+        _ => dockerinfo_parse_root_mount_path(info)?,
+    };
     mounts.push(root_info);
     Ok(mounts)
 }
 
+// nerdctl doesn't report the graph driver, but the root filesystem of a
+// running container is mounted in the directory of its task, in the
+// namespace the container was created in.
+fn nerdctlinfo_parse_root_mount_path(info: &serde_json::Value) -> Result<MountDetail> {
+    let id = info
+        .pointer("/0/Id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| eyre::eyre!("no container id found"))?;
+    let namespace = info
+        .pointer("/0/Config/Labels/nerdctl~1namespace")
+        .and_then(|v| v.as_str());
+
+    Ok(MountDetail {
+        source: nerdctl::container_rootfs(namespace, id),
+        destination: PathBuf::from("/"),
+    })
+}
+
 fn dockerinfo_parse_root_mount_path(info: &serde_json::Value) -> Result<MountDetail> {
     let driver_name = info
         .pointer("/0/GraphDriver/Name")
@@ -1425,6 +1930,118 @@ fn dockerinfo_parse_user_mounts(info: &serde_json::Value) -> Vec<MountDetail> {
         })
 }
 
+// unescape the octal escapes of spaces and other characters in mountinfo.
+fn mountinfo_unescape(field: &str) -> PathBuf {
+    let mut result = vec![];
+    let mut bytes = field.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'\\' {
+            let digits: Vec<u8> = bytes.by_ref().take(3).collect();
+            let value = std::str::from_utf8(&digits)
+                .ok()
+                .and_then(|d| u8::from_str_radix(d, 8).ok());
+            match value {
+                Some(value) => result.push(value),
+                None => {
+                    result.push(byte);
+                    result.extend(digits);
+                }
+            }
+        } else {
+            result.push(byte);
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&result).into_owned())
+}
+
+// the root filesystem of this container, from the overlay directories in
+// `/proc/self/mountinfo`.
+fn mountinfo_parse_root(mountinfo: &str) -> Result<PathBuf> {
+    for line in mountinfo.lines() {
+        let (fields, rest) = line
+            .split_once(" - ")
+            .ok_or_else(|| eyre::eyre!("invalid mountinfo line `{line}`"))?;
+        let fields: Vec<_> = fields.split(' ').collect();
+        let rest: Vec<_> = rest.split(' ').collect();
+        if fields.get(4) != Some(&"/") {
+            continue;
+        }
+        // docker's overlay directories are next to the merged directory.
+        return rest
+            .get(2)
+            .and_then(|opts| opts.split(',').find_map(|o| o.strip_prefix("upperdir=")))
+            .filter(|_| rest[0] == "overlay")
+            .and_then(|upper| upper.strip_suffix("/diff"))
+            .map(|dir| mountinfo_unescape(dir).join("merged"))
+            .ok_or_else(|| eyre::eyre!("could not find the root filesystem on the host"));
+    }
+
+    eyre::bail!("no root filesystem in mountinfo")
+}
+
+// the id of this container, from the files the engine mounts over
+// `/etc/hostname` and similar, which are in a directory named by the id,
+// like `/var/lib/docker/containers/<id>/hostname`.
+fn mountinfo_parse_container_id(mountinfo: &str) -> Result<String> {
+    for line in mountinfo.lines() {
+        let fields: Vec<_> = line.split(' ').collect();
+        if !matches!(
+            fields.get(4),
+            Some(&"/etc/hostname" | &"/etc/hosts" | &"/etc/resolv.conf")
+        ) {
+            continue;
+        }
+        let id = fields[3].split('/').find(|component| {
+            component.len() == 64 && component.bytes().all(|b| b.is_ascii_hexdigit())
+        });
+        if let Some(id) = id {
+            return Ok(id.to_owned());
+        }
+    }
+
+    eyre::bail!("no container id in mountinfo")
+}
+
+// the mounts of this container, found from `/proc/self/mountinfo`, for when
+// `HOSTNAME` isn't the container. the paths in mountinfo are relative to the
+// filesystems mounted in this container, and might not be on the host of the
+// engine, such as with the `docker:dind` service, so they're only used to
+// check that the container inspected is this one.
+fn mountinfo_read_mount_paths(
+    engine: &Engine,
+    msg_info: &mut MessageInfo,
+) -> Result<Vec<MountDetail>> {
+    let mountinfo = file::read("/proc/self/mountinfo")?;
+    let root = mountinfo_parse_root(&mountinfo)?;
+    let id = mountinfo_parse_container_id(&mountinfo)?;
+
+    let mut docker = engine.subcommand("inspect");
+    docker.arg(id);
+    let output = docker.run_and_get_stdout(msg_info)?;
+    let info = serde_json::from_str(&output).wrap_err("failed to parse docker inspect output")?;
+    dockerinfo_find_mounts(&info, &root, engine.kind)
+}
+
+// the mounts of the inspected container with the root filesystem `root`.
+fn dockerinfo_find_mounts(
+    info: &serde_json::Value,
+    root: &Path,
+    kind: EngineType,
+) -> Result<Vec<MountDetail>> {
+    info.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|container| {
+            dockerinfo_parse_mounts(&serde_json::Value::Array(vec![container.clone()]), kind).ok()
+        })
+        .find(|mounts| {
+            mounts
+                .iter()
+                .any(|mount| mount.destination == Path::new("/") && mount.source == root)
+        })
+        .ok_or_else(|| eyre::eyre!("no container of the engine has the root filesystem {root:?}"))
+}
+
 #[derive(Debug, Default)]
 pub struct MountFinder {
     mounts: Vec<MountDetail>,
@@ -1449,11 +2066,48 @@ impl MountFinder {
     }
 
     pub fn create(engine: &Engine, msg_info: &mut MessageInfo) -> Result<MountFinder> {
-        Ok(if engine.in_docker {
-            MountFinder::new(docker_read_mount_paths(engine, msg_info)?)
-        } else {
-            MountFinder::default()
-        })
+        if !engine.in_docker {
+            return Ok(MountFinder::default());
+        }
+        match MountFinder::read_mount_paths(engine, msg_info) {
+            Ok(mounts) => Ok(MountFinder::new(mounts)),
+            // data volumes are used instead, so the paths don't need to be mapped.
+            Err(_) if engine.is_remote => Ok(MountFinder::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like [`MountFinder::create`], but `None` if the paths in this
+    /// container can't be mapped to the host of the engine, which is needed
+    /// to mount them.
+    pub fn try_create(engine: &Engine, msg_info: &mut MessageInfo) -> Result<Option<MountFinder>> {
+        if !engine.in_docker {
+            return Ok(Some(MountFinder::default()));
+        }
+        if let Ok(map) = env::var(CROSS_CONTAINER_PATH_MAP) {
+            return parse_path_map(&map).map(|mounts| Some(MountFinder::new(mounts)));
+        }
+        Ok(MountFinder::read_mount_paths(engine, msg_info)
+            .ok()
+            .map(MountFinder::new))
+    }
+
+    /// If no paths are mapped to the host.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.mounts.is_empty()
+    }
+
+    // the mounts from `CROSS_CONTAINER_PATH_MAP`, or else from inspecting
+    // the current container, or else the container with the root filesystem
+    // of `/proc/self/mountinfo`.
+    fn read_mount_paths(engine: &Engine, msg_info: &mut MessageInfo) -> Result<Vec<MountDetail>> {
+        if let Ok(map) = env::var(CROSS_CONTAINER_PATH_MAP) {
+            return parse_path_map(&map);
+        }
+        // keep the error of the engine, which is more useful.
+        docker_read_mount_paths(engine, msg_info)
+            .or_else(|e| mountinfo_read_mount_paths(engine, msg_info).or(Err(e)))
     }
 
     pub fn find_mount_path(&self, path: impl AsRef<Path>) -> PathBuf {
@@ -1468,6 +2122,25 @@ impl MountFinder {
         path.to_path_buf()
     }
 
+    // the path in the container of a path inside the project, which is
+    // mounted from `host_root` to `mount_root`. both the path and the root
+    // are compared as paths on the host of the engine.
+    fn find_project_path(
+        &self,
+        path: &Path,
+        host_root: &Path,
+        mount_root: &str,
+    ) -> Result<Option<String>> {
+        match self.find_mount_path(path).strip_prefix(host_root) {
+            Ok(relative) => Ok(Some(format!(
+                "{}/{}",
+                mount_root.trim_end_matches('/'),
+                relative.as_posix_relative()?
+            ))),
+            Err(_) => Ok(None),
+        }
+    }
+
     fn find_path(&self, path: &Path, host: bool) -> Result<String> {
         if cfg!(target_os = "windows") && host {
             // On Windows, we can not mount the directory name directly.
@@ -1488,9 +2161,8 @@ pub const PATH_HASH_SHORT: usize = 5;
 /// Collision chance is ~10^-6
 pub const PATH_HASH_UNIQUE: usize = 10;
 
-fn path_digest(path: &Path) -> Result<const_sha1::Digest> {
-    let buffer = const_sha1::ConstBuffer::from_slice(path.to_utf8()?.as_bytes());
-    Ok(const_sha1::sha1(&buffer))
+fn path_digest(path: &Path) -> Result<sha1_smol::Digest> {
+    Ok(sha1_smol::Sha1::from(path.to_utf8()?).digest())
 }
 
 pub fn path_hash(path: &Path, count: usize) -> Result<String> {
@@ -1515,7 +2187,7 @@ mod tests {
         let rootless = "\"engine\"".to_owned();
 
         let test = |noroot, expected| {
-            let mut cmd = Command::new("engine");
+            let mut cmd = EngineCommand::new("engine");
             cmd.add_user_id(noroot);
             assert_eq!(expected, &format!("{cmd:?}"));
         };
@@ -1524,6 +2196,24 @@ mod tests {
         test(true, &rootless);
     }
 
+    #[test]
+    fn test_session_hash() -> Result<()> {
+        let hash = |args: &[&str]| {
+            let mut cmd = EngineCommand::new("engine");
+            cmd.args(args);
+            session_hash(&cmd)
+        };
+
+        let base = hash(&["run", "-v", "/a:/a", "image"])?;
+        assert_eq!(base.len(), PATH_HASH_UNIQUE);
+        // environment variables are passed on every exec
+        assert_eq!(base, hash(&["run", "-e", "A=1", "-v", "/a:/a", "image"])?);
+        assert_ne!(base, hash(&["run", "-v", "/b:/b", "image"])?);
+        assert_ne!(base, hash(&["run", "-v", "/a:/a", "other"])?);
+
+        Ok(())
+    }
+
     #[test]
     fn test_docker_userns() {
         let var = "CROSS_CONTAINER_USER_NAMESPACE";
@@ -1535,7 +2225,7 @@ mod tests {
         let none = "\"engine\"".to_owned();
 
         let test = |expected| {
-            let mut cmd = Command::new("engine");
+            let mut cmd = EngineCommand::new("engine");
             cmd.add_userns();
             assert_eq!(expected, &format!("{cmd:?}"));
         };
@@ -1786,6 +2476,129 @@ mod tests {
                 finder.find_mount_path("/project/target")
             );
         }
+
+        #[test]
+        fn test_find_project_path() -> Result<()> {
+            // the project is at `/project` in this container, and at
+            // `/home/project/path` on the host of the engine.
+            let finder = MountFinder::new(vec![MountDetail {
+                source: PathBuf::from("/home/project/path"),
+                destination: PathBuf::from("/project"),
+            }]);
+            let host_root = Path::new("/home/project/path");
+            let mount_root = "/home/project/path";
+            assert_eq!(
+                finder.find_project_path(
+                    Path::new("/project/tools/runner"),
+                    host_root,
+                    mount_root
+                )?,
+                Some("/home/project/path/tools/runner".to_owned())
+            );
+            assert_eq!(
+                finder.find_project_path(Path::new("/project/tools/runner"), host_root, "/mnt")?,
+                Some("/mnt/tools/runner".to_owned())
+            );
+            assert_eq!(
+                finder.find_project_path(Path::new("/usr/bin/runner"), host_root, mount_root)?,
+                None
+            );
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_path_map() -> Result<()> {
+        assert_eq!(
+            parse_path_map("/builds:/host/builds, /cache:/var/cache/ci")?,
+            vec![
+                MountDetail {
+                    source: PathBuf::from("/host/builds"),
+                    destination: PathBuf::from("/builds"),
+                },
+                MountDetail {
+                    source: PathBuf::from("/var/cache/ci"),
+                    destination: PathBuf::from("/cache"),
+                },
+            ]
+        );
+        assert!(parse_path_map("/builds").is_err());
+        assert!(parse_path_map("builds:/host/builds").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_mountinfo() -> Result<()> {
+        let mountinfo = r"1243 1114 0:118 / / rw,relatime master:424 - overlay overlay rw,lowerdir=/var/lib/docker/overlay2/l/ABC:/var/lib/docker/overlay2/l/DEF,upperdir=/var/lib/docker/overlay2/0a\0401b2c/diff,workdir=/var/lib/docker/overlay2/0a\0401b2c/work
+1244 1243 0:121 / /proc rw,nosuid,nodev,noexec,relatime - proc proc rw
+1252 1243 8:1 /home/user/project /project rw,relatime - ext4 /dev/sda1 rw";
+        assert_eq!(
+            mountinfo_parse_root(mountinfo)?,
+            PathBuf::from("/var/lib/docker/overlay2/0a 1b2c/merged")
+        );
+
+        // the root filesystem of a containerd snapshotter can't be found.
+        let mountinfo =
+            "1 0 0:1 / / rw - overlay overlay rw,upperdir=/var/lib/containerd/snapshots/42/fs";
+        assert!(mountinfo_parse_root(mountinfo).is_err());
+
+        let id = "f".repeat(64);
+        let mountinfo = format!(
+            "1252 1243 8:1 /home/user/project /project rw,relatime - ext4 /dev/sda1 rw
+1253 1243 8:1 /lib/docker/containers/{id}/hostname /etc/hostname rw,relatime - ext4 /dev/sda1 rw"
+        );
+        assert_eq!(mountinfo_parse_container_id(&mountinfo)?, id);
+        let mountinfo = format!("1 0 0:1 /containers/storage/overlay-containers/{id}/userdata/hosts /etc/hosts rw - ext4 /dev/sda1 rw");
+        assert_eq!(mountinfo_parse_container_id(&mountinfo)?, id);
+        assert!(
+            mountinfo_parse_container_id("1 0 0:1 / /etc/hostname rw - tmpfs tmpfs rw").is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_mounts() -> Result<()> {
+        let info = serde_json::json!([
+            {
+                "GraphDriver": {
+                    "Name": "overlay2",
+                    "Data": { "MergedDir": "/var/lib/docker/overlay2/other/merged" }
+                },
+                "Mounts": []
+            },
+            {
+                "GraphDriver": {
+                    "Name": "overlay2",
+                    "Data": { "MergedDir": "/var/lib/docker/overlay2/0a1b2c/merged" }
+                },
+                "Mounts": [
+                    { "Source": "/home/user/project", "Destination": "/project" }
+                ]
+            }
+        ]);
+        let root = Path::new("/var/lib/docker/overlay2/0a1b2c/merged");
+        assert_eq!(
+            dockerinfo_find_mounts(&info, root, EngineType::Docker)?,
+            vec![
+                MountDetail {
+                    source: PathBuf::from("/home/user/project"),
+                    destination: PathBuf::from("/project"),
+                },
+                MountDetail {
+                    source: root.to_owned(),
+                    destination: PathBuf::from("/"),
+                },
+            ]
+        );
+
+        // the engine doesn't run this container, such as a separate engine.
+        let root = Path::new("/var/lib/docker/overlay2/dind/merged");
+        assert!(dockerinfo_find_mounts(&info, root, EngineType::Docker).is_err());
+
+        Ok(())
     }
 
     mod parse_docker_inspect {
@@ -1812,6 +2625,91 @@ mod tests {
             assert_eq!(want, actual);
         }
 
+        #[test]
+        fn test_parse_nerdctl_inspect() {
+            // `nerdctl inspect` of a running container, in the default namespace
+            let info = json!([{
+                "Id": "8e0a1c6c2f6d4bb5a9c4f34b1bb2d4e6e5c1f0d2a3b4c5d6e7f8091a2b3c4d5e",
+                "Created": "2023-03-01T12:00:00.000000000Z",
+                "Path": "sh",
+                "Args": ["-c", "sleep infinity"],
+                "State": {
+                    "Status": "running",
+                    "Running": true,
+                    "Paused": false,
+                    "Restarting": false,
+                    "Pid": 4321,
+                    "ExitCode": 0,
+                    "FinishedAt": ""
+                },
+                "Image": "ghcr.io/cross-rs/cross:main",
+                "ResolvConfPath": "/var/lib/nerdctl/1935db59/containers/builds/8e0a1c6c2f6d/resolv.conf",
+                "HostnamePath": "/var/lib/nerdctl/1935db59/containers/builds/8e0a1c6c2f6d/hostname",
+                "LogPath": "/var/lib/nerdctl/1935db59/containers/builds/8e0a1c6c2f6d/8e0a1c6c2f6d-json.log",
+                "Name": "cross",
+                "RestartCount": 0,
+                "Driver": "overlayfs",
+                "Platform": "linux",
+                "AppArmorProfile": "",
+                "Mounts": [
+                    {
+                        "Type": "bind",
+                        "Source": "/home/user/project",
+                        "Destination": "/project",
+                        "Mode": "",
+                        "RW": true,
+                        "Propagation": "rprivate"
+                    },
+                    {
+                        "Type": "bind",
+                        "Source": "/run/containerd/containerd.sock",
+                        "Destination": "/run/containerd/containerd.sock",
+                        "Mode": "",
+                        "RW": true,
+                        "Propagation": "rprivate"
+                    }
+                ],
+                "Config": {
+                    "Hostname": "8e0a1c6c2f6d",
+                    "AttachStdin": false,
+                    "Labels": {
+                        "io.containerd.image.config.stop-signal": "SIGTERM",
+                        "nerdctl/hostname": "8e0a1c6c2f6d",
+                        "nerdctl/name": "cross",
+                        "nerdctl/namespace": "builds"
+                    }
+                },
+                "NetworkSettings": {
+                    "Ports": {},
+                    "GlobalIPv6Address": "",
+                    "GlobalIPv6PrefixLen": 0,
+                    "IPAddress": "10.4.0.12",
+                    "IPPrefixLen": 24,
+                    "MacAddress": "02:42:0a:04:00:0c",
+                    "Networks": {}
+                }
+            }]);
+            let actual = dockerinfo_parse_mounts(&info, EngineType::Nerdctl).unwrap();
+            assert_eq!(
+                actual,
+                vec![
+                    MountDetail {
+                        source: PathBuf::from("/home/user/project"),
+                        destination: PathBuf::from("/project"),
+                    },
+                    MountDetail {
+                        source: PathBuf::from("/run/containerd/containerd.sock"),
+                        destination: PathBuf::from("/run/containerd/containerd.sock"),
+                    },
+                    MountDetail {
+                        source: PathBuf::from("/run/containerd/io.containerd.runtime.v2.task/builds/8e0a1c6c2f6d4bb5a9c4f34b1bb2d4e6e5c1f0d2a3b4c5d6e7f8091a2b3c4d5e/rootfs"),
+                        destination: PathBuf::from("/"),
+                    },
+                ]
+            );
+            assert!(dockerinfo_parse_mounts(&info, EngineType::Docker).is_err());
+        }
+
         #[test]
         fn test_parse_empty_user_mounts() {
             let actual = dockerinfo_parse_user_mounts(&json!([{
@@ -0,0 +1,202 @@
+//! Run the container engine on a build host over `ssh`.
+//!
+//! Remote cross normally talks to the container engine through
+//! `DOCKER_HOST` or a podman connection, which exposes a root-equivalent
+//! socket over the network. Instead, every engine command can be run on
+//! the build host through the system `ssh` binary, with the project and
+//! build artifacts streamed through its stdin and stdout.
+
+use std::env;
+use std::ffi::OsStr;
+use std::process::Command;
+
+use super::EngineCommand;
+use crate::errors::*;
+use crate::extensions::CommandExt;
+use crate::shell::MessageInfo;
+
+pub const SSH: &str = "ssh";
+
+/// A build host to run the container engine on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SshHost {
+    /// The destination passed to `ssh`, such as `user@host` or `ssh://user@host:2222`.
+    pub destination: String,
+    /// Additional options passed to `ssh` before the destination.
+    pub options: Vec<String>,
+}
+
+impl SshHost {
+    pub fn new(destination: impl Into<String>, options: Vec<String>) -> Self {
+        SshHost {
+            destination: destination.into(),
+            options,
+        }
+    }
+
+    pub fn from_env() -> Result<Option<Self>> {
+        let destination = match env::var("CROSS_REMOTE_SSH") {
+            Ok(destination) if !destination.is_empty() => destination,
+            _ => return Ok(None),
+        };
+        let options = match env::var("CROSS_REMOTE_SSH_OPTS") {
+            Ok(options) => shell_words::split(&options)
+                .wrap_err("invalid value for `CROSS_REMOTE_SSH_OPTS`")?,
+            Err(_) => vec![],
+        };
+
+        Ok(Some(SshHost::new(destination, options)))
+    }
+
+    /// Create a command running `program` on the build host. The program
+    /// and the arguments added to the command are escaped for the shell
+    /// of the build host.
+    pub fn command(&self, program: impl AsRef<OsStr>) -> EngineCommand {
+        let mut command = Command::new(SSH);
+        // never allocate a TTY, since it would corrupt streamed archives,
+        // and fail rather than prompt for a password on every command.
+        command.args(["-T", "-o", "BatchMode=yes"]);
+        command.args(&self.options);
+        command.arg(&self.destination);
+        command.arg("--");
+        let mut command = EngineCommand::shell_escaped(command);
+        command.arg(program);
+        command
+    }
+
+    /// Write `contents` to `path` on the build host.
+    pub fn upload(&self, path: &str, contents: &[u8], msg_info: &mut MessageInfo) -> Result<()> {
+        // write to a temporary file first, since concurrent builds
+        // may be reading the file.
+        let mut command = self.command("sh");
+        command.args([
+            "-c",
+            r#"mkdir -p "$(dirname "$1")" && cat > "$1.$$" && mv -f "$1.$$" "$1""#,
+            "sh",
+            path,
+        ]);
+        let status = command.run_and_get_status_with_stdin(contents, msg_info, true)?;
+        command
+            .status_result(msg_info, status, None)
+            .wrap_err_with(|| format!("when uploading {path} to {}", self.destination))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_command() {
+        let host = SshHost::new("cross@builder", vec!["-p".to_owned(), "2222".to_owned()]);
+        let mut command = host.command("docker");
+        command.args(["exec", "container", "sh", "-c", "echo '$HOME' && ls"]);
+        assert_eq!(command.get_program(), SSH);
+        assert_eq!(
+            args(&command),
+            [
+                "-T",
+                "-o",
+                "BatchMode=yes",
+                "-p",
+                "2222",
+                "cross@builder",
+                "--",
+                "docker",
+                "exec",
+                "container",
+                "sh",
+                "-c",
+                r#"'echo '\''$HOME'\'' && ls'"#,
+            ]
+        );
+
+        // the engine on the host would read the variables from its own
+        // environment, so the values are passed instead.
+        let mut command = host.command("docker");
+        command
+            .pass_env("PATH")
+            .pass_env("CROSS_UNSET_TEST_VARIABLE")
+            .pass_env("TERM=it's");
+        let path = format!("PATH={}", env::var("PATH").unwrap_or_default());
+        assert_eq!(
+            args(&command)[7..],
+            [
+                "docker".to_owned(),
+                "-e".to_owned(),
+                shell_escape::unix::escape(path.into()).into_owned(),
+                "-e".to_owned(),
+                r#"'TERM=it'\''s'"#.to_owned(),
+            ]
+        );
+        let mut command = host.command("docker");
+        command.pass_env_value("TOKEN", "secret");
+        assert_eq!(args(&command)[8..], ["-e", "TOKEN=secret"]);
+
+        let mut command = EngineCommand::new("docker");
+        command.pass_env("PATH").pass_env_value("TOKEN", "secret");
+        assert_eq!(args(&command), ["-e", "PATH", "-e", "TOKEN"]);
+        assert!(command
+            .get_envs()
+            .any(|(k, v)| k == "TOKEN" && v == Some("secret".as_ref())));
+    }
+
+    // a stand-in for `ssh` running the command on localhost: like `sshd`,
+    // it joins the arguments after the destination with spaces and
+    // evaluates them with a shell.
+    #[test]
+    #[cfg(unix)]
+    fn test_fake_ssh() -> Result<()> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::file::ToUtf8;
+
+        let tempdir = tempfile::tempdir()?;
+        let ssh = tempdir.path().join("ssh");
+        fs::write(
+            &ssh,
+            "#!/bin/sh
+while [ \"$1\" != -- ]; do shift; done
+shift
+exec sh -c \"$*\"
+",
+        )?;
+        fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755))?;
+        let run = |command: &EngineCommand| -> Result<String> {
+            let mut command = {
+                let mut fake = Command::new(&ssh);
+                fake.args(command.get_args());
+                fake
+            };
+            command.run_and_get_stdout(&mut MessageInfo::default())
+        };
+
+        let host = SshHost::new("cross@localhost", vec![]);
+        let values = ["it's", "$HOME", "a  b", "; touch pwned", "*", "\"\\n"];
+        let mut command = host.command("printf");
+        command.arg("%s\\n").args(values);
+        assert_eq!(run(&command)?.lines().collect::<Vec<_>>(), values);
+
+        let path = tempdir.path().join("it's; touch pwned");
+        let mut command = host.command("sh");
+        command.args([
+            "-c",
+            r#"mkdir -p "$1" && cd "$1" && pwd"#,
+            "sh",
+            path.to_utf8()?,
+        ]);
+        assert_eq!(run(&command)?.trim_end(), path.to_utf8()?);
+        assert!(path.is_dir());
+        assert!(!tempdir.path().join("pwned").exists());
+
+        Ok(())
+    }
+}
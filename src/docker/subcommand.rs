@@ -0,0 +1,78 @@
+//! Run cargo subcommands allowed by `build.subcommands` in the container.
+//!
+//! The `bin` directory of the cargo home isn't mounted in the container,
+//! so subcommands installed on the host aren't available. If the image
+//! doesn't provide the subcommand, it's installed on demand into the
+//! cargo home, in a directory for the architecture of the container.
+//! Without network access, the cargo home is read-only, and only the
+//! subcommands installed by a previous build can be used.
+
+use crate::cargo::ExternalSubcommand;
+use crate::extensions::SafeCommand;
+use crate::CommandVariant;
+
+pub const SUBCOMMANDS_DIR: &str = "cross-subcommands";
+
+/// The command running `variant`, after installing the subcommand `name`
+/// if needed. The arguments to the cargo command are added to the
+/// returned command.
+pub(crate) fn safe_command(
+    name: &str,
+    external: &ExternalSubcommand,
+    variant: CommandVariant,
+) -> SafeCommand {
+    let package = external
+        .package
+        .clone()
+        .unwrap_or_else(|| format!("cargo-{name}"));
+    let mut cmd = SafeCommand::new("sh");
+    cmd.args(["-c", SUBCOMMAND_SCRIPT, "cross-subcommand", name])
+        .arg(&package)
+        .arg(&SUBCOMMANDS_DIR)
+        .arg(&variant.to_str());
+    cmd
+}
+
+// runs the cargo command given as arguments, installing the subcommand into
+// the cargo home if it's not in the `PATH`. arguments are the name of the
+// subcommand, the package providing it, and the directory to install it
+// into, relative to the cargo home, followed by the cargo command.
+const SUBCOMMAND_SCRIPT: &str = r#"set -e
+name="$1"
+package="$2"
+root="$CARGO_HOME/$3/$(uname -m)"
+shift 3
+
+if ! command -v "cargo-$name" >/dev/null 2>&1; then
+    if [ ! -x "$root/bin/cargo-$name" ]; then
+        # the cargo home is read-only without network access.
+        if [ ! -w "$CARGO_HOME" ]; then
+            echo "\`$package\` for \`cargo $name\` can't be installed into the read-only cargo home, build once without the strict sandbox or \`--offline-container\` to install it" >&2
+            exit 1
+        fi
+        echo "installing \`$package\` for \`cargo $name\`" >&2
+        # don't build into the target directory, or with the project's configuration.
+        (cd / && unset CARGO_TARGET_DIR && cargo install --locked --root "$root" "$package")
+    fi
+    export PATH="$root/bin:$PATH"
+fi
+exec "$@"
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_command() {
+        let external = ExternalSubcommand {
+            needs_docker: true,
+            needs_target_in_command: true,
+            needs_interpreter: true,
+            package: None,
+        };
+        let cmd = safe_command("nextest", &external, CommandVariant::Cargo);
+        assert!(format!("{cmd:?}")
+            .ends_with(" cross-subcommand nextest cargo-nextest cross-subcommands cargo"));
+    }
+}
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::fmt;
-use std::process::{Command, ExitStatus, Output};
+use std::io::Write;
+use std::process::{Command, ExitStatus, Output, Stdio};
 
 use crate::errors::*;
 use crate::shell::MessageInfo;
@@ -43,6 +44,13 @@ pub trait CommandExt {
         silence_stdout: bool,
     ) -> Result<ExitStatus>;
     #[track_caller]
+    fn run_and_get_status_with_stdin(
+        &mut self,
+        stdin: &[u8],
+        msg_info: &mut MessageInfo,
+        silence_stdout: bool,
+    ) -> Result<ExitStatus>;
+    #[track_caller]
     fn run_and_get_stdout(&mut self, msg_info: &mut MessageInfo) -> Result<String>;
     #[track_caller]
     fn run_and_get_output(&mut self, msg_info: &mut MessageInfo) -> Result<std::process::Output>;
@@ -157,7 +165,7 @@ impl CommandExt for Command {
     ) -> Result<ExitStatus> {
         self.debug(msg_info)?;
         if silence_stdout && !msg_info.is_verbose() {
-            self.stdout(std::process::Stdio::null());
+            self.stdout(Stdio::null());
         }
         Ok(self.status().map_err(|e| CommandError::CouldNotExecute {
             source: Box::new(e),
@@ -165,6 +173,36 @@ impl CommandExt for Command {
         })?)
     }
 
+    /// Runs the command to completion, writing `stdin` to its standard input
+    #[track_caller]
+    fn run_and_get_status_with_stdin(
+        &mut self,
+        stdin: &[u8],
+        msg_info: &mut MessageInfo,
+        silence_stdout: bool,
+    ) -> Result<ExitStatus> {
+        self.debug(msg_info)?;
+        if silence_stdout && !msg_info.is_verbose() {
+            self.stdout(Stdio::null());
+        }
+        let mut child =
+            self.stdin(Stdio::piped())
+                .spawn()
+                .map_err(|e| CommandError::CouldNotExecute {
+                    source: Box::new(e),
+                    command: self
+                        .command_pretty(msg_info, |cmd| STRIPPED_BINS.iter().any(|f| f == &cmd)),
+                })?;
+        let mut pipe = child.stdin.take().expect("stdin should be piped");
+        let result = pipe.write_all(stdin);
+        // closing stdin signals the end of the input
+        drop(pipe);
+        let status = child.wait()?;
+        result?;
+
+        Ok(status)
+    }
+
     /// Runs the command to completion and returns its stdout
     #[track_caller]
     fn run_and_get_stdout(&mut self, msg_info: &mut MessageInfo) -> Result<String> {
@@ -47,7 +47,7 @@ pub mod shell;
 pub mod temp;
 
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
 
 use cli::Args;
@@ -513,7 +513,7 @@ fn add_libc_version(triple: &str, zig_version: Option<&str>) -> String {
 }
 
 pub fn run(
-    args: Args,
+    mut args: Args,
     target_list: TargetList,
     msg_info: &mut MessageInfo,
 ) -> Result<Option<ExitStatus>> {
@@ -525,17 +525,62 @@ pub fn run(
         ))?;
     }
 
-    if let Some(Subcommand::Other(command)) = &args.subcommand {
-        msg_info.warn(format_args!(
-            "specified cargo subcommand `{command}` is not supported by `cross`."
-        ))?;
-        return Ok(None);
+    if let Some(split) = args.test_split {
+        if args.subcommand != Some(Subcommand::Test) {
+            eyre::bail!("running the tests in {split} is only supported by `cross test`");
+        }
+    }
+    if args.debug.is_some() && args.keep_alive {
+        eyre::bail!("`cross debug` cannot be used with `--keep-alive`");
+    }
+    if args.coverage && args.subcommand != Some(Subcommand::Test) {
+        eyre::bail!("`--coverage` is only supported by `cross test`");
+    }
+    if !args.reports.is_empty() {
+        if args.subcommand != Some(Subcommand::Test) {
+            eyre::bail!("`--report` is only supported by `cross test`");
+        }
+        // the test names are required, so the output format can't change.
+        let mut test_args = args.rest_args.iter().skip_while(|a| *a != "--");
+        if args.quiet
+            || test_args
+                .any(|a| matches!(a.as_str(), "-q" | "--quiet") || a.starts_with("--format"))
+        {
+            eyre::bail!("`--report` cannot be used with `--quiet` or `--format`");
+        }
     }
 
     let host_version_meta = rustc::version_meta()?;
 
     let cwd = std::env::current_dir()?;
-    if let Some(metadata) = cargo_metadata_with_args(None, Some(&args), msg_info)? {
+    // `cross install` usually runs outside of a package.
+    let metadata = match args.subcommand {
+        Some(Subcommand::Install) => Some(docker::install::metadata(
+            args.install_path.as_deref(),
+            args.target_dir.as_deref(),
+        )?),
+        _ => cargo_metadata_with_args(None, Some(&args), msg_info)?,
+    };
+    if let Some(metadata) = metadata {
+        if let Some(Subcommand::Other(command)) = &args.subcommand {
+            // any warnings are printed when setting up the configuration.
+            let toml = toml(&metadata, &mut Verbosity::Quiet.into())?;
+            match Config::new(Some(toml)).subcommand(command) {
+                Some(external) => {
+                    args.subcommand = Some(Subcommand::External(command.clone(), external));
+                }
+                None => {
+                    msg_info.warn(format_args!(
+                        "specified cargo subcommand `{command}` is not supported by `cross`."
+                    ))?;
+                    msg_info.note(format_args!(
+                        "to run it in the container, add it to `build.subcommands` in your cross configuration."
+                    ))?;
+                    return Ok(None);
+                }
+            }
+        }
+
         let CrossSetup {
             config,
             target,
@@ -545,7 +590,7 @@ pub fn run(
             zig_version,
             toolchain,
             is_remote,
-            engine,
+            mut engine,
             image,
         } = match setup(&host_version_meta, &metadata, &args, target_list, msg_info)? {
             Some(setup) => setup,
@@ -556,6 +601,13 @@ pub fn run(
 
         config.confusable_target(&target, msg_info)?;
 
+        // miri builds its own sysroot for the target.
+        let is_miri = args.subcommand == Some(Subcommand::Miri);
+        let uses_xargo = uses_xargo && !is_miri;
+        let uses_build_std = uses_build_std && !is_miri;
+        let uses_zig = uses_zig && !is_miri;
+        let zig_version = zig_version.filter(|_| !is_miri);
+
         let picked_generic_channel =
             matches!(toolchain.channel.as_str(), "stable" | "beta" | "nightly");
 
@@ -603,18 +655,42 @@ pub fn run(
                 uses_build_std,
             );
 
-            let needs_docker = args
-                .subcommand
-                .clone()
-                .map_or(false, |sc| sc.needs_docker(is_remote));
-            if target.needs_docker() && needs_docker {
-                let paths = docker::DockerPaths::create(
-                    &engine,
-                    metadata,
-                    cwd,
-                    toolchain.clone(),
-                    msg_info,
-                )?;
+            let needs_docker = target.needs_docker()
+                && args
+                    .subcommand
+                    .clone()
+                    .map_or(false, |sc| sc.needs_docker(is_remote));
+            // the paths of this container are only mapped if it runs the engine.
+            let mount_finder = match needs_docker {
+                true => Some(create_mount_finder(&mut engine, msg_info)?),
+                false => None,
+            };
+            let is_remote = engine.is_remote;
+            if args.debug.is_some() {
+                check_debug_support(&target, &config, is_remote)?;
+            }
+            if args.coverage && is_remote {
+                eyre::bail!("`--coverage` is not supported with remote container engines");
+            }
+            if args.offline_container && is_remote {
+                eyre::bail!("`--offline-container` is not supported with remote container engines");
+            }
+            if let Some(mount_finder) = mount_finder {
+                let paths =
+                    docker::DockerPaths::create(mount_finder, metadata, cwd, toolchain.clone())?;
+                let collect_crashes = docker::crash::enabled()
+                    && args
+                        .subcommand
+                        .clone()
+                        .map_or(false, |sc| sc.needs_interpreter());
+                let package_dirs = paths.directories.package_directories();
+                let crash_dir = docker::crash::host_crash_dir(package_dirs.target(), &target);
+                let coverage_dir =
+                    docker::coverage::host_coverage_dir(package_dirs.target(), &target);
+                let install_dir = docker::install::host_install_dir(package_dirs.target(), &target);
+                let mount_root = package_dirs.mount_root().to_owned();
+                let host_root = package_dirs.host_root().to_owned();
+                let has_network = !args.offline_container && !config.sandbox()?.is_strict();
                 let options = docker::DockerOptions::new(
                     engine,
                     target.clone(),
@@ -623,6 +699,16 @@ pub fn run(
                     crate::CommandVariant::create(uses_zig, uses_xargo)?,
                     rustc_version,
                     false,
+                    args.keep_alive,
+                    args.offline_container,
+                    docker::TestOptions {
+                        split: args.test_split,
+                        reports: args.reports.clone(),
+                        json: is_nightly,
+                        collect_crashes,
+                        coverage: args.coverage,
+                    },
+                    args.debug.clone(),
                 );
 
                 if msg_info.should_fail() {
@@ -636,6 +722,23 @@ pub fn run(
                     &options,
                     msg_info,
                 )?;
+                if let Some(debug) = &args.debug {
+                    let target_dir = paths.directories.package_directories().target();
+                    let package = match paths.metadata.workspace_members.as_slice() {
+                        [member] => paths
+                            .metadata
+                            .packages
+                            .iter()
+                            .find(|p| &p.id == member)
+                            .map(|p| p.name.as_str()),
+                        _ => None,
+                    };
+                    let binary =
+                        docker::debug::binary_path(&args.cargo_args, target_dir, &target, package)?;
+                    let sysroot = docker::debug::sysroot_dir(target_dir.to_utf8()?, &target);
+                    debug.print_instructions(&binary, Path::new(&sysroot), msg_info)?;
+                }
+                let start = std::time::SystemTime::now();
                 let status = if let Some(status) = docker::run(
                     options,
                     paths,
@@ -650,10 +753,27 @@ pub fn run(
                     return Ok(None);
                 };
 
-                let needs_host = args.subcommand.map_or(false, |sc| sc.needs_host(is_remote));
                 if !status.success() {
                     warn_on_failure(&target, &toolchain, msg_info)?;
+                    if !has_network {
+                        docker::sandbox::note_offline_failure(&target, msg_info)?;
+                    }
+                    if collect_crashes {
+                        docker::crash::report(&crash_dir, start, msg_info)?;
+                    }
+                }
+                if args.coverage {
+                    docker::coverage::finish(&coverage_dir, &mount_root, &host_root, msg_info)?;
                 }
+                if status.success() && args.subcommand == Some(Subcommand::Install) {
+                    let root = match &args.install_root {
+                        Some(root) => root.clone(),
+                        None => docker::install::default_root()?,
+                    };
+                    docker::install::copy_binaries(&install_dir, &root, msg_info)?;
+                }
+
+                let needs_host = args.subcommand.map_or(false, |sc| sc.needs_host(is_remote));
                 if !(status.success() && needs_host) {
                     return Ok(Some(status));
                 }
@@ -663,6 +783,27 @@ pub fn run(
     Ok(None)
 }
 
+/// Check if the binary can be run under a debug server for `cross debug`.
+fn check_debug_support(target: &Target, config: &Config, is_remote: bool) -> Result<()> {
+    if !target.needs_docker() {
+        return Err(eyre::eyre!(
+            "`cross debug` is only supported for targets built in a container"
+        ))
+        .with_suggestion(|| format!("debug binaries for `{target}` with a debugger on the host"));
+    }
+    if is_remote {
+        eyre::bail!("`cross debug` is not supported with remote container engines");
+    }
+    let kind = config.runner(target)?.and_then(|r| r.kind);
+    if kind == Some(docker::runner::RunnerKind::QemuSystem) {
+        return Err(eyre::eyre!(
+            "`cross debug` is not supported with the `qemu-system` runner"
+        ))
+        .with_suggestion(|| "use the `qemu-user` or `native` runner");
+    }
+    Ok(())
+}
+
 /// Check if an interpreter is needed and then install it.
 pub fn install_interpreter_if_needed(
     args: &Args,
@@ -749,6 +890,16 @@ pub fn get_filtered_args(
     if uses_build_std {
         filtered_args.push("-Zbuild-std".to_owned());
     }
+    // `cross debug` runs the binary with `cargo run`, under a debug server.
+    // the subcommand needs a target, so the arguments before it are kept.
+    if args.subcommand == Some(Subcommand::Debug) {
+        if let Some(arg) = args
+            .subcommand_index
+            .and_then(|index| filtered_args.get_mut(index))
+        {
+            *arg = "run".to_owned();
+        }
+    }
     filtered_args.extend(args.rest_args.iter().cloned());
     filtered_args
 }
@@ -806,8 +957,10 @@ To override the toolchain mounted in the image, set `target.{target}.image.toolc
     } else {
         default_toolchain
     };
-    let is_remote = docker::Engine::is_remote();
-    let engine = docker::Engine::new(None, Some(is_remote), msg_info)?;
+    let engine_config = config.engine();
+    let is_remote = docker::Engine::is_remote_or(engine_config.remote);
+    let engine = docker::Engine::new(None, Some(is_remote), &engine_config, msg_info)?;
+    let is_remote = engine.is_remote;
     let image = image.to_definite_with(&engine, msg_info);
     toolchain.replace_host(&image.platform);
     Ok(Some(CrossSetup {
@@ -824,6 +977,29 @@ To override the toolchain mounted in the image, set `target.{target}.image.toolc
     }))
 }
 
+/// Map the paths of the container `cross` runs in to the host of the
+/// engine. If they can't be mapped, the engine is used as a remote engine,
+/// with data volumes.
+fn create_mount_finder(
+    engine: &mut docker::Engine,
+    msg_info: &mut MessageInfo,
+) -> Result<docker::MountFinder> {
+    match docker::MountFinder::try_create(engine, msg_info)? {
+        Some(mount_finder) => Ok(mount_finder),
+        // data volumes are used instead, so the paths don't need to be mapped.
+        None if engine.is_remote => Ok(docker::MountFinder::default()),
+        None => {
+            msg_info.warn(format_args!("could not map the paths of this container to the host of the container engine, using data volumes instead."))?;
+            msg_info.note(format_args!(
+                "provide the mapping with `{}=container-path:host-path,...`",
+                docker::CROSS_CONTAINER_PATH_MAP
+            ))?;
+            engine.is_remote = true;
+            Ok(docker::MountFinder::default())
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CrossSetup {
     pub config: Config,
@@ -305,6 +305,24 @@ pub fn setup_components(
         {
             install_component("clippy", toolchain, msg_info)?;
         }
+        if args
+            .subcommand
+            .clone()
+            .map_or(false, |sc| sc == crate::Subcommand::Miri)
+        {
+            if !is_nightly {
+                return Err(eyre::eyre!("`cross miri` requires a nightly toolchain"))
+                    .with_suggestion(|| "use `cross +nightly miri`");
+            }
+            for component in ["miri", "rust-src"] {
+                if !component_is_installed(component, toolchain, msg_info)? {
+                    install_component(component, toolchain, msg_info)?;
+                }
+            }
+        }
+        if args.coverage && !component_is_installed("llvm-tools", toolchain, msg_info)? {
+            install_component("llvm-tools-preview", toolchain, msg_info)?;
+        }
     }
     Ok(())
 }
@@ -136,6 +136,50 @@ release: {version}
     );
 }
 
+#[test]
+fn debug_filtered_args() {
+    use crate::{cargo::Subcommand, cli::Args, config::Config, Target};
+
+    // the path of the manifest is stored before the subcommand.
+    let cargo_args = ["--manifest-path", "debug", "debug", "--bin", "debug"];
+    let target = Target::new_built_in("aarch64-unknown-linux-gnu");
+    let args = Args {
+        cargo_args: cargo_args.iter().map(|&arg| arg.to_owned()).collect(),
+        rest_args: vec![],
+        subcommand: Some(Subcommand::Debug),
+        subcommand_index: Some(2),
+        channel: None,
+        target: Some(target.clone()),
+        features: vec![],
+        target_dir: None,
+        manifest_path: None,
+        version: false,
+        keep_alive: false,
+        offline_container: false,
+        test_split: None,
+        reports: vec![],
+        debug: None,
+        coverage: false,
+        install_root: None,
+        install_path: None,
+        verbose: 0,
+        quiet: false,
+        color: None,
+    };
+    assert_eq!(
+        crate::get_filtered_args(None, &args, &target, &Config::new(None), false, false),
+        [
+            "--manifest-path",
+            "debug",
+            "run",
+            "--bin",
+            "debug",
+            "--target",
+            "aarch64-unknown-linux-gnu",
+        ]
+    );
+}
+
 #[test]
 fn check_newlines() -> crate::Result<()> {
     for file in walk_dir(get_cargo_workspace(), &[".git", "target"], |_| true) {
@@ -32,7 +32,8 @@ pub static PROVIDED_IMAGES: &[ProvidedImage] = &["#,
         ProvidedImage {{
             name: "{name}",
             platforms: &[{platform}],
-            sub: {sub}
+            sub: {sub},
+            runners: &[{runners}]
         }},"#,
             name = image_target.target.clone(),
             platform = &image_target
@@ -50,7 +51,15 @@ pub static PROVIDED_IMAGES: &[ProvidedImage] = &["#,
                 format!(r#"Some("{}")"#, sub)
             } else {
                 "None".to_string()
-            }
+            },
+            runners = image_target
+                .runners
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|r| format!(r#""{r}""#))
+                .collect::<Vec<_>>()
+                .join(", ")
         )
         .expect("writing to string should not fail")
     }